@@ -81,6 +81,93 @@ pub enum Biome {
     TheVoid = 127,
 }
 
+impl Biome {
+    /// The biome with a namespaced name, as used by 1.18 chunks. Biomes renamed in 1.18 are understood by both
+    /// names, and biomes added since 1.16 have no `Biome` so give `None`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.strip_prefix("minecraft:").unwrap_or(name);
+
+        Some(match name {
+            "ocean" => Biome::Ocean,
+            "deep_ocean" => Biome::DeepOcean,
+            "frozen_ocean" => Biome::FrozenOcean,
+            "deep_frozen_ocean" => Biome::DeepFrozenOcean,
+            "cold_ocean" => Biome::ColdOcean,
+            "deep_cold_ocean" => Biome::DeepColdOcean,
+            "lukewarm_ocean" => Biome::LukewarmOcean,
+            "deep_lukewarm_ocean" => Biome::DeepLukewarmOcean,
+            "warm_ocean" => Biome::WarmOcean,
+            "deep_warm_ocean" => Biome::DeepWarmOcean,
+            "river" => Biome::River,
+            "frozen_river" => Biome::FrozenRiver,
+            "beach" => Biome::Beach,
+            "stone_shore" | "stony_shore" => Biome::StoneShore,
+            "snowy_beach" => Biome::SnowyBeach,
+            "forest" => Biome::Forest,
+            "wooded_hills" => Biome::WoodedHills,
+            "flower_forest" => Biome::FlowerForest,
+            "birch_forest" => Biome::BirchForest,
+            "birch_forest_hills" => Biome::BirchForestHills,
+            "tall_birch_forest" | "old_growth_birch_forest" => Biome::TallBirchForest,
+            "tall_birch_hills" => Biome::TallBirchHills,
+            "dark_forest" => Biome::DarkForest,
+            "dark_forest_hills" => Biome::DarkForestHills,
+            "jungle" => Biome::Jungle,
+            "jungle_hills" => Biome::JungleHills,
+            "modified_jungle" => Biome::ModifiedJungle,
+            "jungle_edge" | "sparse_jungle" => Biome::JungleEdge,
+            "modified_jungle_edge" => Biome::ModifiedJungleEdge,
+            "bamboo_jungle" => Biome::BambooJungle,
+            "bamboo_jungle_hills" => Biome::BambooJungleHills,
+            "taiga" => Biome::Taiga,
+            "taiga_hills" => Biome::TaigaHills,
+            "taiga_mountains" => Biome::TaigaMountains,
+            "snowy_taiga" => Biome::SnowyTaiga,
+            "snowy_taiga_hills" => Biome::SnowyTaigaHills,
+            "snowy_taiga_mountains" => Biome::SnowyTaigaMountains,
+            "giant_tree_taiga" | "old_growth_pine_taiga" => Biome::GiantTreeTaiga,
+            "giant_tree_taiga_hills" => Biome::GiantTreeTaigaHills,
+            "giant_spruce_taiga" | "old_growth_spruce_taiga" => Biome::GiantSpruceTaiga,
+            "giant_spruce_taiga_hills" => Biome::GiantSpruceTaigaHills,
+            "mushroom_fields" => Biome::MushroomFields,
+            "mushroom_field_shore" => Biome::MushroomFieldShore,
+            "swamp" => Biome::Swamp,
+            "swamp_hills" => Biome::SwampHills,
+            "savanna" => Biome::Savanna,
+            "savanna_plateau" => Biome::SavannaPlateau,
+            "shattered_savanna" | "windswept_savanna" => Biome::ShatteredSavanna,
+            "shattered_savanna_plateau" => Biome::ShatteredSavannaPlateau,
+            "plains" => Biome::Plains,
+            "sunflower_plains" => Biome::SunflowerPlains,
+            "desert" => Biome::Desert,
+            "desert_hills" => Biome::DesertHills,
+            "desert_lakes" => Biome::DesertLakes,
+            "snowy_tundra" | "snowy_plains" => Biome::SnowyTundra,
+            "snowy_mountains" => Biome::SnowyMountains,
+            "ice_spikes" => Biome::IceSpikes,
+            "mountains" | "windswept_hills" => Biome::Mountains,
+            "wooded_mountains" | "windswept_forest" => Biome::WoodedMountains,
+            "gravelly_mountains" | "windswept_gravelly_hills" => Biome::GravellyMountains,
+            "modified_gravelly_mountains" => Biome::ModifiedGravellyMountains,
+            "mountain_edge" => Biome::MountainEdge,
+            "badlands" => Biome::Badlands,
+            "badlands_plateau" => Biome::BadlandsPlateau,
+            "modified_badlands_plateau" => Biome::ModifiedBadlandsPlateau,
+            "wooded_badlands_plateau" | "wooded_badlands" => Biome::WoodedBadlandsPlateau,
+            "modified_wooded_badlands_plateau" => Biome::ModifiedWoodedBadlandsPlateau,
+            "eroded_badlands" => Biome::ErodedBadlands,
+            "nether" | "nether_wastes" => Biome::Nether,
+            "the_end" => Biome::TheEnd,
+            "small_end_islands" => Biome::SmallEndIslands,
+            "end_midlands" => Biome::EndMidlands,
+            "end_highlands" => Biome::EndHighlands,
+            "end_barrens" => Biome::EndBarrens,
+            "the_void" => Biome::TheVoid,
+            _ => return None,
+        })
+    }
+}
+
 pub struct Climate {
     pub temperature: f64,
     pub rainfall: f64,
@@ -5,7 +5,7 @@ use bit_field::{BitArray, BitField};
 /// This requires the number of items in the palette of the section the blockstates came from. This is because
 /// blockstate is packed with on a bit-level granularity. If the maximum index in the palette fits in 5 bits, then
 /// every 5 bits of the blockstates will represent a block.
-////
+///
 /// In 1.15 there is no padding, so blocks bleed into one another, so remainder bits are tracked and handled for you.
 /// In 1.16 padding bits are used so that a block is always in a single 64-bit int.
 pub fn expand_blockstates(data: &[i64], palette_len: usize) -> Vec<u16> {
@@ -33,8 +33,11 @@ pub fn expand_heightmap(data: &[i64]) -> Vec<u16> {
     }
 }
 
-/// Expand generic bit-packed data in the 1.16 format, ie with padding bits.
+/// Expand generic bit-packed data in the 1.16 format, ie with padding bits. Nothing is stored with zero bits.
 pub fn expand_generic_1_16(data: &[i64], bits: usize) -> Vec<u16> {
+    if bits == 0 {
+        return vec![];
+    }
     let values_per_64bits = 64 / bits;
     let mut result: Vec<u16> = Vec::with_capacity(values_per_64bits * data.len());
 
@@ -49,18 +52,22 @@ pub fn expand_generic_1_16(data: &[i64], bits: usize) -> Vec<u16> {
     result
 }
 
-/// Expand generic bit-packed data in the 1.15 format, ie data potentially existing across two 64-bit ints.
+/// Expand generic bit-packed data in the 1.15 format, ie data potentially existing across two 64-bit ints. Nothing
+/// is stored with zero bits.
 pub fn expand_generic_1_15(data: &[i64], bits: usize) -> Vec<u16> {
+    if bits == 0 {
+        return vec![];
+    }
     let mut result: Vec<u16> = vec![0; (data.len() * 64) / bits];
 
     // Unfortunely make a copy here in order to treat the data as u64 rather than i64.
     // At some point we will change the parser to let us take the data as u64 rather than i64.
     let copy: Vec<_> = data.iter().map(|i| *i as u64).collect();
 
-    for i in 0..result.len() {
+    for (i, v) in result.iter_mut().enumerate() {
         let begin = i * bits;
         let end = begin + bits;
-        result[i] = copy.get_bits(begin..end) as u16;
+        *v = copy.get_bits(begin..end) as u16;
     }
 
     result
@@ -70,11 +77,73 @@ pub fn expand_generic_1_15(data: &[i64], bits: usize) -> Vec<u16> {
 ///
 /// See `anvil::expand_blockstates` for more information.
 pub fn bits_per_block(palette_len: usize) -> usize {
-    if palette_len < 16 {
-        4
-    } else {
-        std::cmp::max((palette_len as f64).log2().ceil() as usize, 4)
+    std::cmp::max(min_bits(palette_len), 4)
+}
+
+/// The fewest bits that can hold every index into a palette of the given length. A palette of one entry needs none.
+///
+/// Formats using packed indices usually have a minimum of their own, eg 4 bits for block states.
+pub fn min_bits(palette_len: usize) -> usize {
+    (usize::BITS - palette_len.saturating_sub(1).leading_zeros()) as usize
+}
+
+/// Pack block states for a section, the inverse of `expand_blockstates`. The 1.16 format is used, as the game has
+/// since 1.16.
+pub fn pack_blockstates(states: &[u16], palette_len: usize) -> Vec<i64> {
+    pack_generic_1_16(states, bits_per_block(palette_len))
+}
+
+/// Pack a heightmap, the inverse of `expand_heightmap`. The 1.16 format is used, as the game has since 1.16.
+pub fn pack_heightmap(heights: &[u16]) -> Vec<i64> {
+    pack_generic_1_16(heights, 9)
+}
+
+/// Pack values in the 1.16 format, ie with padding bits so no value spans two 64-bit ints. With zero bits, as for a
+/// single entry palette, nothing is stored.
+///
+/// # Panics
+///
+/// Panics if `bits` is more than 64, or if a value does not fit in `bits` bits.
+pub fn pack_generic_1_16(values: &[u16], bits: usize) -> Vec<i64> {
+    if bits == 0 {
+        return vec![];
+    }
+    debug_assert!(fits(values, bits), "value does not fit in {} bits", bits);
+    let values_per_64bits = 64 / bits;
+
+    values
+        .chunks(values_per_64bits)
+        .map(|chunk| {
+            let mut datum = 0u64;
+            for (i, v) in chunk.iter().enumerate() {
+                datum.set_bits(i * bits..(i + 1) * bits, *v as u64);
+            }
+            datum as i64
+        })
+        .collect()
+}
+
+/// Pack values in the 1.15 format, ie tightly so values can span two 64-bit ints. With zero bits nothing is stored.
+///
+/// # Panics
+///
+/// Panics if `bits` is more than 64, or if a value does not fit in `bits` bits.
+pub fn pack_generic_1_15(values: &[u16], bits: usize) -> Vec<i64> {
+    if bits == 0 {
+        return vec![];
+    }
+    debug_assert!(fits(values, bits), "value does not fit in {} bits", bits);
+    let mut packed = vec![0u64; (values.len() * bits).div_ceil(64)];
+
+    for (i, v) in values.iter().enumerate() {
+        packed.set_bits(i * bits..(i + 1) * bits, *v as u64);
     }
+
+    packed.into_iter().map(|v| v as i64).collect()
+}
+
+fn fits(values: &[u16], bits: usize) -> bool {
+    bits >= 16 || values.iter().all(|v| *v >> bits == 0)
 }
 
 #[cfg(test)]
@@ -124,6 +193,53 @@ mod tests {
 
         let actual = expand_heightmap(&input[..]);
         assert_eq!(&[128; 16 * 16][..], actual.as_slice());
+        assert_eq!(input, pack_generic_1_15(&actual, 9));
+    }
+
+    #[test]
+    fn pack_round_trips() {
+        let values: Vec<u16> = (0..4096).map(|i| (i * 7 % 20) as u16).collect();
+        let bits = bits_per_block(20);
+
+        let padded = pack_blockstates(&values, 20);
+        assert_eq!(342, padded.len());
+        assert_eq!(values, expand_blockstates(&padded, 20)[..4096]);
+
+        let tight = pack_generic_1_15(&values, bits);
+        assert_eq!(320, tight.len());
+        assert_eq!(values, expand_blockstates(&tight, 20));
+
+        let heights: Vec<u16> = (0..256).map(|i| (i * 3 % 384) as u16).collect();
+        let packed = pack_heightmap(&heights);
+        assert_eq!(37, packed.len());
+        assert_eq!(heights, expand_heightmap(&packed)[..256]);
+    }
+
+    #[test]
+    fn min_bits_per_entry() {
+        assert_eq!(0, min_bits(0));
+        assert_eq!(0, min_bits(1));
+        assert_eq!(1, min_bits(2));
+        assert_eq!(2, min_bits(3));
+        assert_eq!(2, min_bits(4));
+        assert_eq!(3, min_bits(5));
+        assert_eq!(8, min_bits(256));
+        assert_eq!(9, min_bits(257));
+    }
+
+    #[test]
+    fn zero_bits() {
+        let values = vec![0u16; 4096];
+        assert!(pack_generic_1_16(&values, min_bits(1)).is_empty());
+        assert!(pack_generic_1_15(&values, 0).is_empty());
+        assert!(expand_generic_1_16(&[], 0).is_empty());
+        assert!(expand_generic_1_15(&[0], 0).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn pack_value_too_wide() {
+        pack_generic_1_16(&[16], 4);
     }
 
     #[test]
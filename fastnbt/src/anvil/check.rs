@@ -0,0 +1,437 @@
+//! Validation and repair of region files.
+
+use super::*;
+use crate::nbt::tree;
+
+/// Something wrong with a chunk's entry in a region.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// The chunk's sectors overlap those of another chunk, given by its coordinates, or the header if `None`.
+    Overlap(Option<(usize, usize)>),
+    /// The chunk has an offset but no sectors.
+    BadLocation,
+    /// The chunk's sectors extend past the end of the file, or its data is cut short by it.
+    PastEnd,
+    /// The chunk's length is zero, or more than its sectors can hold.
+    BadLength(u32),
+    /// The chunk's header gives a compression scheme, by its id, that is not known.
+    UnknownCompressionScheme(u8),
+    /// The chunk is stored externally, but the external file could not be read.
+    MissingExternal,
+    /// The chunk's data could not be decompressed, or is not valid NBT.
+    Corrupt,
+    /// The chunk's `xPos` and `zPos` do not match its place in the region, or the region's own position if known.
+    WrongPosition(i32, i32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkProblem {
+    pub x: usize,
+    pub z: usize,
+    pub problem: Problem,
+}
+
+/// What was done to fix a chunk, see `Region::repair`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fix {
+    /// The chunk was removed from the region.
+    Dropped,
+    /// The chunk was intact, but moved to sectors of its own.
+    Relocated,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Repair {
+    pub x: usize,
+    pub z: usize,
+    pub fix: Fix,
+}
+
+/// The result of `Region::check` or `Region::repair`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Report {
+    /// Number of chunks present in the region.
+    pub chunks: usize,
+    pub problems: Vec<ChunkProblem>,
+    /// Fixes made, always empty for `Region::check`.
+    pub repairs: Vec<Repair>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl<S: Seek + Read> Region<S> {
+    /// Check every chunk in the region, reporting problems rather than failing. Errors are only returned if the
+    /// region itself can't be read, eg if it is shorter than its header.
+    ///
+    /// Regions given `ExternalChunks` know their own coordinates, so chunks copied in from another region are found
+    /// too. Otherwise positions are only checked within the region.
+    pub fn check(&mut self) -> Result<Report> {
+        let file_len = self.data.seek(SeekFrom::End(0))?;
+        // The last sector can be cut short, as long as the chunk's data is all there.
+        let file_sectors = file_len.div_ceil(SECTOR_SIZE as u64) as usize;
+        let header_sectors = HEADER_SIZE / SECTOR_SIZE;
+
+        let mut locations: Vec<_> = self
+            .chunk_locations()?
+            .into_iter()
+            .filter(|loc| loc.sector_count > 0 || loc.begin_sector > 0)
+            .collect();
+        locations.sort_by_key(|loc| (loc.begin_sector, loc.z, loc.x));
+
+        let mut report = Report {
+            chunks: locations.len(),
+            ..Default::default()
+        };
+        let mut owners: Vec<Option<(usize, usize)>> = vec![];
+
+        for loc in &locations {
+            let mut problem = |problem| {
+                report.problems.push(ChunkProblem {
+                    x: loc.x,
+                    z: loc.z,
+                    problem,
+                })
+            };
+
+            let end = loc.begin_sector + loc.sector_count;
+            if loc.sector_count == 0 {
+                problem(Problem::BadLocation);
+                continue;
+            }
+            if loc.begin_sector < header_sectors {
+                problem(Problem::Overlap(None));
+                continue;
+            }
+            if end > file_sectors {
+                problem(Problem::PastEnd);
+                continue;
+            }
+
+            if owners.len() < end {
+                owners.resize(end, None);
+            }
+            if let Some(owner) = owners[loc.begin_sector..end].iter().find_map(|o| *o) {
+                problem(Problem::Overlap(Some(owner)));
+            } else {
+                for o in &mut owners[loc.begin_sector..end] {
+                    *o = Some((loc.x, loc.z));
+                }
+            }
+
+            if let Some(p) = self.check_data(loc, file_len)? {
+                problem(p);
+            }
+        }
+
+        report.problems.sort_by_key(|p| (p.z, p.x));
+        Ok(report)
+    }
+
+    /// Check a chunk's data, assuming its sectors start within the file.
+    fn check_data(&mut self, loc: &ChunkLocation, file_len: u64) -> Result<Option<Problem>> {
+        let begin = (loc.begin_sector * SECTOR_SIZE) as u64;
+        let mut header = [0u8; 5];
+        self.data.seek(SeekFrom::Start(begin))?;
+        match self.data.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Ok(Some(Problem::PastEnd))
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        let meta = match ChunkMeta::new(&header) {
+            Ok(meta) => meta,
+            Err(Error::UnknownCompressionScheme(s)) => {
+                return Ok(Some(Problem::UnknownCompressionScheme(s)))
+            }
+            Err(e) => return Err(e),
+        };
+
+        if meta.len == 0 || 4 + meta.len as usize > loc.sector_count * SECTOR_SIZE {
+            return Ok(Some(Problem::BadLength(meta.len)));
+        }
+
+        let data = if meta.external {
+            match self.load_chunk_data(loc) {
+                Ok(data) => data,
+                Err(Error::NoExternalChunks(..)) | Err(Error::IO(_)) => {
+                    return Ok(Some(Problem::MissingExternal))
+                }
+                Err(e) => return Err(e),
+            }
+        } else {
+            // Only the chunk's own data, which need not fill its last sector.
+            let len = 4 + meta.len as usize;
+            if begin + len as u64 > file_len {
+                return Ok(Some(Problem::PastEnd));
+            }
+            let mut data = vec![0; len];
+            self.data.seek(SeekFrom::Start(begin))?;
+            self.data.read_exact(&mut data)?;
+            data
+        };
+
+        let root = match decompress_chunk(&data).map(tree::read_root) {
+            Ok(Ok((_, root))) => root,
+            _ => return Ok(Some(Problem::Corrupt)),
+        };
+
+        // Positions moved out of the Level compound in 1.18.
+        let level = root.get_compound("Level").unwrap_or(&root);
+        if let (Some(x_pos), Some(z_pos)) = (level.get_i32("xPos"), level.get_i32("zPos")) {
+            let matches = match &self.external {
+                Some(external) => {
+                    x_pos as isize == external.region_x * 32 + loc.x as isize
+                        && z_pos as isize == external.region_z * 32 + loc.z as isize
+                }
+                None => {
+                    x_pos.rem_euclid(32) as usize == loc.x && z_pos.rem_euclid(32) as usize == loc.z
+                }
+            };
+            if !matches {
+                return Ok(Some(Problem::WrongPosition(x_pos, z_pos)));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl<S: Seek + Read + Write> Region<S> {
+    /// Check the region and fix what can be fixed. Chunks that are intact but share sectors with another chunk are
+    /// moved to free sectors, and any other chunk with a problem is dropped from the region. Timestamps of moved
+    /// chunks are kept.
+    pub fn repair(&mut self) -> Result<Report> {
+        let mut report = self.check()?;
+
+        let mut broken: Vec<(usize, usize)> = report
+            .problems
+            .iter()
+            .filter(|p| !matches!(p.problem, Problem::Overlap(Some(_))))
+            .map(|p| (p.x, p.z))
+            .collect();
+        broken.dedup();
+
+        // Drop first, so their sectors can be reused when relocating.
+        for &(x, z) in &broken {
+            self.set_location(x, z, 0, 0)?;
+            self.set_timestamp(x, z, 0)?;
+            report.repairs.push(Repair {
+                x,
+                z,
+                fix: Fix::Dropped,
+            });
+        }
+
+        for p in &report.problems {
+            if !matches!(p.problem, Problem::Overlap(Some(_))) || broken.contains(&(p.x, p.z)) {
+                continue;
+            }
+
+            let loc = self.chunk_location(p.x, p.z)?;
+            let mut header = [0u8; 5];
+            self.load_chunk(&loc, &mut header)?;
+            let meta = ChunkMeta::new(&header)?;
+
+            // Only the chunk's own data is read, as its last sector can be cut short by the end of the file. The
+            // check has made sure it fits in the chunk's sectors.
+            let mut buf = vec![0u8; loc.sector_count * SECTOR_SIZE];
+            self.load_chunk(&loc, &mut buf[..4 + meta.len as usize])?;
+
+            let begin = self.find_free_sectors(loc.sector_count)?;
+            self.data
+                .seek(SeekFrom::Start((begin * SECTOR_SIZE) as u64))?;
+            self.data.write_all(&buf)?;
+            self.set_location(p.x, p.z, begin, loc.sector_count)?;
+
+            report.repairs.push(Repair {
+                x: p.x,
+                z: p.z,
+                fix: Fix::Relocated,
+            });
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tree::{Compound, Node};
+
+    fn chunk(x: i32, z: i32) -> Vec<u8> {
+        let mut c = Compound::new();
+        c.insert("xPos", Node::Int(x));
+        c.insert("zPos", Node::Int(z));
+
+        let mut buf = vec![];
+        tree::write_root(&mut buf, "", &c).unwrap();
+        buf
+    }
+
+    fn region() -> Result<Region<Cursor<Vec<u8>>>> {
+        let mut r = Region::create(Cursor::new(vec![]))?;
+        r.write_chunk(0, 0, &chunk(0, 0))?;
+        r.write_chunk(1, 0, &chunk(-31, 0))?;
+        r.write_chunk(2, 0, &chunk(2, 0))?;
+        Ok(r)
+    }
+
+    fn problems(r: &mut Region<Cursor<Vec<u8>>>) -> Result<Vec<(usize, usize, Problem)>> {
+        Ok(r.check()?
+            .problems
+            .into_iter()
+            .map(|p| (p.x, p.z, p.problem))
+            .collect())
+    }
+
+    #[test]
+    fn valid_region() -> Result<()> {
+        let report = region()?.check()?;
+        assert!(report.is_ok());
+        assert_eq!(3, report.chunks);
+        Ok(())
+    }
+
+    #[test]
+    fn detects_problems() -> Result<()> {
+        let mut r = region()?;
+        r.write_chunk(3, 0, &chunk(4, 0))?;
+        r.set_location(4, 0, 100, 1)?;
+        // Chunk 2 now shares chunk 0's sector, and so has its position too.
+        r.set_location(2, 0, 2, 1)?;
+        r.write_chunk(5, 0, b"not nbt")?;
+
+        assert_eq!(
+            vec![
+                (2, 0, Problem::Overlap(Some((0, 0)))),
+                (2, 0, Problem::WrongPosition(0, 0)),
+                (3, 0, Problem::WrongPosition(4, 0)),
+                (4, 0, Problem::PastEnd),
+                (5, 0, Problem::Corrupt),
+            ],
+            problems(&mut r)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn detects_bad_headers() -> Result<()> {
+        let mut r = region()?;
+        let begin = r.chunk_location(1, 0)?.begin_sector * SECTOR_SIZE;
+        r.data.get_mut()[begin + 4] = 9;
+        let begin = r.chunk_location(2, 0)?.begin_sector * SECTOR_SIZE;
+        r.data.get_mut()[begin..begin + 4].copy_from_slice(&5000u32.to_be_bytes());
+        r.set_location(0, 0, 1, 1)?;
+        r.set_location(3, 0, 10, 0)?;
+
+        assert_eq!(
+            vec![
+                (0, 0, Problem::Overlap(None)),
+                (1, 0, Problem::UnknownCompressionScheme(9)),
+                (2, 0, Problem::BadLength(5000)),
+                (3, 0, Problem::BadLocation),
+            ],
+            problems(&mut r)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_region() -> Result<()> {
+        let mut r = region()?;
+        let loc = r.chunk_location(2, 0)?;
+        let begin = loc.begin_sector * SECTOR_SIZE;
+        let len = 4 + ChunkMeta::new(&r.load_chunk_data(&loc)?)?.len as usize;
+
+        // The last sector cut short after the chunk's data is fine.
+        r.data.get_mut().truncate(begin + len);
+        assert!(r.check()?.is_ok());
+
+        // Cut into the data, or the header.
+        r.data.get_mut().truncate(begin + len - 1);
+        assert_eq!(vec![(2, 0, Problem::PastEnd)], problems(&mut r)?);
+        r.data.get_mut().truncate(begin + 2);
+        assert_eq!(vec![(2, 0, Problem::PastEnd)], problems(&mut r)?);
+        Ok(())
+    }
+
+    #[test]
+    fn position_in_known_region() -> Result<()> {
+        let mut r = region()?.with_external_chunks(ExternalChunks::new(".", -1, 0));
+        assert_eq!(
+            vec![
+                (0, 0, Problem::WrongPosition(0, 0)),
+                (2, 0, Problem::WrongPosition(2, 0)),
+            ],
+            problems(&mut r)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn repair_drops_and_relocates() -> Result<()> {
+        let mut r = region()?;
+        r.write_chunk(3, 0, b"not nbt")?;
+        // Chunk 1 now also claims chunk 2's sector, but both are intact.
+        r.set_location(1, 0, 3, 2)?;
+
+        let report = r.repair()?;
+        assert_eq!(
+            vec![
+                Repair {
+                    x: 3,
+                    z: 0,
+                    fix: Fix::Dropped
+                },
+                Repair {
+                    x: 2,
+                    z: 0,
+                    fix: Fix::Relocated
+                },
+            ],
+            report.repairs
+        );
+
+        // Moved into the sectors freed by dropping chunk 3.
+        assert_eq!(5, r.chunk_location(2, 0)?.begin_sector);
+        let report = r.check()?;
+        assert!(report.is_ok());
+        assert_eq!(3, report.chunks);
+        Ok(())
+    }
+
+    #[test]
+    fn repair_relocates_truncated_chunk() -> Result<()> {
+        let mut r = region()?;
+        let loc = r.chunk_location(2, 0)?;
+        let data = r.load_chunk_data(&loc)?;
+        let len = 4 + ChunkMeta::new(&data)?.len as usize;
+        r.data
+            .get_mut()
+            .truncate(loc.begin_sector * SECTOR_SIZE + len);
+        r.set_location(1, 0, 3, 2)?;
+
+        let report = r.repair()?;
+        assert_eq!(
+            vec![Repair {
+                x: 2,
+                z: 0,
+                fix: Fix::Relocated
+            }],
+            report.repairs
+        );
+        assert!(r.check()?.is_ok());
+
+        let moved = r.chunk_location(2, 0)?;
+        assert_ne!(loc.begin_sector, moved.begin_sector);
+        assert_eq!(data[..len], r.load_chunk_data(&moved)?[..len]);
+        Ok(())
+    }
+}
@@ -0,0 +1,2250 @@
+//! The contents of a chunk, as stored in region files.
+
+use super::biome::Biome;
+use super::bits;
+use super::draw::{DrawError, DrawResult};
+use super::heightmap::Heightmap;
+use crate::block::{self, legacy, BlockState};
+use crate::entity::Entity;
+use crate::item::{self, ItemStack};
+use crate::nbt::tree::{self, Compound, Node};
+use crate::nbt::Tag;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::Read;
+use std::sync::{Arc, OnceLock};
+
+/// The data version of 21w43a, the 1.18 snapshot that moved everything out of the chunk's `Level` compound and gave
+/// sections their own biomes.
+pub const CHUNK_1_18_DATA_VERSION: i32 = 2844;
+
+/// The data version of 1.14, the first to store `isLightOn`. Light in older chunks was always computed before they
+/// were saved.
+pub const LIGHT_ON_DATA_VERSION: i32 = 1952;
+
+/// The data version of 1.13, the first to store blocks with palettes rather than numeric ids.
+pub const FLATTENING_DATA_VERSION: i32 = 1519;
+
+/// The data version of 20w17a, the 1.16 snapshot that stopped packed values from spanning two longs.
+pub const PADDED_DATA_VERSION: i32 = 2529;
+
+/// A chunk of a world. Blocks, biomes and heights are decoded for lookup, and the rest of the chunk is modelled as
+/// far as it is common between versions. Everything is also available as it was read from `Chunk::raw`.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub data_version: Option<i32>,
+    /// Chunk coordinates, in chunks rather than blocks.
+    pub x: i32,
+    pub z: i32,
+    /// How far generation has got, eg `full` for chunks ready to be played in.
+    pub status: Option<String>,
+    /// Game tick at which the chunk was last saved.
+    pub last_update: i64,
+    /// Ticks spent by players in the chunk, summed over players. Regional difficulty rises with it.
+    pub inhabited_time: i64,
+    pub block_entities: Vec<BlockEntity>,
+    /// Entities stored in the chunk itself, as done before 1.17. Since then they are in separate region files, see
+    /// `entity::read_region`.
+    pub entities: Vec<Entity>,
+    /// Entities that could not be read, eg for having no id, kept to be written back. See `Chunk::unread_entities`.
+    unread_entities: Vec<Compound>,
+    /// Scheduled block updates, eg of redstone repeaters.
+    pub block_ticks: Vec<Tick>,
+    /// Scheduled updates of flowing water and lava.
+    pub liquid_ticks: Vec<Tick>,
+    pub structures: Structures,
+    /// Blocks to update once neighbouring chunks are generated, per section. Each position is packed into the low
+    /// 12 bits as `x | z << 4 | y << 8`, relative to the section.
+    pub post_processing: Vec<Vec<i16>>,
+    /// Whether the game has computed the chunk's light. If not, the light stored can't be trusted and the light
+    /// lookups give `None`.
+    pub light_on: bool,
+    /// Heightmaps by `Heightmap` kind, relative to `min_y`, as stored or recomputed. Missing if not stored.
+    heightmaps: [Option<Vec<u16>>; 6],
+    /// Whether blocks were set since the heightmaps were read or recomputed.
+    heightmaps_stale: bool,
+    /// Sections by y, starting from `min_section`.
+    sections: Vec<Option<Section>>,
+    min_section: i8,
+    min_y: isize,
+    max_y: isize,
+    biomes: Option<Vec<i32>>,
+    format: Format,
+    raw: Compound,
+    /// Whether parts of the chunk were left out when reading it, see `Chunk::read_for_drawing`.
+    partial: bool,
+}
+
+/// How a chunk is laid out, which it is written back in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    /// Numeric block ids, before 1.13.
+    Legacy,
+    /// Palettes, within a `Level` compound.
+    Level,
+    /// Palettes for blocks and biomes, without a `Level` compound, since 1.18.
+    Flat,
+}
+
+/// A 16x16x16 section of a chunk.
+#[derive(Debug, Clone)]
+pub struct Section {
+    /// Palette indices of each block. Empty if the section only holds light.
+    pub states: Vec<u16>,
+    /// Block states used in the section, interned with `block::intern`.
+    pub palette: Vec<Arc<BlockState>>,
+    /// Section y, so the section spans blocks `16 * y` to `16 * y + 15`. Negative below y 0, as in 1.18 worlds.
+    pub y: i8,
+    /// Light from blocks such as torches, four bits per block.
+    pub block_light: Option<Vec<u8>>,
+    /// Light from the sky, four bits per block.
+    pub sky_light: Option<Vec<u8>>,
+    /// Palette indices of the biome of each 4x4x4 cell, stored by x then z then y. Empty before 1.18, when biomes
+    /// were stored for the whole chunk.
+    pub biomes: Vec<u16>,
+    /// Namespaced biomes used in the section, eg `minecraft:plains`.
+    pub biome_palette: Vec<String>,
+    /// Ids and data values the blocks were read with before 1.13.
+    legacy: Option<LegacyBlocks>,
+}
+
+/// Numeric ids and data values of a section from before 1.13, as read. Several of them can read as the same state,
+/// so they are written back for every block still holding the state it was read as.
+#[derive(Debug, Clone)]
+struct LegacyBlocks {
+    blocks: Vec<i8>,
+    add: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl LegacyBlocks {
+    fn get(&self, i: usize) -> (u16, u8) {
+        let block = self.blocks.get(i).map_or(0, |b| *b as u8);
+        (
+            block as u16 | (nibble(&self.add, i) as u16) << 8,
+            nibble(&self.data, i),
+        )
+    }
+
+    fn set(&mut self, i: usize, (id, value): (u16, u8)) {
+        if let Some(block) = self.blocks.get_mut(i) {
+            *block = id as u8 as i8;
+        }
+        set_nibble(&mut self.add, i, (id >> 8) as u8);
+        set_nibble(&mut self.data, i, value);
+    }
+}
+
+/// Entries of a chunk left out by `Chunk::read_for_drawing`, in every layout.
+const UNDRAWN: &[&str] = &[
+    "Entities",
+    "TileEntities",
+    "block_entities",
+    "TileTicks",
+    "LiquidTicks",
+    "block_ticks",
+    "fluid_ticks",
+    "ToBeTicked",
+    "LiquidsToBeTicked",
+    "Structures",
+    "structures",
+    "PostProcessing",
+    "CarvingMasks",
+];
+
+/// Names of a chunk's fields, which changed when the `Level` compound was removed.
+struct Layout {
+    sections: &'static str,
+    block_entities: &'static str,
+    block_ticks: &'static str,
+    liquid_ticks: &'static str,
+    structures: &'static str,
+}
+
+const LAYOUT_1_16: Layout = Layout {
+    sections: "Sections",
+    block_entities: "TileEntities",
+    block_ticks: "TileTicks",
+    liquid_ticks: "LiquidTicks",
+    structures: "Structures",
+};
+
+const LAYOUT_1_18: Layout = Layout {
+    sections: "sections",
+    block_entities: "block_entities",
+    block_ticks: "block_ticks",
+    liquid_ticks: "fluid_ticks",
+    structures: "structures",
+};
+
+/// A block entity, such as a chest or sign. Fields beyond those common to every block entity are in
+/// `BlockEntity::raw`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockEntity {
+    /// Namespaced id, eg `minecraft:chest`.
+    pub id: String,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    raw: Compound,
+}
+
+impl BlockEntity {
+    pub fn from_compound(raw: Compound) -> Self {
+        Self {
+            id: raw.get_str("id").unwrap_or_default().to_owned(),
+            x: raw.get_i32("x").unwrap_or_default(),
+            y: raw.get_i32("y").unwrap_or_default(),
+            z: raw.get_i32("z").unwrap_or_default(),
+            raw,
+        }
+    }
+
+    pub fn raw(&self) -> &Compound {
+        &self.raw
+    }
+
+    /// The items of a container such as a chest, furnace or hopper. Empty for other block entities.
+    pub fn items(&self) -> Vec<ItemStack> {
+        item::read_list(&self.raw, "Items")
+    }
+
+    /// Replace the items of a container.
+    pub fn set_items(&mut self, items: &[ItemStack]) {
+        self.raw.insert("Items", item::to_list(items));
+    }
+
+    /// The block entity's NBT, with its modelled fields written over those read.
+    pub fn to_compound(&self) -> Compound {
+        let mut c = self.raw.clone();
+        c.insert("id", Node::String(self.id.clone()));
+        c.insert("x", Node::Int(self.x));
+        c.insert("y", Node::Int(self.y));
+        c.insert("z", Node::Int(self.z));
+        c
+    }
+}
+
+/// A scheduled update of a block or liquid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tick {
+    /// Id of the block or fluid to update.
+    pub id: String,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    /// Ticks until the update, which can be negative if it is overdue.
+    pub delay: i32,
+    /// Lower values are updated first.
+    pub priority: i32,
+    /// Numeric id the tick was read with before 1.13, written back as long as `id` still names that block.
+    pub legacy_id: Option<u16>,
+}
+
+impl Tick {
+    pub fn from_compound(c: &Compound) -> Self {
+        Self {
+            // Numeric ids before 1.13.
+            id: match c.get_i32("i") {
+                Some(id) => legacy_name(id as u16),
+                None => c.get_str("i").unwrap_or_default().to_owned(),
+            },
+            x: c.get_i32("x").unwrap_or_default(),
+            y: c.get_i32("y").unwrap_or_default(),
+            z: c.get_i32("z").unwrap_or_default(),
+            delay: c.get_i32("t").unwrap_or_default(),
+            priority: c.get_i32("p").unwrap_or_default(),
+            legacy_id: c.get_i32("i").map(|id| id as u16),
+        }
+    }
+
+    /// Write the tick, with a numeric id if `legacy` for chunks from before 1.13. Fails for blocks that didn't exist
+    /// then.
+    pub fn to_compound(&self, legacy: bool) -> DrawResult<Compound> {
+        let mut c = Compound::new();
+        if legacy {
+            let id = match self.legacy_id {
+                Some(id) if legacy_name(id) == self.id => id,
+                _ => {
+                    legacy::legacy_id(&BlockState::new(self.id.as_str()))
+                        .ok_or_else(|| DrawError::NoLegacyId(self.id.clone()))?
+                        .0
+                }
+            };
+            c.insert("i", Node::Int(id as i32));
+        } else {
+            c.insert("i", Node::String(self.id.clone()));
+        }
+        c.insert("x", Node::Int(self.x));
+        c.insert("y", Node::Int(self.y));
+        c.insert("z", Node::Int(self.z));
+        c.insert("t", Node::Int(self.delay));
+        c.insert("p", Node::Int(self.priority));
+        Ok(c)
+    }
+}
+
+/// The structures of a chunk.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Structures {
+    /// Structures generated from this chunk.
+    pub starts: Vec<StructureStart>,
+    /// Structures overlapping this chunk, by name, with the chunk each starts in.
+    pub references: Vec<(String, Vec<(i32, i32)>)>,
+}
+
+/// A structure starting in a chunk, eg a village. Its pieces are in `StructureStart::raw`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureStart {
+    /// Name of the structure, eg `Village` before 1.16 or `minecraft:village` after.
+    pub name: String,
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    raw: Compound,
+}
+
+impl StructureStart {
+    pub fn raw(&self) -> &Compound {
+        &self.raw
+    }
+}
+
+impl Structures {
+    pub fn from_compound(c: &Compound) -> Self {
+        let mut structures = Self::default();
+
+        for (name, start) in c
+            .get_compound("Starts")
+            .or_else(|| c.get_compound("starts"))
+            .map(Compound::iter)
+            .into_iter()
+            .flatten()
+        {
+            let start = match start.as_compound() {
+                // Chunks without the structure store it with this id.
+                Some(start) if start.get_str("id") != Some("INVALID") => start,
+                _ => continue,
+            };
+
+            structures.starts.push(StructureStart {
+                name: name.to_owned(),
+                chunk_x: start.get_i32("ChunkX").unwrap_or_default(),
+                chunk_z: start.get_i32("ChunkZ").unwrap_or_default(),
+                raw: start.clone(),
+            });
+        }
+
+        for (name, refs) in c
+            .get_compound("References")
+            .map(Compound::iter)
+            .into_iter()
+            .flatten()
+        {
+            // Chunk positions are packed with x in the low half and z in the high.
+            let chunks = refs
+                .as_long_array()
+                .unwrap_or_default()
+                .iter()
+                .map(|p| (*p as i32, (*p >> 32) as i32))
+                .collect();
+            structures.references.push((name.to_owned(), chunks));
+        }
+
+        structures
+    }
+
+    /// Write the structures, with the lowercase `starts` used since 1.18 if `flat`.
+    pub fn to_compound(&self, flat: bool) -> Compound {
+        let mut starts = Compound::new();
+        for start in &self.starts {
+            let mut c = start.raw.clone();
+            c.insert("ChunkX", Node::Int(start.chunk_x));
+            c.insert("ChunkZ", Node::Int(start.chunk_z));
+            starts.insert(start.name.as_str(), Node::Compound(c));
+        }
+
+        let mut references = Compound::new();
+        for (name, chunks) in &self.references {
+            let packed = chunks
+                .iter()
+                .map(|(x, z)| (*x as u32 as i64) | ((*z as i64) << 32))
+                .collect();
+            references.insert(name.as_str(), Node::LongArray(packed));
+        }
+
+        let mut c = Compound::new();
+        c.insert(
+            if flat { "starts" } else { "Starts" },
+            Node::Compound(starts),
+        );
+        c.insert("References", Node::Compound(references));
+        c
+    }
+}
+
+impl Chunk {
+    /// Create a chunk from its parts. The height of the world is taken to be 0 to 256, extended to cover any
+    /// sections holding blocks outside of that, see `Chunk::set_height`. Heights are the `WORLD_SURFACE` heightmap,
+    /// relative to the bottom of the world, and can be left empty to compute them from the blocks.
+    pub fn new(heights: Vec<u16>, sections: Vec<Section>, biomes: Option<Vec<i32>>) -> Self {
+        let blocks = sections.iter().filter(|s| !s.states.is_empty());
+        let min_y = blocks
+            .clone()
+            .map(|s| s.y as isize * 16)
+            .fold(0, isize::min);
+        let max_y = blocks.map(|s| s.y as isize * 16 + 16).fold(256, isize::max);
+
+        // Sections only holding light can be just outside the world.
+        let min_section = sections.iter().map(|s| s.y).fold(0, i8::min);
+        let max_section = sections.iter().map(|s| s.y).fold(15, i8::max);
+
+        let mut s = Vec::new();
+        s.resize_with(
+            (max_section as isize - min_section as isize + 1) as usize,
+            || None,
+        );
+
+        for sec in sections {
+            let i = (sec.y as isize - min_section as isize) as usize;
+            s[i] = Some(sec);
+        }
+
+        let mut chunk = Self {
+            data_version: None,
+            x: 0,
+            z: 0,
+            status: None,
+            last_update: 0,
+            inhabited_time: 0,
+            block_entities: vec![],
+            entities: vec![],
+            unread_entities: vec![],
+            block_ticks: vec![],
+            liquid_ticks: vec![],
+            structures: Structures::default(),
+            post_processing: vec![],
+            light_on: true,
+            heightmaps: Default::default(),
+            heightmaps_stale: false,
+            sections: s,
+            min_section,
+            min_y,
+            max_y,
+            biomes,
+            format: Format::Flat,
+            raw: Compound::new(),
+            partial: false,
+        };
+
+        if !heights.is_empty() {
+            chunk.heightmaps[Heightmap::WorldSurface as usize] = Some(heights);
+        }
+        chunk
+    }
+
+    /// Read a chunk from its root compound, as found in a region file. Both the layout used since 1.18 and the
+    /// older one with everything in a `Level` compound are understood.
+    pub fn from_compound(root: Compound) -> DrawResult<Self> {
+        let flat = match root.get_i32("DataVersion") {
+            Some(version) => version >= CHUNK_1_18_DATA_VERSION,
+            None => !root.contains("Level"),
+        };
+        let (level, layout) = match root.get_compound("Level") {
+            Some(level) if !flat => (level, &LAYOUT_1_16),
+            _ => (&root, &LAYOUT_1_18),
+        };
+
+        let mut sections = vec![];
+        for section in compounds(level, layout.sections) {
+            if let Some(section) = Section::from_compound(section)? {
+                sections.push(section);
+            }
+        }
+
+        let ticks = |name| compounds(level, name).map(Tick::from_compound).collect();
+
+        let mut chunk = Self::new(vec![], sections, biomes(level));
+        chunk.format = match root.get_i32("DataVersion") {
+            _ if flat => Format::Flat,
+            Some(version) if version < FLATTENING_DATA_VERSION => Format::Legacy,
+            Some(_) => Format::Level,
+            // Chunks from before 1.9 have no data version.
+            None => Format::Legacy,
+        };
+
+        // Only stored since 1.18, where the bottom of the world might be empty but is still saved.
+        if let Some(y) = level.get_i32("yPos") {
+            chunk.min_y = y as isize * 16;
+        }
+
+        if let Some(stored) = level.get_compound("Heightmaps") {
+            for kind in Heightmap::ALL {
+                chunk.heightmaps[kind as usize] = stored
+                    .get_long_array(kind.name())
+                    .map(|h| expand_heights(h, chunk.height()));
+            }
+        }
+
+        chunk.data_version = root.get_i32("DataVersion");
+        chunk.x = level.get_i32("xPos").unwrap_or_default();
+        chunk.z = level.get_i32("zPos").unwrap_or_default();
+        chunk.status = level.get_str("Status").map(|s| s.to_owned());
+        chunk.last_update = level.get_i64("LastUpdate").unwrap_or_default();
+        chunk.inhabited_time = level.get_i64("InhabitedTime").unwrap_or_default();
+        chunk.block_entities = compounds(level, layout.block_entities)
+            .map(|c| BlockEntity::from_compound(c.clone()))
+            .collect();
+        for c in compounds(level, "Entities") {
+            match Entity::from_compound(c.clone()) {
+                Ok(entity) => chunk.entities.push(entity),
+                Err(_) => chunk.unread_entities.push(c.clone()),
+            }
+        }
+        chunk.block_ticks = ticks(layout.block_ticks);
+        chunk.liquid_ticks = ticks(layout.liquid_ticks);
+        chunk.structures = level
+            .get_compound(layout.structures)
+            .map(Structures::from_compound)
+            .unwrap_or_default();
+        chunk.light_on = match level
+            .get_i8("isLightOn")
+            .or_else(|| level.get_i8("LightPopulated"))
+        {
+            Some(on) => on != 0,
+            None => !matches!(chunk.data_version, Some(v) if v >= LIGHT_ON_DATA_VERSION),
+        };
+        chunk.post_processing = level
+            .get_list("PostProcessing")
+            .unwrap_or_default()
+            .iter()
+            .map(|l| {
+                l.as_list()
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(Node::as_i16)
+                    .collect()
+            })
+            .collect();
+
+        chunk.raw = root;
+        Ok(chunk)
+    }
+
+    /// Read a chunk's NBT, leaving out the parts drawing never looks at: entities, block entities, ticks and
+    /// structures. These are skipped without building nodes for them, which makes reading much cheaper. The chunk
+    /// can't be written back.
+    pub(crate) fn read_for_drawing<R: Read>(reader: R) -> DrawResult<Self> {
+        let (_, root) = tree::read_root_without(reader, UNDRAWN)?;
+        let mut chunk = Self::from_compound(root)?;
+        chunk.partial = true;
+        Ok(chunk)
+    }
+
+    /// The chunk's NBT, as it was read.
+    pub fn raw(&self) -> &Compound {
+        &self.raw
+    }
+
+    /// Write the chunk back to NBT, in the layout it was read in. Everything modelled is written from the chunk,
+    /// and anything else is kept as it was read. Heightmaps are written back as stored, or worked out from the blocks
+    /// if blocks were set since.
+    ///
+    /// Chunks from before 1.13 can only hold blocks that existed then, and fail with `DrawError::NoLegacyId`
+    /// otherwise. Chunks read with `draw::parse_chunk` fail with `DrawError::PartialChunk`.
+    pub fn to_compound(&self) -> DrawResult<Compound> {
+        if self.partial {
+            return Err(DrawError::PartialChunk);
+        }
+
+        let mut root = self.raw.clone();
+        if let Some(version) = self.data_version {
+            root.insert("DataVersion", Node::Int(version));
+        }
+
+        let (level, layout) = match self.format {
+            Format::Flat => (&mut root, &LAYOUT_1_18),
+            _ => {
+                if root.get_compound("Level").is_none() {
+                    root.insert("Level", Node::Compound(Compound::new()));
+                }
+                (root.get_compound_mut("Level").unwrap(), &LAYOUT_1_16)
+            }
+        };
+        let padded = self.format == Format::Flat
+            || !matches!(self.data_version, Some(v) if v < PADDED_DATA_VERSION);
+
+        level.insert("xPos", Node::Int(self.x));
+        level.insert("zPos", Node::Int(self.z));
+        if self.format == Format::Flat {
+            level.insert("yPos", Node::Int(self.min_y.div_euclid(16) as i32));
+        }
+        if let Some(status) = &self.status {
+            level.insert("Status", Node::String(status.clone()));
+        }
+        level.insert("LastUpdate", Node::Long(self.last_update));
+        level.insert("InhabitedTime", Node::Long(self.inhabited_time));
+
+        let sections = self
+            .sections()
+            .map(|s| Ok(Node::Compound(s.to_compound(self.format, padded)?)))
+            .collect::<DrawResult<_>>()?;
+        level.insert(layout.sections, Node::List(Tag::Compound, sections));
+
+        if let Some(biomes) = &self.biomes {
+            level.insert(
+                "Biomes",
+                match self.format {
+                    Format::Legacy => Node::ByteArray(biomes.iter().map(|b| *b as i8).collect()),
+                    _ => Node::IntArray(biomes.clone()),
+                },
+            );
+        }
+
+        match self.format {
+            Format::Legacy => {
+                level.insert("LightPopulated", Node::Byte(self.light_on as i8));
+                if !self.light_on {
+                    let heights = (0..16 * 16)
+                        .map(|i| {
+                            self.heightmap_of(Heightmap::MotionBlocking, i / 16, i % 16) as i32
+                        })
+                        .collect();
+                    level.insert("HeightMap", Node::IntArray(heights));
+                }
+            }
+            _ => {
+                level.insert("isLightOn", Node::Byte(self.light_on as i8));
+
+                let bits = bits::min_bits(self.height() + 1);
+                let mut heightmaps = Compound::new();
+                for kind in Heightmap::ALL {
+                    if let Some(stored) = &self.heightmaps[kind as usize] {
+                        let computed;
+                        let heights = if self.heightmaps_stale {
+                            computed = self.compute_heightmap(kind);
+                            &computed
+                        } else {
+                            stored
+                        };
+                        let packed = if padded {
+                            bits::pack_generic_1_16(heights, bits)
+                        } else {
+                            bits::pack_generic_1_15(heights, bits)
+                        };
+                        heightmaps.insert(kind.name(), Node::LongArray(packed));
+                    }
+                }
+                level.insert("Heightmaps", Node::Compound(heightmaps));
+            }
+        }
+
+        let compound_list = |items: Vec<Compound>| {
+            Node::List(
+                Tag::Compound,
+                items.into_iter().map(Node::Compound).collect(),
+            )
+        };
+        let legacy = self.format == Format::Legacy;
+        let ticks = |ticks: &[Tick]| {
+            ticks
+                .iter()
+                .map(|t| t.to_compound(legacy))
+                .collect::<DrawResult<_>>()
+        };
+
+        level.insert(
+            layout.block_entities,
+            compound_list(
+                self.block_entities
+                    .iter()
+                    .map(BlockEntity::to_compound)
+                    .collect(),
+            ),
+        );
+        if self.format != Format::Flat || level.contains("Entities") {
+            level.insert(
+                "Entities",
+                compound_list(
+                    self.entities
+                        .iter()
+                        .map(|e| e.raw().clone())
+                        .chain(self.unread_entities.iter().cloned())
+                        .collect(),
+                ),
+            );
+        }
+        level.insert(layout.block_ticks, compound_list(ticks(&self.block_ticks)?));
+        level.insert(
+            layout.liquid_ticks,
+            compound_list(ticks(&self.liquid_ticks)?),
+        );
+        if !legacy {
+            level.insert(
+                layout.structures,
+                Node::Compound(self.structures.to_compound(self.format == Format::Flat)),
+            );
+            let post_processing = self
+                .post_processing
+                .iter()
+                .map(|l| Node::List(Tag::Short, l.iter().map(|p| Node::Short(*p)).collect()))
+                .collect();
+            level.insert("PostProcessing", Node::List(Tag::List, post_processing));
+        }
+
+        Ok(root)
+    }
+
+    /// Entities left out of `Chunk::entities` because they could not be read, eg for having no id. They are written
+    /// back unchanged, after the others.
+    pub fn unread_entities(&self) -> &[Compound] {
+        &self.unread_entities
+    }
+
+    /// Whether the chunk is from before 1.13, when blocks were stored as numeric ids and data values.
+    pub fn is_legacy(&self) -> bool {
+        self.format == Format::Legacy
+    }
+
+    /// Sections of the chunk that were stored, from the bottom up.
+    pub fn sections(&self) -> impl Iterator<Item = &Section> {
+        self.sections.iter().flatten()
+    }
+
+    /// The section with the given section y, if stored.
+    pub fn section(&self, y: i8) -> Option<&Section> {
+        let i = y as isize - self.min_section as isize;
+        if i < 0 {
+            return None;
+        }
+        self.sections.get(i as usize)?.as_ref()
+    }
+
+    /// Set the block at a position within the chunk, see `Chunk::id_of` for how positions are given. The section's
+    /// palette grows as needed, with more bits per block when written, and the section is created if it wasn't
+    /// stored. A block entity left at the position by a different block is removed.
+    ///
+    /// Heightmaps are marked out of date, so are worked out from the blocks until `Chunk::recompute_heightmaps`.
+    /// Light is too, so the game relights the chunk when it next loads it.
+    ///
+    /// Panics if `y` is outside the world.
+    pub fn set_block(&mut self, x: usize, y: isize, z: usize, state: &BlockState) {
+        assert!(
+            (self.min_y..self.max_y).contains(&y),
+            "y {} is outside the world",
+            y
+        );
+
+        if self.state_of(x, y, z).name != state.name {
+            // Chunk positions are z then x, the other way round to block entities.
+            let pos = (self.x * 16 + z as i32, y as i32, self.z * 16 + x as i32);
+            self.block_entities.retain(|be| (be.x, be.y, be.z) != pos);
+        }
+
+        let section = self.section_mut_or_insert(y.div_euclid(16) as i8);
+        section.set_state(x, y.rem_euclid(16) as usize, z, state);
+
+        self.heightmaps_stale = true;
+        self.light_on = false;
+    }
+
+    /// Compact the palette of every section, see `Section::compact`. Sections that are entirely air are removed if
+    /// they hold nothing else, ie no light or biomes. Otherwise they lose their blocks, except from 1.18 on, where
+    /// every section keeps its blocks next to its biomes. Returns whether anything changed.
+    pub fn compact(&mut self) -> bool {
+        let mut changed = false;
+        let format = self.format;
+
+        for slot in &mut self.sections {
+            let section = match slot {
+                Some(section) => section,
+                None => continue,
+            };
+            changed |= section.compact();
+
+            if !section.is_air() {
+                continue;
+            }
+            if section.block_light.is_none()
+                && section.sky_light.is_none()
+                && section.biome_palette.is_empty()
+            {
+                *slot = None;
+                changed = true;
+            } else if format != Format::Flat {
+                section.states.clear();
+                section.palette.clear();
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    fn section_mut_or_insert(&mut self, y: i8) -> &mut Section {
+        if y < self.min_section {
+            let missing = (self.min_section as isize - y as isize) as usize;
+            self.sections
+                .splice(0..0, std::iter::repeat_with(|| None).take(missing));
+            self.min_section = y;
+        }
+
+        let i = (y as isize - self.min_section as isize) as usize;
+        if i >= self.sections.len() {
+            self.sections.resize_with(i + 1, || None);
+        }
+
+        self.sections[i].get_or_insert_with(|| Section::new(y))
+    }
+
+    /// The lowest y of the world.
+    pub fn min_y(&self) -> isize {
+        self.min_y
+    }
+
+    /// One above the highest y of the world.
+    pub fn max_y(&self) -> isize {
+        self.max_y
+    }
+
+    pub fn height(&self) -> usize {
+        (self.max_y - self.min_y) as usize
+    }
+
+    /// Set the height of the world, eg from the `min_y` and `height` of a dimension type. A chunk can only tell the
+    /// height from the sections stored, which misses empty sections at the bottom before 1.18.
+    pub fn set_height(&mut self, min_y: isize, height: usize) {
+        self.min_y = min_y;
+        self.max_y = min_y + height as isize;
+    }
+
+    /// Id of the block at a position within the chunk, or an empty string if there is no block stored there, eg
+    /// outside of the world.
+    pub fn id_of(&self, x: usize, y: isize, z: usize) -> &str {
+        match self.stored_state(x, y, z) {
+            Some(state) => &state.name,
+            None => "",
+        }
+    }
+
+    /// The full block state, including properties such as `waterlogged` or a crop's `age`. Parts of the chunk with
+    /// no blocks stored are air.
+    pub fn state_of(&self, x: usize, y: isize, z: usize) -> &BlockState {
+        static AIR: OnceLock<BlockState> = OnceLock::new();
+
+        match self.stored_state(x, y, z) {
+            Some(state) => state,
+            None => AIR.get_or_init(|| BlockState::new("minecraft:air")),
+        }
+    }
+
+    /// Light from blocks such as torches at a position, from 0 to 15. Sections without block light stored have
+    /// none. `None` if the chunk's light has not been computed.
+    pub fn block_light_of(&self, x: usize, y: isize, z: usize) -> Option<u8> {
+        if !self.light_on {
+            return None;
+        }
+
+        let level = i8::try_from(y.div_euclid(16))
+            .ok()
+            .and_then(|sec_y| self.section(sec_y)?.block_light.as_deref())
+            .map(|light| nibble(light, light_index(x, y.rem_euclid(16) as usize, z)));
+        Some(level.unwrap_or(0))
+    }
+
+    /// Light from the sky at a position, from 0 to 15, as it is during the day. `None` if the chunk's light has not
+    /// been computed.
+    ///
+    /// The game leaves out sky light for sections it can tell from the sections above, so this is taken from the
+    /// bottom of the nearest section above with sky light, or full light if there is none. Chunks without any sky
+    /// light, such as in the nether, have none throughout.
+    pub fn sky_light_of(&self, x: usize, y: isize, z: usize) -> Option<u8> {
+        if !self.light_on {
+            return None;
+        }
+        if self.sections().all(|s| s.sky_light.is_none()) {
+            return Some(0);
+        }
+
+        let sec_y = y.div_euclid(16);
+        let found = self
+            .sections()
+            .filter(|s| s.y as isize >= sec_y)
+            .find_map(|s| Some((s.y as isize, s.sky_light.as_deref()?)));
+
+        Some(match found {
+            Some((found_y, light)) if found_y == sec_y => {
+                nibble(light, light_index(x, y.rem_euclid(16) as usize, z))
+            }
+            Some((_, light)) => nibble(light, light_index(x, 0, z)),
+            None => 15,
+        })
+    }
+
+    fn stored_state(&self, x: usize, y: isize, z: usize) -> Option<&BlockState> {
+        let sec = self.section(i8::try_from(y.div_euclid(16)).ok()?)?;
+        let sec_y = y.rem_euclid(16) as usize;
+        let state_index = (sec_y * 16 * 16) + x * 16 + z;
+        let pal_index = *sec.states.get(state_index)?;
+        sec.palette.get(pal_index as usize).map(|s| &**s)
+    }
+
+    /// One above the highest block that is not air, or `Chunk::min_y` if there are none.
+    pub fn height_of(&self, x: usize, z: usize) -> isize {
+        self.heightmap_of(Heightmap::WorldSurface, x, z)
+    }
+
+    /// One above the highest block counted by a heightmap, or `Chunk::min_y` if there are none. Taken from the
+    /// stored heightmap, or worked out from the blocks if it is missing or out of date.
+    pub fn heightmap_of(&self, kind: Heightmap, x: usize, z: usize) -> isize {
+        let height = match self.heightmap(kind) {
+            Some(heights) => heights[x * 16 + z],
+            None => self.column_height(kind, x, z),
+        };
+        height as isize + self.min_y
+    }
+
+    /// A stored heightmap that is up to date, indexed as `x * 16 + z` and relative to `Chunk::min_y`. Only the
+    /// heightmaps used during generation are complete before features are placed, so the others are out of date in
+    /// chunks still generating.
+    pub fn heightmap(&self, kind: Heightmap) -> Option<&[u16]> {
+        if self.heightmaps_stale || (self.is_generating() && !kind.is_worldgen()) {
+            return None;
+        }
+        self.heightmaps[kind as usize].as_deref()
+    }
+
+    /// Whether the chunk has not yet had features placed, going by its status.
+    fn is_generating(&self) -> bool {
+        matches!(
+            self.status
+                .as_deref()
+                .map(|s| s.strip_prefix("minecraft:").unwrap_or(s)),
+            Some(
+                "empty"
+                    | "structure_starts"
+                    | "structure_references"
+                    | "biomes"
+                    | "noise"
+                    | "surface"
+                    | "carvers"
+                    | "liquid_carvers"
+            )
+        )
+    }
+
+    /// Work out a heightmap from the blocks, indexed as `x * 16 + z` and relative to `Chunk::min_y`.
+    pub fn compute_heightmap(&self, kind: Heightmap) -> Vec<u16> {
+        let mut heights = vec![None; 16 * 16];
+
+        for sec in self.sections.iter().rev().flatten() {
+            let counted: Vec<bool> = sec.palette.iter().map(|s| kind.counts(s)).collect();
+            if sec.states.is_empty() || !counted.contains(&true) {
+                continue;
+            }
+
+            for (column, height) in heights.iter_mut().enumerate() {
+                if height.is_some() {
+                    continue;
+                }
+                *height = (0..16).rev().find_map(|sec_y| {
+                    let state = sec.states[sec_y * 16 * 16 + column];
+                    let counts = counted.get(state as usize).copied().unwrap_or(false);
+                    counts.then(|| sec.y as isize * 16 + sec_y as isize + 1)
+                });
+            }
+
+            if heights.iter().all(Option::is_some) {
+                break;
+            }
+        }
+
+        heights
+            .into_iter()
+            .map(|h| (h.unwrap_or(self.min_y) - self.min_y).clamp(0, u16::MAX as isize) as u16)
+            .collect()
+    }
+
+    /// Replace the stored heightmaps with ones worked out from the blocks. The heightmaps only used during world
+    /// generation are only kept if they were stored.
+    pub fn recompute_heightmaps(&mut self) {
+        for kind in Heightmap::ALL {
+            if !kind.is_worldgen() || self.heightmaps[kind as usize].is_some() {
+                self.heightmaps[kind as usize] = Some(self.compute_heightmap(kind));
+            }
+        }
+        self.heightmaps_stale = false;
+    }
+
+    fn column_height(&self, kind: Heightmap, x: usize, z: usize) -> u16 {
+        (self.min_y..self.max_y)
+            .rev()
+            .find(|y| kind.counts(self.state_of(x, *y, z)))
+            .map_or(0, |y| (y + 1 - self.min_y) as u16)
+    }
+
+    /// The biome at a position. Since 1.18 this is looked up by name, so biomes added since 1.16 have none, see
+    /// `Chunk::biome_name_of`.
+    pub fn biome_of(&self, x: usize, y: isize, z: usize) -> Option<Biome> {
+        if let Some(name) = self.biome_name_of(x, y, z) {
+            return Biome::from_name(name);
+        }
+
+        // TODO: Take into account height for chunks before 1.18.
+
+        // For biome len of 1024,
+        //  it's 4x4x4 sets of blocks stored by z then x then y (+1 moves one in z)
+        //  for overworld theres no vertical chunks so it looks like only first 16 values are used.
+        // For biome len of 256, it's chunk 1x1 columns stored z then x.
+
+        let biomes = self.biomes.as_ref()?;
+
+        if biomes.len() == 1024 {
+            Biome::try_from(biomes[(x / 4) * 4 + (z / 4)]).ok()
+        } else {
+            Biome::try_from(biomes[x * 16 + z]).ok()
+        }
+    }
+
+    /// Namespaced biome at a position, eg `minecraft:plains`. Only chunks from 1.18 on store biomes by name.
+    pub fn biome_name_of(&self, x: usize, y: isize, z: usize) -> Option<&str> {
+        let sec = self.section(i8::try_from(y.div_euclid(16)).ok()?)?;
+        let sec_y = y.rem_euclid(16) as usize;
+        let index = (sec_y / 4) * 16 + (x / 4) * 4 + z / 4;
+        let pal_index = *sec.biomes.get(index)?;
+        sec.biome_palette
+            .get(pal_index as usize)
+            .map(String::as_str)
+    }
+}
+
+impl Section {
+    /// A section of air.
+    pub fn new(y: i8) -> Self {
+        Self {
+            states: vec![0; 16 * 16 * 16],
+            palette: vec![block::intern(BlockState::new("minecraft:air"))],
+            y,
+            block_light: None,
+            sky_light: None,
+            biomes: vec![],
+            biome_palette: vec![],
+            legacy: None,
+        }
+    }
+
+    /// Set the block at a position within the section, adding it to the palette if needed. A section only holding
+    /// light is filled with air first.
+    pub fn set_state(&mut self, x: usize, y: usize, z: usize, state: &BlockState) {
+        if self.states.is_empty() {
+            self.states = vec![0; 16 * 16 * 16];
+            self.palette = vec![block::intern(BlockState::new("minecraft:air"))];
+        }
+
+        let index = match self.palette.iter().position(|p| **p == *state) {
+            Some(i) => i,
+            None => {
+                self.palette.push(block::intern(state.clone()));
+                self.palette.len() - 1
+            }
+        };
+        let i = y * 16 * 16 + x * 16 + z;
+        self.states[i] = index as u16;
+
+        if let (Some(blocks), Some(ids)) = (&mut self.legacy, legacy::legacy_id(state)) {
+            blocks.set(i, ids);
+        }
+    }
+
+    /// Remove palette entries no block uses and merge equal states, keeping the palette in the order blocks first
+    /// use it. Returns whether the palette changed. Sections with indices outside their palette are left alone.
+    pub fn compact(&mut self) -> bool {
+        if self.states.is_empty()
+            || self
+                .states
+                .iter()
+                .any(|&s| s as usize >= self.palette.len())
+        {
+            return false;
+        }
+
+        let mut palette: Vec<Arc<BlockState>> = vec![];
+        let mut remap: Vec<Option<u16>> = vec![None; self.palette.len()];
+        let old_palette = &self.palette;
+
+        for state in &mut self.states {
+            let old = *state as usize;
+            let new = match remap[old] {
+                Some(new) => new,
+                None => {
+                    let new = match palette.iter().position(|p| *p == old_palette[old]) {
+                        Some(i) => i,
+                        None => {
+                            palette.push(old_palette[old].clone());
+                            palette.len() - 1
+                        }
+                    };
+                    remap[old] = Some(new as u16);
+                    new as u16
+                }
+            };
+            *state = new;
+        }
+
+        let changed = palette.len() != self.palette.len()
+            || palette
+                .iter()
+                .zip(&self.palette)
+                .any(|(a, b)| !Arc::ptr_eq(a, b));
+        self.palette = palette;
+        changed
+    }
+
+    /// Whether every block of the section is air, including cave and void air. This goes by the palette, so
+    /// compact it first to ignore unused entries. Sections only holding light are not air.
+    pub fn is_air(&self) -> bool {
+        !self.states.is_empty()
+            && self.palette.iter().all(|p| {
+                matches!(
+                    p.name.as_str(),
+                    "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air"
+                )
+            })
+    }
+
+    fn to_compound(&self, format: Format, padded: bool) -> DrawResult<Compound> {
+        let mut c = Compound::new();
+        c.insert("Y", Node::Byte(self.y));
+
+        if !self.states.is_empty() {
+            match format {
+                Format::Legacy => self.write_legacy_blocks(&mut c)?,
+                Format::Level => {
+                    let palette = self
+                        .palette
+                        .iter()
+                        .map(|p| Node::Compound(p.to_compound()))
+                        .collect();
+                    c.insert("Palette", Node::List(Tag::Compound, palette));
+                    let states = if padded {
+                        bits::pack_blockstates(&self.states, self.palette.len())
+                    } else {
+                        bits::pack_generic_1_15(
+                            &self.states,
+                            bits::bits_per_block(self.palette.len()),
+                        )
+                    };
+                    c.insert("BlockStates", Node::LongArray(states));
+                }
+                Format::Flat => {
+                    let mut block_states = Compound::new();
+                    let palette = self
+                        .palette
+                        .iter()
+                        .map(|p| Node::Compound(p.to_compound()))
+                        .collect();
+                    block_states.insert("palette", Node::List(Tag::Compound, palette));
+                    // A single state fills the section without any data.
+                    if self.palette.len() > 1 {
+                        block_states.insert(
+                            "data",
+                            Node::LongArray(bits::pack_blockstates(
+                                &self.states,
+                                self.palette.len(),
+                            )),
+                        );
+                    }
+                    c.insert("block_states", Node::Compound(block_states));
+                }
+            }
+        }
+
+        if !self.biome_palette.is_empty() {
+            let mut biomes = Compound::new();
+            let palette = self
+                .biome_palette
+                .iter()
+                .map(|b| Node::String(b.clone()))
+                .collect();
+            biomes.insert("palette", Node::List(Tag::String, palette));
+            if self.biome_palette.len() > 1 {
+                let bits = bits::min_bits(self.biome_palette.len());
+                biomes.insert(
+                    "data",
+                    Node::LongArray(bits::pack_generic_1_16(&self.biomes, bits)),
+                );
+            }
+            c.insert("biomes", Node::Compound(biomes));
+        }
+
+        let light = |l: &Vec<u8>| Node::ByteArray(l.iter().map(|b| *b as i8).collect());
+        if let Some(l) = &self.block_light {
+            c.insert("BlockLight", light(l));
+        }
+        if let Some(l) = &self.sky_light {
+            c.insert("SkyLight", light(l));
+        }
+        Ok(c)
+    }
+
+    /// Write blocks as numeric ids and data values, see `legacy_blocks`. Blocks still holding the state they were
+    /// read as keep their id and data value. Fails for blocks that didn't exist before 1.13.
+    fn write_legacy_blocks(&self, c: &mut Compound) -> DrawResult<()> {
+        let ids: Vec<Option<(u16, u8)>> =
+            self.palette.iter().map(|s| legacy::legacy_id(s)).collect();
+        let mut read_as = HashMap::new();
+
+        let mut blocks = Vec::with_capacity(self.states.len());
+        let mut data = vec![0u8; self.states.len() / 2];
+        let mut add = vec![0u8; self.states.len() / 2];
+
+        for (i, state) in self.states.iter().enumerate() {
+            let current = &self.palette[*state as usize];
+            let kept = self
+                .legacy
+                .as_ref()
+                .map(|l| l.get(i))
+                .filter(|&(id, value)| {
+                    read_as
+                        .entry((id, value))
+                        .or_insert_with(|| legacy_state(id, value))
+                        == &**current
+                });
+
+            let (id, value) = match kept.or(ids[*state as usize]) {
+                Some(ids) => ids,
+                None => return Err(DrawError::NoLegacyId(current.name.clone())),
+            };
+            blocks.push(id as u8 as i8);
+            set_nibble(&mut data, i, value);
+            set_nibble(&mut add, i, (id >> 8) as u8);
+        }
+
+        let bytes = |v: Vec<u8>| Node::ByteArray(v.into_iter().map(|b| b as i8).collect());
+        c.insert("Blocks", Node::ByteArray(blocks));
+        c.insert("Data", bytes(data));
+        if add.iter().any(|a| *a != 0) {
+            c.insert("Add", bytes(add));
+        }
+        Ok(())
+    }
+
+    /// Read a section, or `None` if it holds no blocks, light or biomes. Sections from 1.18 on, with palettes for
+    /// both blocks and biomes, are told apart by their `block_states`.
+    pub fn from_compound(c: &Compound) -> DrawResult<Option<Self>> {
+        let y = match c.get_i8("Y") {
+            Some(y) => y,
+            None => return Ok(None),
+        };
+
+        let (palette, states, legacy) = match c.get_byte_array("Blocks") {
+            Some(blocks) => {
+                let nibbles = |name| {
+                    c.get_byte_array(name)
+                        .map(|a| a.iter().map(|b| *b as u8).collect())
+                        .unwrap_or_else(|| vec![0; blocks.len() / 2])
+                };
+                let legacy = LegacyBlocks {
+                    blocks: blocks.to_vec(),
+                    add: nibbles("Add"),
+                    data: nibbles("Data"),
+                };
+                let (palette, states) = legacy_blocks(&legacy);
+                (palette, states, Some(legacy))
+            }
+            None => {
+                let (palette, states) = palette_blocks(c)?;
+                (palette, states, None)
+            }
+        };
+
+        let (biomes, biome_palette) = match c.get_compound("biomes") {
+            Some(biomes) => {
+                let palette: Vec<String> = biomes
+                    .get_list("palette")
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|b| b.as_str().map(str::to_owned))
+                    .collect();
+
+                let indices = match biomes.get_long_array("data") {
+                    Some(data) if palette.len() > 1 => {
+                        let mut indices =
+                            bits::expand_generic_1_16(data, bits::min_bits(palette.len()));
+                        indices.truncate(4 * 4 * 4);
+                        indices
+                    }
+                    _ if !palette.is_empty() => vec![0; 4 * 4 * 4],
+                    _ => vec![],
+                };
+                (indices, palette)
+            }
+            None => (vec![], vec![]),
+        };
+
+        let light = |name| {
+            c.get_byte_array(name)
+                .map(|l| l.iter().map(|b| *b as u8).collect())
+        };
+        let block_light: Option<Vec<u8>> = light("BlockLight");
+        let sky_light: Option<Vec<u8>> = light("SkyLight");
+
+        if states.is_empty()
+            && block_light.is_none()
+            && sky_light.is_none()
+            && biome_palette.is_empty()
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            states,
+            palette,
+            y,
+            block_light,
+            sky_light,
+            biomes,
+            biome_palette,
+            legacy,
+        }))
+    }
+}
+
+/// Blocks of a section with a palette, as stored since 1.13.
+fn palette_blocks(c: &Compound) -> DrawResult<(Vec<Arc<BlockState>>, Vec<u16>)> {
+    let (palette, states) = match c.get_compound("block_states") {
+        Some(block_states) => (
+            block_states.get_list("palette").unwrap_or_default(),
+            block_states.get_long_array("data"),
+        ),
+        None => (
+            c.get_list("Palette").unwrap_or_default(),
+            c.get_long_array("BlockStates"),
+        ),
+    };
+
+    let palette = palette
+        .iter()
+        .map(|entry| {
+            entry
+                .as_compound()
+                .and_then(BlockState::from_compound)
+                .map(block::intern)
+                .ok_or(DrawError::InvalidPalette)
+        })
+        .collect::<DrawResult<Vec<_>>>()?;
+
+    let states = match states {
+        Some(states) if !palette.is_empty() => {
+            let mut states = bits::expand_blockstates(states, palette.len());
+            states.truncate(16 * 16 * 16);
+            states
+        }
+        // A single state fills the section without any data.
+        None if palette.len() == 1 => vec![0; 16 * 16 * 16],
+        _ => vec![],
+    };
+
+    Ok((palette, states))
+}
+
+/// Blocks of a section from before 1.13, stored as numeric ids and data values. Ids above 255 put their high bits in
+/// the optional `Add` array. Both it and `Data` hold four bits per block, the first block in the low bits of a byte.
+fn legacy_blocks(legacy: &LegacyBlocks) -> (Vec<Arc<BlockState>>, Vec<u16>) {
+    let mut palette = vec![];
+    let mut lookup = HashMap::new();
+    let mut states = Vec::with_capacity(legacy.blocks.len());
+
+    for i in 0..legacy.blocks.len() {
+        let key = legacy.get(i);
+        let index = *lookup.entry(key).or_insert_with(|| {
+            palette.push(block::intern(legacy_state(key.0, key.1)));
+            (palette.len() - 1) as u16
+        });
+        states.push(index);
+    }
+
+    (palette, states)
+}
+
+/// The state of a numeric id and data value. Ids that were never used can't be shown, so are taken to be air.
+fn legacy_state(id: u16, data: u8) -> BlockState {
+    legacy::block_state(id, data).unwrap_or_else(|| BlockState::new("minecraft:air"))
+}
+
+/// Name of a block with a numeric id, or an empty string for ids that were never used.
+fn legacy_name(id: u16) -> String {
+    legacy::block_state(id, 0)
+        .map(|s| s.name)
+        .unwrap_or_default()
+}
+
+/// Index of a block in a section's light, which is stored in the same order as its blocks.
+fn light_index(x: usize, sec_y: usize, z: usize) -> usize {
+    sec_y * 16 * 16 + x * 16 + z
+}
+
+/// A value from an array of four bits per value, the first in the low bits of a byte.
+fn nibble(array: &[u8], i: usize) -> u8 {
+    match array.get(i / 2) {
+        Some(b) if i & 1 == 0 => b & 0x0F,
+        Some(b) => b >> 4,
+        None => 0,
+    }
+}
+
+/// Set a value in an array of four bits per value, see `nibble`.
+fn set_nibble(array: &mut [u8], i: usize, value: u8) {
+    if let Some(b) = array.get_mut(i / 2) {
+        *b = if i & 1 == 0 {
+            (*b & 0xF0) | (value & 0x0F)
+        } else {
+            (*b & 0x0F) | (value << 4)
+        };
+    }
+}
+
+/// Biomes of a chunk stored for the whole chunk, as done before 1.18. They were a byte per column before 1.13.
+fn biomes(level: &Compound) -> Option<Vec<i32>> {
+    match level.get_int_array("Biomes") {
+        Some(b) => Some(b.to_vec()),
+        None => level
+            .get_byte_array("Biomes")
+            .map(|b| b.iter().map(|b| *b as u8 as i32).collect()),
+    }
+}
+
+/// Expand a heightmap for a world of the given height. Heights need enough bits to hold every value from zero to
+/// the height, so more than the usual 9 in tall worlds.
+fn expand_heights(data: &[i64], height: usize) -> Vec<u16> {
+    let bits = bits::min_bits(height + 1);
+
+    let mut heights = if bits == 9 {
+        bits::expand_heightmap(data)
+    } else {
+        bits::expand_generic_1_16(data, bits)
+    };
+    heights.resize(16 * 16, 0);
+    heights
+}
+
+/// The compounds in a list, skipping anything else.
+fn compounds<'a>(c: &'a Compound, name: &str) -> impl Iterator<Item = &'a Compound> {
+    c.get_list(name)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Node::as_compound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::Tag;
+
+    fn compound(entries: Vec<(&str, Node)>) -> Compound {
+        let mut c = Compound::new();
+        for (name, node) in entries {
+            c.insert(name, node);
+        }
+        c
+    }
+
+    fn list(entries: Vec<Compound>) -> Node {
+        Node::List(
+            Tag::Compound,
+            entries.into_iter().map(Node::Compound).collect(),
+        )
+    }
+
+    /// A 1.16 chunk with one section of stone with a slab in the corner, and one of only light above it.
+    fn chunk_1_16() -> Compound {
+        let slab = compound(vec![
+            ("Name", Node::String("minecraft:oak_slab".to_owned())),
+            (
+                "Properties",
+                Node::Compound(compound(vec![
+                    ("waterlogged", Node::String("true".to_owned())),
+                    ("type", Node::String("top".to_owned())),
+                ])),
+            ),
+        ]);
+        let palette = list(vec![
+            compound(vec![("Name", Node::String("minecraft:stone".to_owned()))]),
+            slab,
+        ]);
+        // Four bits per block, with a slab in the corner.
+        let mut states = vec![0; 256];
+        states[0] = 1;
+        let stone = compound(vec![
+            ("Y", Node::Byte(0)),
+            ("Palette", palette),
+            ("BlockStates", Node::LongArray(states)),
+            ("SkyLight", Node::ByteArray(vec![0; 2048])),
+        ]);
+        let light = compound(vec![
+            ("Y", Node::Byte(1)),
+            ("SkyLight", Node::ByteArray(vec![-1; 2048])),
+        ]);
+        let below = compound(vec![
+            ("Y", Node::Byte(-1)),
+            ("SkyLight", Node::ByteArray(vec![0; 2048])),
+        ]);
+
+        let chest = compound(vec![
+            ("id", Node::String("minecraft:chest".to_owned())),
+            ("x", Node::Int(-31)),
+            ("y", Node::Int(5)),
+            ("z", Node::Int(2)),
+        ]);
+        let tick = compound(vec![
+            ("i", Node::String("minecraft:water".to_owned())),
+            ("x", Node::Int(-30)),
+            ("y", Node::Int(16)),
+            ("z", Node::Int(3)),
+            ("t", Node::Int(-2)),
+            ("p", Node::Int(0)),
+        ]);
+        let cow = compound(vec![("id", Node::String("minecraft:cow".to_owned()))]);
+
+        let village = compound(vec![
+            ("id", Node::String("minecraft:village".to_owned())),
+            ("ChunkX", Node::Int(-2)),
+            ("ChunkZ", Node::Int(0)),
+        ]);
+        let invalid = compound(vec![("id", Node::String("INVALID".to_owned()))]);
+        let structures = compound(vec![
+            (
+                "Starts",
+                Node::Compound(compound(vec![
+                    ("minecraft:village", Node::Compound(village)),
+                    ("minecraft:fortress", Node::Compound(invalid)),
+                ])),
+            ),
+            (
+                "References",
+                Node::Compound(compound(vec![(
+                    "minecraft:village",
+                    Node::LongArray(vec![(3i64 << 32) | (-2i64 & 0xFFFF_FFFF)]),
+                )])),
+            ),
+        ]);
+
+        let mut post_processing = vec![Node::List(Tag::Short, vec![]); 16];
+        post_processing[1] = Node::List(Tag::Short, vec![Node::Short(0x123)]);
+
+        let level = compound(vec![
+            ("xPos", Node::Int(-2)),
+            ("zPos", Node::Int(0)),
+            ("Status", Node::String("full".to_owned())),
+            ("LastUpdate", Node::Long(1000)),
+            ("InhabitedTime", Node::Long(20)),
+            ("Sections", list(vec![below, stone, light])),
+            (
+                "Heightmaps",
+                Node::Compound(compound(vec![(
+                    "WORLD_SURFACE",
+                    Node::LongArray(vec![0; 37]),
+                )])),
+            ),
+            ("TileEntities", list(vec![chest])),
+            ("Entities", list(vec![cow])),
+            ("TileTicks", list(vec![])),
+            ("LiquidTicks", list(vec![tick])),
+            ("Structures", Node::Compound(structures)),
+            ("PostProcessing", Node::List(Tag::List, post_processing)),
+        ]);
+
+        compound(vec![
+            ("DataVersion", Node::Int(2586)),
+            ("Level", Node::Compound(level)),
+        ])
+    }
+
+    #[test]
+    fn full_chunk_1_16() -> DrawResult<()> {
+        let chunk = Chunk::from_compound(chunk_1_16())?;
+
+        assert_eq!(Some(2586), chunk.data_version);
+        assert_eq!((-2, 0), (chunk.x, chunk.z));
+        assert_eq!(Some("full"), chunk.status.as_deref());
+        assert_eq!(1000, chunk.last_update);
+        assert_eq!(20, chunk.inhabited_time);
+
+        assert_eq!(
+            (-31, 5, 2),
+            (
+                chunk.block_entities[0].x,
+                chunk.block_entities[0].y,
+                chunk.block_entities[0].z
+            )
+        );
+        assert_eq!("minecraft:chest", chunk.block_entities[0].id);
+        assert!(chunk.block_entities[0].items().is_empty());
+        assert_eq!("minecraft:cow", chunk.entities[0].id);
+        assert!(chunk.block_ticks.is_empty());
+        assert_eq!(
+            vec![Tick {
+                id: "minecraft:water".to_owned(),
+                x: -30,
+                y: 16,
+                z: 3,
+                delay: -2,
+                priority: 0,
+                legacy_id: None,
+            }],
+            chunk.liquid_ticks
+        );
+
+        assert_eq!(1, chunk.structures.starts.len());
+        assert_eq!("minecraft:village", chunk.structures.starts[0].name);
+        assert_eq!(-2, chunk.structures.starts[0].chunk_x);
+        assert_eq!(
+            vec![("minecraft:village".to_owned(), vec![(-2, 3)])],
+            chunk.structures.references
+        );
+        assert_eq!(vec![0x123], chunk.post_processing[1]);
+
+        // Sections only holding light are kept, but don't extend the world.
+        let ys: Vec<_> = chunk.sections().map(|s| s.y).collect();
+        assert_eq!(vec![-1, 0, 1], ys);
+        assert_eq!((0, 256), (chunk.min_y(), chunk.max_y()));
+        assert_eq!("minecraft:stone", chunk.id_of(3, 15, 3));
+        assert_eq!("", chunk.id_of(3, 16, 3));
+
+        let slab = chunk.state_of(0, 0, 0);
+        assert_eq!("minecraft:oak_slab", slab.name);
+        assert_eq!(Some("top"), slab.property("type"));
+        assert_eq!(Some("true"), slab.property("waterlogged"));
+        assert_eq!("minecraft:air", chunk.state_of(3, 16, 3).name);
+        assert_eq!("minecraft:air", chunk.state_of(3, 40, 3).name);
+        assert_eq!(
+            Some(255),
+            chunk.section(1).unwrap().sky_light.as_ref().map(|l| l[0])
+        );
+
+        assert!(chunk.raw().contains("Level"));
+        Ok(())
+    }
+
+    #[test]
+    fn read_for_drawing() -> DrawResult<()> {
+        let mut data = vec![];
+        tree::write_root(&mut data, "", &chunk_1_16())?;
+        let full = Chunk::from_compound(chunk_1_16())?;
+        let chunk = Chunk::read_for_drawing(data.as_slice())?;
+
+        assert!(chunk.entities.is_empty() && chunk.block_entities.is_empty());
+        assert!(chunk.liquid_ticks.is_empty());
+        assert!(!chunk
+            .raw()
+            .get_compound("Level")
+            .unwrap()
+            .contains("Entities"));
+        for (x, y, z) in [(0, 0, 0), (2, 5, 1), (3, 16, 3)] {
+            assert_eq!(full.state_of(x, y, z), chunk.state_of(x, y, z));
+        }
+        assert_eq!(full.height_of(0, 0), chunk.height_of(0, 0));
+        assert!(matches!(chunk.to_compound(), Err(DrawError::PartialChunk)));
+        Ok(())
+    }
+
+    #[test]
+    fn block_entity_items() -> DrawResult<()> {
+        let mut chunk = Chunk::from_compound(chunk_1_16())?;
+        let mut apples = ItemStack::new("minecraft:apple", 3);
+        apples.slot = Some(4);
+        chunk.block_entities[0].set_items(&[apples.clone()]);
+
+        let read = Chunk::from_compound(chunk.to_compound()?)?;
+        let items = read.block_entities[0].items();
+        assert_eq!(1, items.len());
+        assert_eq!(
+            (&apples.id, apples.count, apples.slot),
+            (&items[0].id, items[0].count, items[0].slot)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unreadable_parts_are_kept() -> DrawResult<()> {
+        let mut root = chunk_1_16();
+        let level = root.get_compound_mut("Level").unwrap();
+        match level.get_mut("Entities") {
+            Some(Node::List(_, v)) => v.push(Node::Compound(compound(vec![(
+                "Pos",
+                Node::List(Tag::Double, vec![Node::Double(0.); 3]),
+            )]))),
+            _ => unreachable!(),
+        }
+
+        let mut chunk = Chunk::from_compound(root)?;
+        assert_eq!(1, chunk.entities.len());
+        assert_eq!(1, chunk.unread_entities().len());
+        let read = Chunk::from_compound(chunk.to_compound()?)?;
+        assert_eq!(1, read.unread_entities().len());
+
+        // A block outside its section's palette reads as nothing rather than panicking.
+        let section = chunk
+            .sections
+            .iter_mut()
+            .flatten()
+            .find(|s| s.y == 0)
+            .unwrap();
+        section.states[0] = 1000;
+        assert_eq!("", chunk.id_of(0, 0, 0));
+        assert_eq!("minecraft:air", chunk.state_of(0, 0, 0).name);
+        chunk.compute_heightmap(Heightmap::WorldSurface);
+        Ok(())
+    }
+
+    #[test]
+    fn extended_height() -> DrawResult<()> {
+        let section = |y: i8, name: &str| {
+            compound(vec![
+                ("Y", Node::Byte(y)),
+                (
+                    "Palette",
+                    list(vec![compound(vec![(
+                        "Name",
+                        Node::String(name.to_owned()),
+                    )])]),
+                ),
+                ("BlockStates", Node::LongArray(vec![0; 256])),
+            ])
+        };
+
+        let mut root = chunk_1_16();
+        let level = root.get_compound_mut("Level").unwrap();
+        level.insert(
+            "Sections",
+            list(vec![
+                section(19, "minecraft:air"),
+                section(-4, "minecraft:deepslate"),
+            ]),
+        );
+        // Relative to the bottom of the world, so the deepslate is the highest block.
+        let mut heights = vec![0i64; 37];
+        heights[0] = 16;
+        level
+            .get_compound_mut("Heightmaps")
+            .unwrap()
+            .insert("WORLD_SURFACE", Node::LongArray(heights));
+
+        let mut chunk = Chunk::from_compound(root)?;
+        assert_eq!((-64, 320), (chunk.min_y(), chunk.max_y()));
+        assert_eq!("minecraft:deepslate", chunk.id_of(0, -64, 0));
+        assert_eq!("minecraft:deepslate", chunk.id_of(0, -49, 0));
+        assert_eq!("", chunk.id_of(0, -48, 0));
+        assert_eq!("minecraft:air", chunk.id_of(0, 319, 0));
+        assert_eq!("", chunk.id_of(0, 320, 0));
+        assert_eq!("", chunk.id_of(0, -1000, 0));
+        assert_eq!(-48, chunk.height_of(0, 0));
+
+        chunk.set_height(-128, 512);
+        assert_eq!((-128, 384), (chunk.min_y(), chunk.max_y()));
+        assert_eq!(-112, chunk.height_of(0, 0));
+        Ok(())
+    }
+
+    /// A 1.18 chunk with a deepslate section at the bottom of the world, and one of air at the top.
+    fn chunk_1_18() -> Compound {
+        let state = |name: &str| compound(vec![("Name", Node::String(name.to_owned()))]);
+        let biomes = |names: &[&str], data: Option<Vec<i64>>| {
+            let palette = names
+                .iter()
+                .map(|n| Node::String((*n).to_owned()))
+                .collect();
+            let mut c = compound(vec![("palette", Node::List(Tag::String, palette))]);
+            if let Some(data) = data {
+                c.insert("data", Node::LongArray(data));
+            }
+            Node::Compound(c)
+        };
+
+        // Deepslate with a diamond ore in the corner, and a single cave biome cell at the bottom.
+        let mut ore = vec![0i64; 256];
+        ore[0] = 1;
+        let deepslate = compound(vec![
+            ("Y", Node::Byte(-4)),
+            (
+                "block_states",
+                Node::Compound(compound(vec![
+                    (
+                        "palette",
+                        list(vec![
+                            state("minecraft:deepslate"),
+                            state("minecraft:deepslate_diamond_ore"),
+                        ]),
+                    ),
+                    ("data", Node::LongArray(ore)),
+                ])),
+            ),
+            (
+                "biomes",
+                biomes(
+                    &["minecraft:dripstone_caves", "minecraft:windswept_hills"],
+                    Some(vec![!1]),
+                ),
+            ),
+        ]);
+        // Only a palette, so all air and all plains.
+        let air = compound(vec![
+            ("Y", Node::Byte(19)),
+            (
+                "block_states",
+                Node::Compound(compound(vec![(
+                    "palette",
+                    list(vec![state("minecraft:air")]),
+                )])),
+            ),
+            ("biomes", biomes(&["minecraft:plains"], None)),
+        ]);
+
+        let tick = compound(vec![
+            ("i", Node::String("minecraft:lava".to_owned())),
+            ("x", Node::Int(1)),
+            ("y", Node::Int(-60)),
+            ("z", Node::Int(2)),
+            ("t", Node::Int(5)),
+            ("p", Node::Int(0)),
+        ]);
+        let fortress = compound(vec![
+            ("id", Node::String("minecraft:fortress".to_owned())),
+            ("ChunkX", Node::Int(0)),
+            ("ChunkZ", Node::Int(0)),
+        ]);
+
+        compound(vec![
+            ("DataVersion", Node::Int(2975)),
+            ("xPos", Node::Int(0)),
+            ("yPos", Node::Int(-4)),
+            ("zPos", Node::Int(0)),
+            ("Status", Node::String("full".to_owned())),
+            ("sections", list(vec![deepslate, air])),
+            (
+                "Heightmaps",
+                Node::Compound(compound(vec![(
+                    "WORLD_SURFACE",
+                    Node::LongArray(vec![16; 37]),
+                )])),
+            ),
+            ("fluid_ticks", list(vec![tick])),
+            (
+                "structures",
+                Node::Compound(compound(vec![(
+                    "starts",
+                    Node::Compound(compound(vec![(
+                        "minecraft:fortress",
+                        Node::Compound(fortress),
+                    )])),
+                )])),
+            ),
+        ])
+    }
+
+    #[test]
+    fn full_chunk_1_18() -> DrawResult<()> {
+        let chunk = Chunk::from_compound(chunk_1_18())?;
+        assert_eq!((-64, 320), (chunk.min_y(), chunk.max_y()));
+        assert_eq!("minecraft:deepslate_diamond_ore", chunk.id_of(0, -64, 0));
+        assert_eq!("minecraft:deepslate", chunk.id_of(0, -64, 1));
+        assert_eq!("minecraft:air", chunk.id_of(15, 319, 15));
+        assert_eq!("", chunk.id_of(0, 0, 0));
+        assert_eq!(-48, chunk.height_of(0, 0));
+
+        assert_eq!(
+            Some("minecraft:dripstone_caves"),
+            chunk.biome_name_of(0, -64, 0)
+        );
+        assert!(chunk.biome_of(0, -64, 0).is_none());
+        assert_eq!(
+            Some("minecraft:windswept_hills"),
+            chunk.biome_name_of(4, -64, 0)
+        );
+        assert!(matches!(chunk.biome_of(4, -64, 0), Some(Biome::Mountains)));
+        assert!(matches!(chunk.biome_of(15, 310, 15), Some(Biome::Plains)));
+
+        assert_eq!("minecraft:lava", chunk.liquid_ticks[0].id);
+        assert_eq!("minecraft:fortress", chunk.structures.starts[0].name);
+        Ok(())
+    }
+
+    /// A 1.12 chunk with numeric block ids.
+    fn chunk_1_12() -> Compound {
+        // Stone with red wool in the corner, a birch log at x 1, and a block id needing the Add array at x 2.
+        let mut blocks = vec![1i8; 4096];
+        let mut data = vec![0i8; 2048];
+        let mut add = vec![0i8; 2048];
+        blocks[0] = 35;
+        data[0] = 14;
+        blocks[1] = 17;
+        data[0] |= 2 << 4;
+        blocks[2] = 0x23;
+        add[1] = 0x01;
+
+        let section = compound(vec![
+            ("Y", Node::Byte(1)),
+            ("Blocks", Node::ByteArray(blocks)),
+            ("Add", Node::ByteArray(add)),
+            ("Data", Node::ByteArray(data)),
+        ]);
+        let tick = compound(vec![
+            ("i", Node::Int(55)),
+            ("x", Node::Int(0)),
+            ("y", Node::Int(20)),
+            ("z", Node::Int(0)),
+            ("t", Node::Int(1)),
+        ]);
+        let level = compound(vec![
+            ("Sections", list(vec![section])),
+            ("HeightMap", Node::IntArray(vec![32; 256])),
+            ("Biomes", Node::ByteArray(vec![-127; 256])),
+            ("TileTicks", list(vec![tick])),
+        ]);
+        compound(vec![
+            ("DataVersion", Node::Int(1343)),
+            ("Level", Node::Compound(level)),
+        ])
+    }
+
+    #[test]
+    fn legacy_round_trip() -> DrawResult<()> {
+        let mut root = chunk_1_12();
+        let level = root.get_compound_mut("Level").unwrap();
+        let sections = match level.get_mut("Sections") {
+            Some(Node::List(_, sections)) => sections,
+            _ => unreachable!(),
+        };
+        let original = sections[0].as_compound_mut().unwrap();
+        // Stone with data values that don't exist, which read as plain stone.
+        let mut data = original.get_byte_array("Data").unwrap().to_vec();
+        data[5] = 9 | 9 << 4;
+        original.insert("Data", Node::ByteArray(data));
+        let original = original.clone();
+
+        let mut chunk = Chunk::from_compound(root)?;
+        let written = chunk.to_compound()?;
+        let level = written.get_compound("Level").unwrap();
+        let section = compounds(level, "Sections").next().unwrap();
+        for name in ["Blocks", "Add", "Data"] {
+            assert_eq!(original.get_byte_array(name), section.get_byte_array(name));
+        }
+        let tick = compounds(level, "TileTicks").next().unwrap();
+        assert_eq!(Some(55), tick.get_i32("i"));
+
+        // Only changed blocks are encoded again.
+        chunk.set_block(0, 16, 3, &BlockState::new("minecraft:gold_block"));
+        let written = chunk.to_compound()?;
+        let section = compounds(written.get_compound("Level").unwrap(), "Sections")
+            .next()
+            .unwrap();
+        let blocks = section.get_byte_array("Blocks").unwrap();
+        assert_eq!((0x23, 41), (blocks[2], blocks[3]));
+        assert_eq!(
+            original.get_byte_array("Add"),
+            section.get_byte_array("Add")
+        );
+
+        chunk.set_block(0, 16, 4, &BlockState::new("minecraft:deepslate"));
+        assert!(matches!(
+            chunk.to_compound(),
+            Err(DrawError::NoLegacyId(name)) if name == "minecraft:deepslate"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn full_chunk_1_12() -> DrawResult<()> {
+        let chunk = Chunk::from_compound(chunk_1_12())?;
+        // Chunk positions are z then y then x.
+        assert_eq!("minecraft:red_wool", chunk.id_of(0, 16, 0));
+        assert_eq!("minecraft:birch_log", chunk.id_of(0, 16, 1));
+        assert_eq!("minecraft:stone", chunk.id_of(0, 16, 3));
+        assert_eq!("minecraft:air", chunk.id_of(0, 16, 2));
+        assert_eq!(32, chunk.height_of(5, 5));
+        assert!(matches!(
+            chunk.biome_of(0, 0, 0),
+            Some(Biome::SunflowerPlains)
+        ));
+        assert_eq!("minecraft:redstone_wire", chunk.block_ticks[0].id);
+        Ok(())
+    }
+
+    #[test]
+    fn set_block_and_write() -> DrawResult<()> {
+        let mut chunk = Chunk::from_compound(chunk_1_16())?;
+        let diamond = BlockState::new("minecraft:diamond_block");
+
+        chunk.set_block(1, 5, 1, &diamond);
+        // Into the section only holding light, and one that wasn't stored.
+        chunk.set_block(2, 16, 2, &diamond);
+        chunk.set_block(3, 100, 3, &diamond);
+        // Enough states for 5 bits per block.
+        for i in 0..16 {
+            let wool = BlockState::new(format!("minecraft:wool_{}", i));
+            chunk.set_block(i, 6, 0, &wool);
+        }
+
+        // The chest is replaced, so its block entity goes.
+        chunk.set_block(2, 5, 1, &diamond);
+        assert!(chunk.block_entities.is_empty());
+        assert!(!chunk.light_on);
+        assert_eq!(None, chunk.heightmap(Heightmap::WorldSurface));
+        assert_eq!(101, chunk.height_of(3, 3));
+
+        let written = chunk.to_compound()?;
+        let level = written.get_compound("Level").unwrap();
+        assert_eq!(Some(0), level.get_i8("isLightOn"));
+        let stone = compounds(level, "Sections")
+            .find(|s| s.get_i8("Y") == Some(0))
+            .unwrap();
+        assert_eq!(19, stone.get_list("Palette").unwrap().len());
+        assert_eq!(342, stone.get_long_array("BlockStates").unwrap().len());
+
+        let read = Chunk::from_compound(written)?;
+        assert_eq!("minecraft:diamond_block", read.id_of(1, 5, 1));
+        assert_eq!("minecraft:diamond_block", read.id_of(2, 16, 2));
+        assert_eq!("minecraft:diamond_block", read.id_of(3, 100, 3));
+        assert_eq!("minecraft:wool_15", read.id_of(15, 6, 0));
+        assert_eq!("minecraft:stone", read.id_of(4, 7, 0));
+        assert_eq!("minecraft:air", read.id_of(3, 99, 3));
+        assert_eq!(Some("top"), read.state_of(0, 0, 0).property("type"));
+        assert_eq!(
+            Some(255),
+            read.section(1).unwrap().sky_light.as_ref().map(|l| l[0])
+        );
+        assert_eq!(chunk.liquid_ticks, read.liquid_ticks);
+        assert_eq!(chunk.structures, read.structures);
+        assert_eq!(chunk.post_processing, read.post_processing);
+        assert_eq!((-2, 0, 20), (read.x, read.z, read.inhabited_time));
+        Ok(())
+    }
+
+    #[test]
+    fn compact() -> DrawResult<()> {
+        let stone = BlockState::new("minecraft:stone");
+        let mut section = Section::new(0);
+        section.set_state(0, 0, 0, &BlockState::new("minecraft:dirt"));
+        section.set_state(0, 0, 0, &stone);
+        // An equal state that isn't shared with the first.
+        section.palette.push(Arc::new(stone.clone()));
+        section.states[1] = 3;
+
+        assert!(section.compact());
+        assert_eq!(2, section.palette.len());
+        // In the order blocks first use them.
+        assert_eq!("minecraft:stone", section.palette[0].name);
+        assert_eq!(
+            (0, 0, 1),
+            (section.states[0], section.states[1], section.states[2])
+        );
+        assert!(!section.compact());
+        assert!(!section.is_air());
+
+        let mut chunk = Chunk::from_compound(chunk_1_16())?;
+        chunk.set_block(3, 100, 3, &stone);
+        chunk.set_block(3, 100, 3, &BlockState::new("minecraft:cave_air"));
+        assert!(chunk.section(6).is_some());
+
+        assert!(chunk.compact());
+        assert!(chunk.section(6).is_none());
+        // Still holding light.
+        assert!(chunk.section(1).is_some());
+        assert!(!chunk.compact());
+
+        let read = Chunk::from_compound(chunk.to_compound()?)?;
+        assert!(read.section(6).is_none());
+        assert_eq!("minecraft:air", read.state_of(3, 100, 3).name);
+        assert_eq!(chunk.state_of(0, 0, 0), read.state_of(0, 0, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn compact_keeps_biomes() -> DrawResult<()> {
+        // The 1.18 fixture's top section is all air, but holds its biomes.
+        let mut chunk = Chunk::from_compound(chunk_1_18())?;
+        chunk.compact();
+        assert!(chunk.section(19).is_some());
+
+        for _ in 0..2 {
+            chunk = Chunk::from_compound(chunk.to_compound()?)?;
+            assert_eq!(Some("minecraft:plains"), chunk.biome_name_of(15, 310, 15));
+            assert_eq!("minecraft:air", chunk.id_of(15, 310, 15));
+            assert_eq!(2, chunk.sections().count());
+        }
+
+        // Without blocks, as written by other tools, the biomes are still read.
+        let mut root = chunk_1_18();
+        let sections = match root.get_mut("sections") {
+            Some(Node::List(_, v)) => v,
+            _ => unreachable!(),
+        };
+        if let Some(Node::Compound(top)) = sections.last_mut() {
+            top.remove("block_states");
+        }
+        let chunk = Chunk::from_compound(root)?;
+        assert_eq!(Some("minecraft:plains"), chunk.biome_name_of(15, 310, 15));
+        Ok(())
+    }
+
+    #[test]
+    fn write_unchanged() -> DrawResult<()> {
+        for root in [chunk_1_12(), chunk_1_16(), chunk_1_18()] {
+            let chunk = Chunk::from_compound(root)?;
+            let read = Chunk::from_compound(chunk.to_compound()?)?;
+
+            for (x, y, z) in [(0, 16, 0), (0, 16, 1), (0, -64, 0), (0, 0, 0), (5, 40, 5)] {
+                assert_eq!(chunk.state_of(x, y, z), read.state_of(x, y, z));
+                assert_eq!(chunk.biome_name_of(x, y, z), read.biome_name_of(x, y, z));
+                assert_eq!(chunk.height_of(x, z), read.height_of(x, z));
+            }
+            assert_eq!((chunk.min_y(), chunk.max_y()), (read.min_y(), read.max_y()));
+            assert_eq!(chunk.block_ticks, read.block_ticks);
+            assert_eq!(chunk.liquid_ticks, read.liquid_ticks);
+            assert_eq!(chunk.light_on, read.light_on);
+        }
+
+        // Numeric ids and data values are kept for old chunks, and 1.18 sections filled with one state need no data.
+        let legacy = Chunk::from_compound(chunk_1_12())?.to_compound()?;
+        let section = &compounds(legacy.get_compound("Level").unwrap(), "Sections")
+            .next()
+            .unwrap();
+        assert_eq!(Some(35), section.get_byte_array("Blocks").map(|b| b[0]));
+        assert_eq!(
+            Some(14 | 2 << 4),
+            section.get_byte_array("Data").map(|d| d[0])
+        );
+
+        let flat = Chunk::from_compound(chunk_1_18())?.to_compound()?;
+        let top = compounds(&flat, "sections")
+            .find(|s| s.get_i8("Y") == Some(19))
+            .unwrap();
+        assert!(!top.get_compound("block_states").unwrap().contains("data"));
+        assert_eq!(Some(-4), flat.get_i32("yPos"));
+        Ok(())
+    }
+
+    #[test]
+    fn light() -> DrawResult<()> {
+        let mut root = chunk_1_16();
+        let level = root.get_compound_mut("Level").unwrap();
+        level.insert("isLightOn", Node::Byte(1));
+
+        // A torch lit block next to the slab, and darkness below the bottom of the light only section above.
+        let sections = match level.get_mut("Sections") {
+            Some(Node::List(_, sections)) => sections,
+            _ => unreachable!(),
+        };
+        let stone = sections[1].as_compound_mut().unwrap();
+        let mut block_light = vec![0i8; 2048];
+        block_light[0] = 0x0E << 4;
+        stone.insert("BlockLight", Node::ByteArray(block_light));
+        let light = sections[2].as_compound_mut().unwrap();
+        let mut sky_light = vec![-1i8; 2048];
+        sky_light[0] = 0x70;
+        light.insert("SkyLight", Node::ByteArray(sky_light));
+
+        let chunk = Chunk::from_compound(root.clone())?;
+        assert_eq!(Some(0), chunk.block_light_of(0, 0, 0));
+        assert_eq!(Some(14), chunk.block_light_of(0, 0, 1));
+        assert_eq!(Some(0), chunk.block_light_of(0, 200, 1));
+        assert_eq!(Some(0), chunk.sky_light_of(0, 15, 0));
+        assert_eq!(Some(0), chunk.sky_light_of(0, 16, 0));
+        assert_eq!(Some(7), chunk.sky_light_of(0, 16, 1));
+        // No section stored until the top of the world, so only the light only section's bottom is known.
+        assert_eq!(Some(15), chunk.sky_light_of(0, 40, 0));
+        assert_eq!(Some(15), chunk.sky_light_of(0, 300, 0));
+
+        let level = root.get_compound_mut("Level").unwrap();
+        level.insert("isLightOn", Node::Byte(0));
+        let chunk = Chunk::from_compound(root)?;
+        assert_eq!(None, chunk.block_light_of(0, 0, 1));
+        assert_eq!(None, chunk.sky_light_of(0, 16, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn missing_heightmap() -> DrawResult<()> {
+        let mut root = chunk_1_16();
+        root.get_compound_mut("Level").unwrap().remove("Heightmaps");
+
+        let mut chunk = Chunk::from_compound(root)?;
+        assert_eq!(None, chunk.heightmap(Heightmap::WorldSurface));
+        assert_eq!(16, chunk.height_of(0, 0));
+        assert_eq!(16, chunk.heightmap_of(Heightmap::MotionBlocking, 7, 3));
+
+        chunk.recompute_heightmaps();
+        assert_eq!(Some(&[16; 256][..]), chunk.heightmap(Heightmap::OceanFloor));
+        assert_eq!(None, chunk.heightmap(Heightmap::OceanFloorWg));
+        Ok(())
+    }
+
+    #[test]
+    fn heightmaps_of_proto_chunk() -> DrawResult<()> {
+        let mut root = chunk_1_16();
+        let level = root.get_compound_mut("Level").unwrap();
+        level.insert("Status", Node::String("carvers".to_owned()));
+
+        // Torches on top of the stone along x, which only the world surface counts.
+        let mut states = vec![0; 256];
+        states[..16].fill(1);
+        let sections = match level.get_mut("Sections") {
+            Some(Node::List(_, sections)) => sections,
+            _ => unreachable!(),
+        };
+        let torches = compound(vec![
+            ("Y", Node::Byte(1)),
+            (
+                "Palette",
+                list(vec![
+                    compound(vec![("Name", Node::String("minecraft:air".to_owned()))]),
+                    compound(vec![("Name", Node::String("minecraft:torch".to_owned()))]),
+                ]),
+            ),
+            ("BlockStates", Node::LongArray(states)),
+        ]);
+        sections[2] = Node::Compound(torches);
+
+        let mut wg = vec![0i64; 37];
+        wg[0] = 5;
+        level
+            .get_compound_mut("Heightmaps")
+            .unwrap()
+            .insert("OCEAN_FLOOR_WG", Node::LongArray(wg));
+
+        let chunk = Chunk::from_compound(root)?;
+        // The stored world surface isn't complete yet, so is worked out from the blocks.
+        assert_eq!(None, chunk.heightmap(Heightmap::WorldSurface));
+        assert_eq!(17, chunk.height_of(0, 0));
+        assert_eq!(16, chunk.height_of(0, 1));
+        assert_eq!(16, chunk.heightmap_of(Heightmap::MotionBlocking, 0, 0));
+        assert_eq!(5, chunk.heightmap_of(Heightmap::OceanFloorWg, 0, 0));
+        assert_eq!(
+            vec![17, 16, 16],
+            chunk.compute_heightmap(Heightmap::WorldSurface)[..3].to_vec()
+        );
+
+        // Every stored heightmap is written back as it was.
+        let written = chunk.to_compound()?;
+        let stored = |c: &Compound, name: &str| {
+            c.get_compound("Level")
+                .and_then(|l| l.get_compound("Heightmaps"))
+                .and_then(|h| h.get_long_array(name))
+                .map(|h| h.to_vec())
+        };
+        for name in ["WORLD_SURFACE", "OCEAN_FLOOR_WG"] {
+            assert!(stored(&written, name).is_some());
+            assert_eq!(stored(chunk.raw(), name), stored(&written, name));
+        }
+
+        // Setting a block leaves them out of date, but they are kept to be recomputed.
+        let mut chunk = chunk;
+        chunk.set_block(0, 40, 0, &BlockState::new("minecraft:stone"));
+        assert_eq!(None, chunk.heightmap(Heightmap::OceanFloorWg));
+        assert_eq!(41, chunk.heightmap_of(Heightmap::OceanFloorWg, 0, 0));
+        chunk.recompute_heightmaps();
+        assert_eq!(41, chunk.heightmap(Heightmap::OceanFloorWg).unwrap()[0]);
+        let written = Chunk::from_compound(chunk.to_compound()?)?;
+        assert_eq!(41, written.heightmap_of(Heightmap::OceanFloorWg, 0, 0));
+        Ok(())
+    }
+}
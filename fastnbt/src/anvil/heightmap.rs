@@ -0,0 +1,212 @@
+//! The kinds of heightmap stored in chunks, and which blocks each counts.
+
+use crate::block::BlockState;
+
+/// A kind of heightmap. Each gives one above the highest block it counts in every column of a chunk.
+///
+/// The `Wg` kinds are used during world generation and are dropped once a chunk is fully generated. The others are
+/// only complete from then on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Heightmap {
+    /// Blocks that stop movement, and fluids. Used for rain and snow.
+    MotionBlocking,
+    /// As `MotionBlocking`, ignoring leaves.
+    MotionBlockingNoLeaves,
+    /// Blocks that stop movement.
+    OceanFloor,
+    OceanFloorWg,
+    /// Anything but air.
+    WorldSurface,
+    WorldSurfaceWg,
+}
+
+impl Heightmap {
+    pub const ALL: [Heightmap; 6] = [
+        Heightmap::MotionBlocking,
+        Heightmap::MotionBlockingNoLeaves,
+        Heightmap::OceanFloor,
+        Heightmap::OceanFloorWg,
+        Heightmap::WorldSurface,
+        Heightmap::WorldSurfaceWg,
+    ];
+
+    /// The name of the heightmap in a chunk's `Heightmaps` compound, eg `WORLD_SURFACE`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Heightmap::MotionBlocking => "MOTION_BLOCKING",
+            Heightmap::MotionBlockingNoLeaves => "MOTION_BLOCKING_NO_LEAVES",
+            Heightmap::OceanFloor => "OCEAN_FLOOR",
+            Heightmap::OceanFloorWg => "OCEAN_FLOOR_WG",
+            Heightmap::WorldSurface => "WORLD_SURFACE",
+            Heightmap::WorldSurfaceWg => "WORLD_SURFACE_WG",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|h| h.name() == name)
+    }
+
+    /// Whether the heightmap is only kept during world generation.
+    pub fn is_worldgen(self) -> bool {
+        matches!(self, Heightmap::OceanFloorWg | Heightmap::WorldSurfaceWg)
+    }
+
+    /// Whether a block counts towards the heightmap's height.
+    pub fn counts(self, state: &BlockState) -> bool {
+        match self {
+            Heightmap::WorldSurface | Heightmap::WorldSurfaceWg => !is_air(state),
+            Heightmap::OceanFloor | Heightmap::OceanFloorWg => blocks_motion(state),
+            Heightmap::MotionBlocking => blocks_motion(state) || has_fluid(state),
+            Heightmap::MotionBlockingNoLeaves => {
+                (blocks_motion(state) || has_fluid(state)) && !state.name.ends_with("_leaves")
+            }
+        }
+    }
+}
+
+fn is_air(state: &BlockState) -> bool {
+    matches!(
+        state.name.as_str(),
+        "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air"
+    )
+}
+
+fn has_fluid(state: &BlockState) -> bool {
+    matches!(
+        state.name.as_str(),
+        "minecraft:water"
+            | "minecraft:lava"
+            | "minecraft:bubble_column"
+            | "minecraft:kelp"
+            | "minecraft:kelp_plant"
+            | "minecraft:seagrass"
+            | "minecraft:tall_seagrass"
+    ) || state.property("waterlogged") == Some("true")
+}
+
+/// Whether a block stops movement. The game decides this by the block's material, which isn't stored, so this goes
+/// by name. Plants, decorations such as torches and rails, and fluids don't.
+fn blocks_motion(state: &BlockState) -> bool {
+    if is_air(state) {
+        return false;
+    }
+
+    let name = state.name.strip_prefix("minecraft:").unwrap_or(&state.name);
+
+    const PASSABLE: &[&str] = &[
+        "water",
+        "lava",
+        "bubble_column",
+        "kelp",
+        "kelp_plant",
+        "seagrass",
+        "tall_seagrass",
+        "grass",
+        "short_grass",
+        "tall_grass",
+        "fern",
+        "large_fern",
+        "dead_bush",
+        "vine",
+        "glow_lichen",
+        "sugar_cane",
+        "snow",
+        "fire",
+        "soul_fire",
+        "redstone_wire",
+        "tripwire",
+        "tripwire_hook",
+        "lever",
+        "ladder",
+        "repeater",
+        "comparator",
+        "nether_portal",
+        "end_portal",
+        "end_gateway",
+        "cobweb",
+        "lily_pad",
+        "structure_void",
+        "light",
+        "dandelion",
+        "poppy",
+        "blue_orchid",
+        "allium",
+        "azure_bluet",
+        "oxeye_daisy",
+        "cornflower",
+        "lily_of_the_valley",
+        "wither_rose",
+        "torchflower",
+        "pink_petals",
+        "sunflower",
+        "lilac",
+        "rose_bush",
+        "peony",
+        "pitcher_plant",
+        "wheat",
+        "carrots",
+        "potatoes",
+        "beetroots",
+        "nether_wart",
+        "sweet_berry_bush",
+        "cocoa",
+        "melon_stem",
+        "pumpkin_stem",
+        "attached_melon_stem",
+        "attached_pumpkin_stem",
+        "brown_mushroom",
+        "red_mushroom",
+        "crimson_fungus",
+        "warped_fungus",
+        "crimson_roots",
+        "warped_roots",
+        "nether_sprouts",
+        "cave_vines",
+        "cave_vines_plant",
+        "weeping_vines",
+        "weeping_vines_plant",
+        "twisting_vines",
+        "twisting_vines_plant",
+        "hanging_roots",
+        "spore_blossom",
+        "small_dripleaf",
+        "flower_pot",
+    ];
+    const PASSABLE_SUFFIXES: &[&str] = &[
+        "_sapling", "_tulip", "torch", "_button", "rail", "_carpet", "_skull",
+    ];
+
+    !(PASSABLE.contains(&name)
+        || name.starts_with("potted_")
+        || (name.ends_with("_head") && name != "piston_head")
+        || PASSABLE_SUFFIXES.iter().any(|s| name.ends_with(s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counted_blocks() {
+        let stone = BlockState::new("minecraft:stone");
+        let water = BlockState::new("minecraft:water");
+        let leaves = BlockState::new("minecraft:oak_leaves");
+        let torch = BlockState::new("minecraft:wall_torch");
+        let wet_stairs =
+            BlockState::new("minecraft:oak_stairs").with_property("waterlogged", "true");
+
+        for kind in Heightmap::ALL {
+            assert!(kind.counts(&stone));
+            assert!(!kind.counts(&BlockState::new("minecraft:cave_air")));
+            assert_eq!(Some(kind), Heightmap::from_name(kind.name()));
+        }
+
+        assert!(Heightmap::WorldSurface.counts(&torch));
+        assert!(!Heightmap::MotionBlocking.counts(&torch));
+        assert!(Heightmap::MotionBlocking.counts(&water));
+        assert!(!Heightmap::OceanFloor.counts(&water));
+        assert!(Heightmap::MotionBlocking.counts(&leaves));
+        assert!(!Heightmap::MotionBlockingNoLeaves.counts(&leaves));
+        assert!(Heightmap::OceanFloorWg.counts(&wet_stairs));
+    }
+}
@@ -0,0 +1,188 @@
+//! The LZ4 block stream format Minecraft uses for chunks, ie that of lz4-java's `LZ4BlockOutputStream`.
+//!
+//! Data is split into blocks, each with a header of the magic `LZ4Block`, a token giving the compression method, the
+//! compressed and decompressed lengths, and a checksum of the decompressed data. Lengths and checksum are little
+//! endian. The stream ends with an empty block.
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{self, Read};
+
+const MAGIC: &[u8; 8] = b"LZ4Block";
+const HEADER_LEN: usize = MAGIC.len() + 1 + 4 + 4 + 4;
+
+const METHOD_RAW: u8 = 0x10;
+const METHOD_LZ4: u8 = 0x20;
+
+/// Size of blocks written, the lz4-java default.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Seed of the xxHash32 checksum, masked to 28 bits as lz4-java does.
+const CHECKSUM_SEED: u32 = 0x9747b28c;
+
+fn checksum(data: &[u8]) -> u32 {
+    xxhash_rust::xxh32::xxh32(data, CHECKSUM_SEED) & 0x0FFF_FFFF
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("lz4 block stream: {}", msg),
+    )
+}
+
+/// Decompresses an LZ4 block stream.
+pub struct Lz4BlockDecoder<R: Read> {
+    inner: R,
+    block: Vec<u8>,
+    pos: usize,
+    finished: bool,
+}
+
+impl<R: Read> Lz4BlockDecoder<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            block: vec![],
+            pos: 0,
+            finished: false,
+        }
+    }
+
+    /// Read the next block into the buffer. Returns false at the end of the stream.
+    fn next_block(&mut self) -> io::Result<bool> {
+        let mut header = [0u8; HEADER_LEN];
+        self.inner.read_exact(&mut header)?;
+
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(invalid("bad magic"));
+        }
+
+        let mut fields = &header[MAGIC.len()..];
+        let method = fields.read_u8()? & 0xF0;
+        let compressed_len = fields.read_u32::<LittleEndian>()? as usize;
+        let len = fields.read_u32::<LittleEndian>()? as usize;
+        let expected = fields.read_u32::<LittleEndian>()?;
+
+        if len == 0 {
+            return Ok(false);
+        }
+        // Blocks are at most 32 MiB in lz4-java, so anything larger is corrupt.
+        if len > 1 << 25 || compressed_len > 1 << 25 {
+            return Err(invalid("block too large"));
+        }
+
+        let mut compressed = vec![0; compressed_len];
+        self.inner.read_exact(&mut compressed)?;
+
+        self.block.resize(len, 0);
+        match method {
+            METHOD_RAW if compressed_len == len => self.block.copy_from_slice(&compressed),
+            METHOD_LZ4 => {
+                let n = lz4_flex::block::decompress_into(&compressed, &mut self.block)
+                    .map_err(|e| invalid(&e.to_string()))?;
+                if n != len {
+                    return Err(invalid("wrong decompressed length"));
+                }
+            }
+            _ => return Err(invalid("unknown compression method")),
+        }
+
+        if checksum(&self.block) != expected {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        self.pos = 0;
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for Lz4BlockDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.block.len() {
+            if self.finished || !self.next_block()? {
+                self.finished = true;
+                return Ok(0);
+            }
+        }
+
+        let n = buf.len().min(self.block.len() - self.pos);
+        buf[..n].copy_from_slice(&self.block[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Compress data into an LZ4 block stream.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    // The token's low bits hold log2 of the block size less 10.
+    let level = (BLOCK_SIZE.trailing_zeros() - 10) as u8;
+
+    for block in data.chunks(BLOCK_SIZE) {
+        let compressed = lz4_flex::block::compress(block);
+        let (method, body) = if compressed.len() < block.len() {
+            (METHOD_LZ4, compressed.as_slice())
+        } else {
+            (METHOD_RAW, block)
+        };
+
+        write_header(
+            &mut out,
+            method | level,
+            body.len(),
+            block.len(),
+            checksum(block),
+        );
+        out.extend_from_slice(body);
+    }
+
+    write_header(&mut out, METHOD_RAW | level, 0, 0, 0);
+    out
+}
+
+fn write_header(out: &mut Vec<u8>, token: u8, compressed_len: usize, len: usize, checksum: u32) {
+    out.extend_from_slice(MAGIC);
+    // Writing to a Vec can't fail.
+    out.write_u8(token).unwrap();
+    out.write_u32::<LittleEndian>(compressed_len as u32)
+        .unwrap();
+    out.write_u32::<LittleEndian>(len as u32).unwrap();
+    out.write_u32::<LittleEndian>(checksum).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = vec![];
+        Lz4BlockDecoder::new(data).read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn round_trip_multiple_blocks() -> io::Result<()> {
+        let data: Vec<u8> = (0..3 * BLOCK_SIZE + 17).map(|i| (i % 251) as u8).collect();
+        assert_eq!(data, decompress(&compress(&data))?);
+        assert_eq!(Vec::<u8>::new(), decompress(&compress(&[]))?);
+        Ok(())
+    }
+
+    #[test]
+    fn raw_block() -> io::Result<()> {
+        // Too short to compress, so stored raw.
+        let compressed = compress(b"abc");
+        assert_eq!(METHOD_RAW, compressed[8] & 0xF0);
+        assert_eq!(b"abc".to_vec(), decompress(&compressed)?);
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_data_errors() {
+        let mut compressed = compress(b"some data to check");
+        compressed[HEADER_LEN] ^= 1;
+        assert!(decompress(&compressed).is_err());
+        assert!(decompress(b"LZ4Blocx").is_err());
+        assert!(decompress(&compressed[..HEADER_LEN + 2]).is_err());
+    }
+}
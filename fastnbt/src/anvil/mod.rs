@@ -1,24 +1,126 @@
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
 use num_enum::TryFromPrimitive;
 use std::convert::TryFrom;
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub const SECTOR_SIZE: usize = 4096;
 pub const HEADER_SIZE: usize = 2 * SECTOR_SIZE;
 
 pub mod biome;
 pub mod bits;
+pub mod check;
+pub mod chunk;
 pub mod draw;
+pub mod heightmap;
+pub mod lz4;
 
-#[derive(Debug, TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive)]
 #[repr(u8)]
 pub enum CompressionScheme {
     Gzip = 1,
     Zlib = 2,
+    Uncompressed = 3,
+    /// The block stream format of lz4-java, see `lz4`. Used when a server sets `region-file-compression=lz4`.
+    Lz4 = 4,
 }
 
+impl CompressionScheme {
+    /// A reader giving the decompressed form of data compressed with this scheme.
+    pub fn decoder<'a, R: Read + 'a>(self, compressed: R) -> Box<dyn Read + 'a> {
+        match self {
+            CompressionScheme::Gzip => Box::new(GzDecoder::new(compressed)),
+            CompressionScheme::Zlib => Box::new(ZlibDecoder::new(compressed)),
+            CompressionScheme::Uncompressed => Box::new(compressed),
+            CompressionScheme::Lz4 => Box::new(lz4::Lz4BlockDecoder::new(compressed)),
+        }
+    }
+
+    /// Compress data with this scheme, using the default level for schemes that have one.
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(match self {
+            CompressionScheme::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+            CompressionScheme::Zlib => {
+                let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+            CompressionScheme::Uncompressed => data.to_vec(),
+            CompressionScheme::Lz4 => lz4::compress(data),
+        })
+    }
+}
+
+/// Decompress chunk data as loaded by `Region::load_chunk_data`, ie starting with its 5 byte header.
+pub fn decompress_chunk(data: &[u8]) -> Result<Box<dyn Read + '_>> {
+    let meta = ChunkMeta::new(data)?;
+    if meta.external {
+        return Err(Error::InvalidChunkMeta);
+    }
+
+    let end = 4 + meta.len as usize;
+    if meta.len == 0 || end > data.len() {
+        return Err(Error::InsufficientData);
+    }
+
+    Ok(meta.compression_scheme.decoder(&data[5..end]))
+}
+
+/// Set in a chunk's compression scheme byte when its data is stored in a separate `.mcc` file.
+const EXTERNAL_FLAG: u8 = 128;
+
 pub struct Region<S: Seek + Read> {
     data: S,
+    external: Option<ExternalChunks>,
+}
+
+/// Where a region keeps chunks too large to fit in it, ie `c.<x>.<z>.mcc` files next to the region file. The file
+/// names use absolute chunk coordinates, so the region's own coordinates are needed to find them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalChunks {
+    pub dir: PathBuf,
+    pub region_x: isize,
+    pub region_z: isize,
+}
+
+impl ExternalChunks {
+    pub fn new<P: Into<PathBuf>>(dir: P, region_x: isize, region_z: isize) -> Self {
+        Self {
+            dir: dir.into(),
+            region_x,
+            region_z,
+        }
+    }
+
+    /// External chunks for a region file named `r.<x>.<z>.mca`, or `None` if the name is not of that form.
+    pub fn for_region_file<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let path = path.as_ref();
+        let (x, z) = crate::world::region_coords(path)?;
+
+        let dir = match path.parent() {
+            Some(p) if p != Path::new("") => p.to_owned(),
+            _ => PathBuf::from("."),
+        };
+
+        Some(Self::new(dir, x, z))
+    }
+
+    /// Path of the external file for the chunk at `x`, `z` within the region.
+    pub fn path(&self, x: usize, z: usize) -> PathBuf {
+        let cx = self.region_x * 32 + x as isize;
+        let cz = self.region_z * 32 + z as isize;
+        self.dir.join(format!("c.{}.{}.mcc", cx, cz))
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -27,12 +129,27 @@ pub struct ChunkLocation {
     pub sector_count: usize,
     pub x: usize,
     pub z: usize,
+    /// When the chunk was last saved, in seconds since the Unix epoch. Zero if unknown. Only filled in by
+    /// `Region::chunk_locations`; use `Region::chunk_timestamp` for a single chunk.
+    pub timestamp: u32,
+}
+
+impl ChunkLocation {
+    /// When the chunk was last saved, if known.
+    pub fn modified(&self) -> Option<SystemTime> {
+        match self.timestamp {
+            0 => None,
+            t => Some(UNIX_EPOCH + Duration::from_secs(t as u64)),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct ChunkMeta {
     pub len: u32,
     pub compression_scheme: CompressionScheme,
+    /// The chunk's data is in an external `.mcc` file rather than the region. See `ExternalChunks`.
+    pub external: bool,
 }
 
 impl ChunkMeta {
@@ -41,23 +158,36 @@ impl ChunkMeta {
             return Err(Error::InsufficientData);
         }
 
-        let mut buf = (&data[..5]).clone();
+        let mut buf = &data[..5];
         let len = buf.read_u32::<BigEndian>()?;
         let scheme = buf.read_u8()?;
-        let scheme = CompressionScheme::try_from(scheme).map_err(|_| Error::InvalidChunkMeta)?;
+        let external = scheme & EXTERNAL_FLAG != 0;
+        let scheme = CompressionScheme::try_from(scheme & !EXTERNAL_FLAG)
+            .map_err(|_| Error::UnknownCompressionScheme(scheme & !EXTERNAL_FLAG))?;
 
         Ok(Self {
             len,
             compression_scheme: scheme,
+            external,
         })
     }
 }
 
 impl<S: Seek + Read> Region<S> {
     pub fn new(data: S) -> Self {
-        Self { data }
+        Self {
+            data,
+            external: None,
+        }
+    }
+
+    /// Allow reading and writing chunks too large for the region, which are kept in separate files.
+    pub fn with_external_chunks(mut self, external: ExternalChunks) -> Self {
+        self.external = Some(external);
+        self
     }
 
+    /// Where the chunk is in the region. Only the location table is read, so the returned `timestamp` is zero.
     pub fn chunk_location(&mut self, x: usize, z: usize) -> Result<ChunkLocation> {
         if x >= 32 || z >= 32 {
             return Err(Error::InvalidOffset(x, z));
@@ -71,18 +201,30 @@ impl<S: Seek + Read> Region<S> {
         self.data.read_exact(&mut buf[..])?;
 
         let mut off = 0usize;
-        off = off | ((buf[0] as usize) << 16);
-        off = off | ((buf[1] as usize) << 8);
-        off = off | ((buf[2] as usize) << 0);
+        off |= (buf[0] as usize) << 16;
+        off |= (buf[1] as usize) << 8;
+        off |= buf[2] as usize;
         let count = buf[3] as usize;
         Ok(ChunkLocation {
             begin_sector: off,
             sector_count: count,
             x,
             z,
+            timestamp: 0,
         })
     }
 
+    /// When the chunk was last saved, in seconds since the Unix epoch. Zero if the chunk is not present.
+    pub fn chunk_timestamp(&mut self, x: usize, z: usize) -> Result<u32> {
+        if x >= 32 || z >= 32 {
+            return Err(Error::InvalidOffset(x, z));
+        }
+
+        let pos = SECTOR_SIZE + 4 * (x + z * 32);
+        self.data.seek(SeekFrom::Start(pos as u64))?;
+        Ok(self.data.read_u32::<BigEndian>()?)
+    }
+
     pub fn load_chunk(&mut self, offset: &ChunkLocation, dest: &mut [u8]) -> Result<()> {
         self.data.seek(SeekFrom::Start(
             offset.begin_sector as u64 * SECTOR_SIZE as u64,
@@ -91,6 +233,459 @@ impl<S: Seek + Read> Region<S> {
         self.data.read_exact(dest)?;
         Ok(())
     }
+
+    /// Load a chunk's data, starting with its 5 byte header. Chunks stored in an external file are read from it, and
+    /// given a header describing that data, so the result can be used the same way for every chunk, eg with
+    /// `ChunkMeta::new` or `draw::parse_chunk`.
+    pub fn load_chunk_data(&mut self, loc: &ChunkLocation) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.load_chunk_data_into(loc, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Like `load_chunk_data`, but reusing the given buffer.
+    fn load_chunk_data_into(&mut self, loc: &ChunkLocation, buf: &mut Vec<u8>) -> Result<()> {
+        buf.resize(loc.sector_count * SECTOR_SIZE, 0);
+        self.load_chunk(loc, buf)?;
+
+        let meta = ChunkMeta::new(buf)?;
+        if !meta.external {
+            return Ok(());
+        }
+
+        let path = match &self.external {
+            Some(external) => external.path(loc.x, loc.z),
+            None => return Err(Error::NoExternalChunks(loc.x, loc.z)),
+        };
+
+        buf.truncate(5);
+        buf[4] = meta.compression_scheme as u8;
+        std::fs::File::open(path)?.read_to_end(buf)?;
+
+        let len = (buf.len() - 4) as u32;
+        buf[..4].copy_from_slice(&len.to_be_bytes());
+        Ok(())
+    }
+
+    /// Iterate over the chunks present in the region, in the order they are stored in the file.
+    ///
+    /// One buffer is reused for every chunk, so `Chunks` is not an `Iterator`: each chunk must be dropped before the
+    /// next is read.
+    ///
+    /// ```no_run
+    /// # use fastnbt::anvil::Region;
+    /// # use std::io::Read;
+    /// let mut region = Region::new(std::fs::File::open("r.0.0.mca").unwrap());
+    /// let mut chunks = region.iter_chunks().unwrap();
+    ///
+    /// while let Some(chunk) = chunks.next() {
+    ///     let chunk = chunk.unwrap();
+    ///     let mut nbt = vec![];
+    ///     chunk.reader().read_to_end(&mut nbt).unwrap();
+    ///     println!("chunk {},{} is {} bytes", chunk.location.x, chunk.location.z, nbt.len());
+    /// }
+    /// ```
+    pub fn iter_chunks(&mut self) -> Result<Chunks<'_, S>> {
+        let mut locations: Vec<_> = self
+            .chunk_locations()?
+            .into_iter()
+            .filter(|loc| loc.begin_sector != 0 && loc.sector_count != 0)
+            .collect();
+        locations.sort_by_key(|loc| loc.begin_sector);
+
+        Ok(Chunks {
+            region: self,
+            locations: locations.into_iter(),
+            buf: vec![],
+        })
+    }
+
+    /// Locations of every chunk in the region, including those not present, with their timestamps. Timestamps are
+    /// zero if the region ends before its timestamp table.
+    pub fn chunk_locations(&mut self) -> Result<Vec<ChunkLocation>> {
+        let mut header = [0u8; HEADER_SIZE];
+        self.data.seek(SeekFrom::Start(0))?;
+        let (locations, timestamps) = header.split_at_mut(SECTOR_SIZE);
+        self.data.read_exact(locations)?;
+        match self.data.read_exact(timestamps) {
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => timestamps.fill(0),
+            r => r?,
+        }
+
+        Ok(locations
+            .chunks_exact(4)
+            .zip(timestamps.chunks_exact(4))
+            .enumerate()
+            .map(|(i, (buf, ts))| ChunkLocation {
+                begin_sector: (buf[0] as usize) << 16 | (buf[1] as usize) << 8 | buf[2] as usize,
+                sector_count: buf[3] as usize,
+                x: i % 32,
+                z: i / 32,
+                timestamp: u32::from_be_bytes([ts[0], ts[1], ts[2], ts[3]]),
+            })
+            .collect())
+    }
+
+    /// Write a copy of this region to `out` with its chunks packed contiguously in the order they appear, dropping
+    /// any sectors no chunk refers to. If `level` is given, every chunk is recompressed with zlib at that level,
+    /// otherwise chunk data is copied as is. Timestamps are kept, and are zero if the region ends before its
+    /// timestamp table. `out` is flushed before returning.
+    pub fn compact_into<W: Seek + Write>(
+        &mut self,
+        mut out: W,
+        level: Option<u32>,
+    ) -> Result<Compaction> {
+        let before = self.data.seek(SeekFrom::End(0))?;
+
+        let mut locations: Vec<_> = self
+            .chunk_locations()?
+            .into_iter()
+            .filter(|loc| loc.sector_count > 0)
+            .collect();
+        locations.sort_by_key(|loc| loc.begin_sector);
+
+        let mut header = [0u8; SECTOR_SIZE];
+        let mut timestamps = [0u8; SECTOR_SIZE];
+        let mut next_sector = HEADER_SIZE / SECTOR_SIZE;
+        let mut buf = vec![];
+
+        for loc in &locations {
+            buf.resize(loc.sector_count * SECTOR_SIZE, 0);
+            self.load_chunk(loc, &mut buf)?;
+
+            let meta = ChunkMeta::new(&buf)?;
+            let end = 4 + meta.len as usize;
+            if meta.len == 0 || end > buf.len() {
+                return Err(Error::InvalidChunkMeta);
+            }
+
+            // External chunks keep their file, so only the stub is copied.
+            let mut chunk = match level {
+                Some(level) if !meta.external => {
+                    recompress(&buf[5..end], meta.compression_scheme, level)?
+                }
+                _ => buf[..end].to_vec(),
+            };
+            let sectors = chunk.len().div_ceil(SECTOR_SIZE);
+            if sectors > u8::MAX as usize {
+                return Err(Error::ChunkTooLarge);
+            }
+            chunk.resize(sectors * SECTOR_SIZE, 0);
+
+            out.seek(SeekFrom::Start((next_sector * SECTOR_SIZE) as u64))?;
+            out.write_all(&chunk)?;
+
+            let begin = (next_sector as u32).to_be_bytes();
+            let i = 4 * (loc.x + loc.z * 32);
+            header[i..i + 4].copy_from_slice(&[begin[1], begin[2], begin[3], sectors as u8]);
+            timestamps[i..i + 4].copy_from_slice(&loc.timestamp.to_be_bytes());
+            next_sector += sectors;
+        }
+
+        out.seek(SeekFrom::Start(0))?;
+        out.write_all(&header)?;
+        out.write_all(&timestamps)?;
+        out.flush()?;
+
+        Ok(Compaction {
+            chunks: locations.len(),
+            bytes_before: before,
+            bytes_after: (next_sector * SECTOR_SIZE) as u64,
+        })
+    }
+}
+
+/// Chunks of a region, see `Region::iter_chunks`.
+pub struct Chunks<'a, S: Seek + Read> {
+    region: &'a mut Region<S>,
+    locations: std::vec::IntoIter<ChunkLocation>,
+    buf: Vec<u8>,
+}
+
+impl<S: Seek + Read> Chunks<'_, S> {
+    /// The next chunk, or `None` once every chunk has been read. A chunk that can't be loaded gives an error, and
+    /// the following chunks can still be read.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<RegionChunk<'_>>> {
+        let loc = self.locations.next()?;
+
+        if let Err(e) = self.region.load_chunk_data_into(&loc, &mut self.buf) {
+            return Some(Err(e));
+        }
+
+        let meta = match ChunkMeta::new(&self.buf) {
+            Ok(meta) => meta,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let end = 4 + meta.len as usize;
+        if meta.len == 0 || end > self.buf.len() {
+            return Some(Err(Error::InsufficientData));
+        }
+
+        Some(Ok(RegionChunk {
+            compression_scheme: meta.compression_scheme,
+            location: loc,
+            data: &self.buf[..end],
+        }))
+    }
+}
+
+/// A chunk read from a region by `Chunks`.
+pub struct RegionChunk<'a> {
+    pub location: ChunkLocation,
+    pub compression_scheme: CompressionScheme,
+    data: &'a [u8],
+}
+
+impl RegionChunk<'_> {
+    /// The chunk's data as stored, starting with its 5 byte header, eg for `draw::parse_chunk`.
+    pub fn data(&self) -> &[u8] {
+        self.data
+    }
+
+    /// The chunk's decompressed NBT.
+    pub fn reader(&self) -> Box<dyn Read + '_> {
+        self.compression_scheme.decoder(&self.data[5..])
+    }
+}
+
+/// Read the NBT of every chunk of a region in file order, giving each root to `read` along with the chunk's
+/// coordinates in chunks. The region's coordinates are needed for these. Used for region files holding entities or
+/// points of interest rather than blocks.
+pub(crate) fn read_region_roots<S, T, E, F>(
+    region: &mut Region<S>,
+    region_x: isize,
+    region_z: isize,
+    mut read: F,
+) -> std::result::Result<Vec<T>, E>
+where
+    S: Read + Seek,
+    E: From<Error> + From<crate::nbt::Error>,
+    F: FnMut(&crate::nbt::tree::Compound, isize, isize) -> std::result::Result<T, E>,
+{
+    let mut out = vec![];
+    let mut chunks = region.iter_chunks()?;
+
+    while let Some(chunk) = chunks.next() {
+        let chunk = chunk?;
+        let (_, root) = crate::nbt::tree::read_root(chunk.reader())?;
+
+        out.push(read(
+            &root,
+            region_x * 32 + chunk.location.x as isize,
+            region_z * 32 + chunk.location.z as isize,
+        )?);
+    }
+
+    Ok(out)
+}
+
+/// The result of compacting a region, see `Region::compact_into`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compaction {
+    pub chunks: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl Compaction {
+    /// Bytes saved by compacting. Negative if recompressing at a lower level made chunks bigger.
+    pub fn bytes_reclaimed(&self) -> i64 {
+        self.bytes_before as i64 - self.bytes_after as i64
+    }
+}
+
+/// Decompress chunk data and compress it again with zlib, returning it with its length and scheme prefixed.
+fn recompress(compressed: &[u8], scheme: CompressionScheme, level: u32) -> Result<Vec<u8>> {
+    let mut raw = vec![];
+    scheme.decoder(compressed).read_to_end(&mut raw)?;
+
+    let mut chunk = vec![0; 5];
+    let mut encoder = ZlibEncoder::new(chunk, Compression::new(level));
+    encoder.write_all(&raw)?;
+    chunk = encoder.finish()?;
+
+    let len = (chunk.len() - 4) as u32;
+    chunk[..4].copy_from_slice(&len.to_be_bytes());
+    chunk[4] = CompressionScheme::Zlib as u8;
+    Ok(chunk)
+}
+
+impl<S: Seek + Read + Write> Region<S> {
+    /// Create a new region with no chunks, writing an empty header.
+    pub fn create(mut data: S) -> Result<Self> {
+        data.seek(SeekFrom::Start(0))?;
+        data.write_all(&[0; HEADER_SIZE])?;
+        Ok(Self::new(data))
+    }
+
+    /// Compress and write the given chunk NBT, replacing any chunk already at this location.
+    ///
+    /// The chunk is put in the first free run of sectors large enough to hold it, otherwise it is appended to the
+    /// end of the region. The sectors previously used by the chunk become free, and the chunk's timestamp is set to
+    /// now.
+    ///
+    /// The new data never overwrites the chunk's current sectors. It is written first and the location header is
+    /// only then pointed at it, so an interrupted write leaves the old chunk readable.
+    pub fn write_chunk(&mut self, x: usize, z: usize, uncompressed: &[u8]) -> Result<()> {
+        self.write_chunk_with(x, z, CompressionScheme::Zlib, uncompressed)
+    }
+
+    /// Like `write_chunk`, but compressing with the given scheme rather than zlib.
+    pub fn write_chunk_with(
+        &mut self,
+        x: usize,
+        z: usize,
+        scheme: CompressionScheme,
+        uncompressed: &[u8],
+    ) -> Result<()> {
+        let compressed = scheme.compress(uncompressed)?;
+        self.write_compressed_chunk(x, z, scheme, &compressed)
+    }
+
+    /// Write already compressed chunk data, replacing any chunk already at this location. See `write_chunk`.
+    ///
+    /// Chunks too large for the region are written to an external file if the region has been given
+    /// `ExternalChunks`, otherwise this fails with `Error::ChunkTooLarge`.
+    pub fn write_compressed_chunk(
+        &mut self,
+        x: usize,
+        z: usize,
+        scheme: CompressionScheme,
+        compressed: &[u8],
+    ) -> Result<()> {
+        if x >= 32 || z >= 32 {
+            return Err(Error::InvalidOffset(x, z));
+        }
+
+        // Length includes the compression scheme byte.
+        let len = compressed.len() + 1;
+        let mut sectors = (len + 4).div_ceil(SECTOR_SIZE);
+        let mut buf = Vec::with_capacity(sectors * SECTOR_SIZE);
+
+        let is_external = sectors > u8::MAX as usize;
+        if is_external {
+            let path = match &self.external {
+                Some(external) => external.path(x, z),
+                None => return Err(Error::ChunkTooLarge),
+            };
+            // Replace any existing external file in one step rather than rewriting it in place.
+            let temp = path.with_extension("mcc.tmp");
+            std::fs::write(&temp, compressed)?;
+            std::fs::rename(temp, path)?;
+
+            // The region only keeps a stub pointing at the external file.
+            sectors = 1;
+            buf.write_u32::<BigEndian>(1)?;
+            buf.write_u8(scheme as u8 | EXTERNAL_FLAG)?;
+        } else {
+            buf.write_u32::<BigEndian>(len as u32)?;
+            buf.write_u8(scheme as u8)?;
+            buf.extend_from_slice(compressed);
+        }
+        buf.resize(sectors * SECTOR_SIZE, 0);
+
+        let begin = self.find_free_sectors(sectors)?;
+        self.data
+            .seek(SeekFrom::Start((begin * SECTOR_SIZE) as u64))?;
+        self.data.write_all(&buf)?;
+
+        self.set_location(x, z, begin, sectors)?;
+        self.set_timestamp(x, z, now())?;
+
+        // Only drop the external file once the header no longer points at it.
+        if !is_external {
+            self.remove_external(x, z)?;
+        }
+        Ok(())
+    }
+
+    /// Remove a chunk from the region, freeing its sectors.
+    pub fn remove_chunk(&mut self, x: usize, z: usize) -> Result<()> {
+        if x >= 32 || z >= 32 {
+            return Err(Error::InvalidOffset(x, z));
+        }
+
+        self.remove_external(x, z)?;
+        self.set_location(x, z, 0, 0)?;
+        self.set_timestamp(x, z, 0)
+    }
+
+    /// Delete any external file for a chunk, as it is no longer needed.
+    fn remove_external(&self, x: usize, z: usize) -> Result<()> {
+        if let Some(external) = &self.external {
+            match std::fs::remove_file(external.path(x, z)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the first run of `count` sectors not used by any chunk, or the sector just past the end of the used sectors
+    /// if there is no such run.
+    fn find_free_sectors(&mut self, count: usize) -> Result<usize> {
+        let mut used = vec![true; HEADER_SIZE / SECTOR_SIZE];
+
+        for loc in self.chunk_locations()? {
+            if loc.sector_count == 0 {
+                continue;
+            }
+
+            let end = loc.begin_sector + loc.sector_count;
+            if used.len() < end {
+                used.resize(end, false);
+            }
+            for sector in &mut used[loc.begin_sector..end] {
+                *sector = true;
+            }
+        }
+
+        let mut run = 0;
+        for (i, sector) in used.iter().enumerate() {
+            run = if *sector { 0 } else { run + 1 };
+            if run == count {
+                return Ok(i + 1 - count);
+            }
+        }
+
+        // Continue any free run at the end of the used sectors.
+        Ok(used.len() - run)
+    }
+
+    fn set_location(&mut self, x: usize, z: usize, begin: usize, count: usize) -> Result<()> {
+        let pos = 4 * (x + z * 32);
+        let begin = (begin as u32).to_be_bytes();
+
+        self.data.seek(SeekFrom::Start(pos as u64))?;
+        self.data
+            .write_all(&[begin[1], begin[2], begin[3], count as u8])?;
+        Ok(())
+    }
+
+    fn set_timestamp(&mut self, x: usize, z: usize, timestamp: u32) -> Result<()> {
+        let pos = SECTOR_SIZE + 4 * (x + z * 32);
+
+        self.data.seek(SeekFrom::Start(pos as u64))?;
+        self.data.write_u32::<BigEndian>(timestamp)?;
+        Ok(())
+    }
+}
+
+impl<S: Seek + Read> Region<S> {
+    /// Get back the underlying data, eg to inspect a region written to a `Cursor`.
+    pub fn into_inner(self) -> S {
+        self.data
+    }
+}
+
+/// Seconds since the epoch, as used for chunk timestamps.
+fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or_default()
 }
 
 #[derive(Debug)]
@@ -99,6 +694,12 @@ pub enum Error {
     IO(std::io::Error),
     InvalidOffset(usize, usize),
     InvalidChunkMeta,
+    UnknownCompressionScheme(u8),
+    /// Compressed chunk data needs more than the 255 sectors a region can give a single chunk, and the region has no
+    /// `ExternalChunks` to put it in instead.
+    ChunkTooLarge,
+    /// The chunk at the given location is stored externally, but the region has no `ExternalChunks`.
+    NoExternalChunks(usize, usize),
 }
 
 impl From<std::io::Error> for Error {
@@ -109,6 +710,7 @@ impl From<std::io::Error> for Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+#[derive(Default)]
 pub struct Builder {
     inner: Vec<u8>,
 }
@@ -188,10 +790,303 @@ mod tests {
                 begin_sector: 2,
                 sector_count: 1,
                 x: 0,
-                z: 0
+                z: 0,
+                timestamp: 0,
             },
             r.chunk_location(0, 0)?
         );
         Ok(())
     }
+
+    fn read_back(r: &mut Region<Cursor<Vec<u8>>>, x: usize, z: usize) -> Result<Vec<u8>> {
+        let loc = r.chunk_location(x, z)?;
+        let buf = r.load_chunk_data(&loc)?;
+
+        let mut data = vec![];
+        decompress_chunk(&buf)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn write_and_read_chunks() -> Result<()> {
+        let mut r = Region::create(Cursor::new(vec![]))?;
+        r.write_chunk(0, 0, b"first")?;
+        r.write_chunk(31, 31, b"second")?;
+
+        assert_eq!(b"first".to_vec(), read_back(&mut r, 0, 0)?);
+        assert_eq!(b"second".to_vec(), read_back(&mut r, 31, 31)?);
+        assert_eq!(2, r.chunk_location(0, 0)?.begin_sector);
+        assert_eq!(3, r.chunk_location(31, 31)?.begin_sector);
+        assert_eq!(0, r.chunk_location(1, 0)?.sector_count);
+
+        let len = r.into_inner().into_inner().len();
+        assert_eq!(0, len % SECTOR_SIZE);
+        Ok(())
+    }
+
+    #[test]
+    fn growing_chunk_moves_without_touching_neighbours() -> Result<()> {
+        let mut r = Region::create(Cursor::new(vec![]))?;
+        r.write_chunk(0, 0, b"small")?;
+        r.write_chunk(1, 0, b"neighbour")?;
+
+        // Incompressible data bigger than a sector.
+        let mut state = 1u32;
+        let big: Vec<u8> = (0..3 * SECTOR_SIZE)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        r.write_chunk(0, 0, &big)?;
+
+        assert_eq!(4, r.chunk_location(0, 0)?.begin_sector);
+        assert_eq!(big, read_back(&mut r, 0, 0)?);
+        assert_eq!(b"neighbour".to_vec(), read_back(&mut r, 1, 0)?);
+
+        // The freed sector is reused.
+        r.write_chunk(2, 0, b"reuse")?;
+        assert_eq!(2, r.chunk_location(2, 0)?.begin_sector);
+        Ok(())
+    }
+
+    #[test]
+    fn rewriting_chunk_leaves_old_sectors_intact() -> Result<()> {
+        let mut r = Region::create(Cursor::new(vec![]))?;
+        r.write_chunk(0, 0, b"old")?;
+        let old = r.chunk_location(0, 0)?;
+        let old_data = r.load_chunk_data(&old)?;
+
+        r.write_chunk(0, 0, b"new")?;
+        assert_eq!(3, r.chunk_location(0, 0)?.begin_sector);
+        assert_eq!(b"new".to_vec(), read_back(&mut r, 0, 0)?);
+
+        // The old header would still find the old chunk.
+        assert_eq!(old_data, r.load_chunk_data(&old)?);
+        Ok(())
+    }
+
+    #[test]
+    fn remove_chunk_frees_sectors() -> Result<()> {
+        let mut r = Region::create(Cursor::new(vec![]))?;
+        r.write_chunk(0, 0, b"gone")?;
+        r.remove_chunk(0, 0)?;
+        assert_eq!(0, r.chunk_location(0, 0)?.sector_count);
+
+        r.write_chunk(5, 5, b"here")?;
+        assert_eq!(2, r.chunk_location(5, 5)?.begin_sector);
+        Ok(())
+    }
+
+    #[test]
+    fn compact_drops_holes() -> Result<()> {
+        let mut r = Region::create(Cursor::new(vec![]))?;
+        for x in 0..4 {
+            r.write_chunk(x, 0, format!("chunk {}", x).as_bytes())?;
+        }
+        r.remove_chunk(0, 0)?;
+        r.remove_chunk(2, 0)?;
+
+        let mut out = Cursor::new(vec![]);
+        let stats = r.compact_into(&mut out, None)?;
+        assert_eq!(2, stats.chunks);
+        assert_eq!(2 * SECTOR_SIZE as i64, stats.bytes_reclaimed());
+
+        let mut compacted = Region::new(out);
+        assert_eq!(2, compacted.chunk_location(1, 0)?.begin_sector);
+        assert_eq!(3, compacted.chunk_location(3, 0)?.begin_sector);
+        assert_eq!(0, compacted.chunk_location(0, 0)?.sector_count);
+        assert_eq!(b"chunk 3".to_vec(), read_back(&mut compacted, 3, 0)?);
+        Ok(())
+    }
+
+    #[test]
+    fn compact_keeps_timestamps() -> Result<()> {
+        let mut r = Region::create(Cursor::new(vec![]))?;
+        r.write_chunk(5, 6, b"chunk")?;
+        let ts = r.chunk_timestamp(5, 6)?;
+
+        let mut compacted = Region::new(Cursor::new(vec![]));
+        r.compact_into(&mut compacted.data, None)?;
+        assert_eq!(ts, compacted.chunk_timestamp(5, 6)?);
+
+        // Without a timestamp table, as chunk_locations allows.
+        let mut r = Region::new(Builder::new().build());
+        let stats = r.compact_into(Cursor::new(vec![]), None)?;
+        assert_eq!(0, stats.chunks);
+        Ok(())
+    }
+
+    #[test]
+    fn compact_recompresses() -> Result<()> {
+        let mut r = Region::create(Cursor::new(vec![]))?;
+        let data = vec![7; 3 * SECTOR_SIZE];
+        r.write_chunk(1, 1, &data)?;
+
+        let mut compacted = Region::new(Cursor::new(vec![]));
+        r.compact_into(&mut compacted.data, Some(0))?;
+
+        // Level 0 stores the data uncompressed, so it needs more sectors.
+        assert_eq!(4, compacted.chunk_location(1, 1)?.sector_count);
+        assert_eq!(data, read_back(&mut compacted, 1, 1)?);
+        Ok(())
+    }
+
+    #[test]
+    fn timestamps() -> Result<()> {
+        let mut r = Region::create(Cursor::new(vec![]))?;
+        assert_eq!(0, r.chunk_timestamp(3, 4)?);
+        assert_eq!(None, r.chunk_locations()?[3 + 4 * 32].modified());
+
+        r.write_chunk(3, 4, b"chunk")?;
+        let ts = r.chunk_timestamp(3, 4)?;
+        assert!(ts > 1_600_000_000);
+        assert_eq!(ts, r.chunk_locations()?[3 + 4 * 32].timestamp);
+        assert!(r.chunk_locations()?[3 + 4 * 32].modified().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn locations_without_timestamp_table() -> Result<()> {
+        // Only the location table.
+        let r = Builder::new().location(2, 1).build();
+        let mut r = Region::new(r);
+        assert_eq!(2, r.chunk_location(0, 0)?.begin_sector);
+
+        let locations = r.chunk_locations()?;
+        assert_eq!(1, locations[0].sector_count);
+        assert_eq!(0, locations[0].timestamp);
+        Ok(())
+    }
+
+    #[test]
+    fn external_chunks() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("fastnbt-external-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let external = ExternalChunks::new(&dir, -1, 2);
+
+        let mut state = 1u32;
+        let huge: Vec<u8> = (0..256 * SECTOR_SIZE)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+
+        let mut r = Region::create(Cursor::new(vec![]))?;
+        match r.write_chunk(1, 3, &huge) {
+            Err(Error::ChunkTooLarge) => {}
+            o => panic!("should error {:?}", o),
+        }
+
+        let mut r = r.with_external_chunks(external.clone());
+        r.write_chunk(1, 3, &huge)?;
+        assert_eq!(dir.join("c.-31.67.mcc"), external.path(1, 3));
+        assert!(external.path(1, 3).exists());
+        assert_eq!(1, r.chunk_location(1, 3)?.sector_count);
+        assert_eq!(huge, read_back(&mut r, 1, 3)?);
+
+        // Without the directory the chunk can't be read.
+        let mut without = Region::new(Cursor::new(r.into_inner().into_inner()));
+        let loc = without.chunk_location(1, 3)?;
+        match without.load_chunk_data(&loc) {
+            Err(Error::NoExternalChunks(1, 3)) => {}
+            o => panic!("should error {:?}", o),
+        }
+
+        // Shrinking the chunk removes the external file.
+        let mut r = without.with_external_chunks(external.clone());
+        r.write_chunk(1, 3, b"small")?;
+        assert!(!external.path(1, 3).exists());
+        assert_eq!(b"small".to_vec(), read_back(&mut r, 1, 3)?);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn external_chunks_for_region_file() {
+        assert_eq!(
+            Some(ExternalChunks::new("world/region", -3, 4)),
+            ExternalChunks::for_region_file("world/region/r.-3.4.mca")
+        );
+        assert_eq!(
+            Some(ExternalChunks::new(".", 0, 0)),
+            ExternalChunks::for_region_file("r.0.0.mca")
+        );
+        assert_eq!(None, ExternalChunks::for_region_file("level.dat"));
+    }
+
+    #[test]
+    fn every_compression_scheme() -> Result<()> {
+        let mut r = Region::create(Cursor::new(vec![]))?;
+        let schemes = [
+            CompressionScheme::Gzip,
+            CompressionScheme::Zlib,
+            CompressionScheme::Uncompressed,
+            CompressionScheme::Lz4,
+        ];
+
+        for (x, scheme) in schemes.iter().enumerate() {
+            r.write_chunk_with(x, 0, *scheme, b"chunk data chunk data chunk data")?;
+        }
+
+        for (x, scheme) in schemes.iter().enumerate() {
+            let loc = r.chunk_location(x, 0)?;
+            let buf = r.load_chunk_data(&loc)?;
+            assert_eq!(*scheme, ChunkMeta::new(&buf)?.compression_scheme);
+            assert_eq!(
+                b"chunk data chunk data chunk data".to_vec(),
+                read_back(&mut r, x, 0)?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_compression_scheme() {
+        match ChunkMeta::new(&[0, 0, 0, 1, 9]) {
+            Err(Error::UnknownCompressionScheme(9)) => {}
+            o => panic!("should error {:?}", o),
+        }
+        assert!(decompress_chunk(&[0, 0, 0, 1, 127]).is_err());
+        // Length past the end of the data.
+        assert!(decompress_chunk(&[0, 0, 0, 9, 2, 0]).is_err());
+    }
+
+    #[test]
+    fn iterate_chunks_in_file_order() -> Result<()> {
+        let mut r = Region::create(Cursor::new(vec![]))?;
+        r.write_chunk(5, 5, b"first")?;
+        r.write_chunk_with(0, 0, CompressionScheme::Lz4, b"second")?;
+        r.write_chunk(3, 1, b"third")?;
+
+        let mut chunks = r.iter_chunks()?;
+        let mut seen = vec![];
+        while let Some(chunk) = chunks.next() {
+            let chunk = chunk?;
+            let mut data = vec![];
+            chunk.reader().read_to_end(&mut data)?;
+            seen.push((
+                chunk.location.x,
+                chunk.location.z,
+                chunk.compression_scheme,
+                data,
+            ));
+        }
+
+        assert_eq!(
+            vec![
+                (5, 5, CompressionScheme::Zlib, b"first".to_vec()),
+                (0, 0, CompressionScheme::Lz4, b"second".to_vec()),
+                (3, 1, CompressionScheme::Zlib, b"third".to_vec()),
+            ],
+            seen
+        );
+        Ok(())
+    }
 }
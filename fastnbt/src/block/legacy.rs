@@ -0,0 +1,626 @@
+use super::BlockState;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Convert a pre-1.13 numeric block id and data value into a block state.
+///
+/// The data value picks the variant of blocks that were split up by the flattening, eg wool colours and stone types,
+/// and sets the common properties such as the facing of stairs and chests, the age of crops and the level of
+/// liquids. Properties of other blocks are left at their defaults. Returns `None` for ids that were never used.
+pub fn block_state(id: u16, data: u8) -> Option<BlockState> {
+    let data = data & 0x0F;
+
+    if let Some(state) = variant(id, data) {
+        return Some(state);
+    }
+
+    let state = BlockState::new(base_name(id)?);
+    Some(match id {
+        8..=11 => state.with_property("level", data.to_string()),
+        59 | 141 | 142 => state.with_property("age", (data & 7).to_string()),
+        115 | 207 => state.with_property("age", (data & 3).to_string()),
+        78 => state.with_property("layers", ((data & 7) + 1).to_string()),
+        53 | 67 | 108 | 109 | 114 | 128 | 134..=136 | 156 | 163 | 164 | 180 | 203 => state
+            .with_property(
+                "facing",
+                ["east", "west", "south", "north"][data as usize & 3],
+            )
+            .with_property("half", if data & 4 == 0 { "bottom" } else { "top" }),
+        54 | 65 | 68 | 130 | 146 => match horizontal_facing(data) {
+            Some(facing) => state.with_property("facing", facing),
+            None => state,
+        },
+        61 | 62 => {
+            let state = state.with_property("lit", (id == 62).to_string());
+            match horizontal_facing(data) {
+                Some(facing) => state.with_property("facing", facing),
+                None => state,
+            }
+        }
+        44 | 126 | 182 | 205 => state.with_property("type", slab_half(data)),
+        43 | 125 | 181 | 204 => state.with_property("type", "double"),
+        _ => state,
+    })
+}
+
+/// Find the numeric block id and data value for a block state, the inverse of `block_state`. States that the data
+/// value can't hold, eg with other properties, are matched by name alone. Returns `None` for blocks that did not
+/// exist before 1.13.
+pub fn legacy_id(state: &BlockState) -> Option<(u16, u8)> {
+    let reverse = REVERSE.get_or_init(Reverse::new);
+    reverse
+        .states
+        .get(state)
+        .or_else(|| reverse.names.get(&state.name))
+        .copied()
+}
+
+static REVERSE: OnceLock<Reverse> = OnceLock::new();
+
+/// Every block state `block_state` gives, mapped back to the first id and data value giving it.
+struct Reverse {
+    states: HashMap<BlockState, (u16, u8)>,
+    names: HashMap<String, (u16, u8)>,
+}
+
+impl Reverse {
+    fn new() -> Self {
+        let mut reverse = Self {
+            states: HashMap::new(),
+            names: HashMap::new(),
+        };
+
+        let ids = (0..256u16)
+            .filter(|id| !ALTERNATE_IDS.contains(id))
+            .chain(ALTERNATE_IDS.iter().copied())
+            .flat_map(|id| (0..16u8).map(move |data| (id, data)));
+        for (id, data) in ids {
+            if let Some(state) = block_state(id, data) {
+                reverse
+                    .names
+                    .entry(state.name.clone())
+                    .or_insert((id, data));
+                reverse.states.entry(state).or_insert((id, data));
+            }
+        }
+        reverse
+    }
+}
+
+const COLOURS: [&str; 16] = [
+    "white",
+    "orange",
+    "magenta",
+    "light_blue",
+    "yellow",
+    "lime",
+    "pink",
+    "gray",
+    "light_gray",
+    "cyan",
+    "purple",
+    "blue",
+    "brown",
+    "green",
+    "red",
+    "black",
+];
+
+const WOODS: [&str; 6] = ["oak", "spruce", "birch", "jungle", "acacia", "dark_oak"];
+
+/// Blocks whose name depends on the data value.
+fn variant(id: u16, data: u8) -> Option<BlockState> {
+    let pick = |names: &[&str]| names.get(data as usize).map(|n| format!("minecraft:{}", n));
+    let coloured = |suffix: &str| Some(format!("minecraft:{}_{}", COLOURS[data as usize], suffix));
+
+    let name = match id {
+        1 => pick(&[
+            "stone",
+            "granite",
+            "polished_granite",
+            "diorite",
+            "polished_diorite",
+            "andesite",
+            "polished_andesite",
+        ]),
+        3 => pick(&["dirt", "coarse_dirt", "podzol"]),
+        5 => pick(&WOODS),
+        6 => {
+            let name = WOODS.get(data as usize & 7)?;
+            return Some(
+                BlockState::new(format!("minecraft:{}_sapling", name))
+                    .with_property("stage", (data >> 3).to_string()),
+            );
+        }
+        12 => pick(&["sand", "red_sand"]),
+        17 | 162 => {
+            let wood = *WOODS.get((data as usize & 3) + if id == 17 { 0 } else { 4 })?;
+            // All six sides of bark were logs with an axis of 3.
+            let (kind, axis) = match data >> 2 {
+                0 => ("log", "y"),
+                1 => ("log", "x"),
+                2 => ("log", "z"),
+                _ => ("wood", "y"),
+            };
+            return Some(
+                BlockState::new(format!("minecraft:{}_{}", wood, kind)).with_property("axis", axis),
+            );
+        }
+        18 | 161 => {
+            let wood = *WOODS.get((data as usize & 3) + if id == 18 { 0 } else { 4 })?;
+            return Some(
+                BlockState::new(format!("minecraft:{}_leaves", wood))
+                    .with_property("persistent", (data & 4 != 0).to_string()),
+            );
+        }
+        19 => pick(&["sponge", "wet_sponge"]),
+        24 => pick(&["sandstone", "chiseled_sandstone", "cut_sandstone"]),
+        31 => pick(&["dead_bush", "grass", "fern"]),
+        35 => coloured("wool"),
+        38 => pick(&[
+            "poppy",
+            "blue_orchid",
+            "allium",
+            "azure_bluet",
+            "red_tulip",
+            "orange_tulip",
+            "white_tulip",
+            "pink_tulip",
+            "oxeye_daisy",
+        ]),
+        43 | 44 => {
+            let name = [
+                "smooth_stone",
+                "sandstone",
+                "petrified_oak",
+                "cobblestone",
+                "brick",
+                "stone_brick",
+                "nether_brick",
+                "quartz",
+            ][data as usize & 7];
+            let kind = if id == 43 { "double" } else { slab_half(data) };
+            return Some(
+                BlockState::new(format!("minecraft:{}_slab", name)).with_property("type", kind),
+            );
+        }
+        95 => coloured("stained_glass"),
+        97 => pick(&[
+            "infested_stone",
+            "infested_cobblestone",
+            "infested_stone_bricks",
+            "infested_mossy_stone_bricks",
+            "infested_cracked_stone_bricks",
+            "infested_chiseled_stone_bricks",
+        ]),
+        98 => pick(&[
+            "stone_bricks",
+            "mossy_stone_bricks",
+            "cracked_stone_bricks",
+            "chiseled_stone_bricks",
+        ]),
+        125 | 126 => {
+            let wood = WOODS.get(data as usize & 7)?;
+            let kind = if id == 125 { "double" } else { slab_half(data) };
+            return Some(
+                BlockState::new(format!("minecraft:{}_slab", wood)).with_property("type", kind),
+            );
+        }
+        139 => pick(&["cobblestone_wall", "mossy_cobblestone_wall"]),
+        155 => {
+            let axis = match data {
+                0 => return Some(BlockState::new("minecraft:quartz_block")),
+                1 => return Some(BlockState::new("minecraft:chiseled_quartz_block")),
+                2 => "y",
+                3 => "x",
+                4 => "z",
+                _ => return None,
+            };
+            return Some(BlockState::new("minecraft:quartz_pillar").with_property("axis", axis));
+        }
+        159 => coloured("terracotta"),
+        160 => coloured("stained_glass_pane"),
+        168 => pick(&["prismarine", "prismarine_bricks", "dark_prismarine"]),
+        171 => coloured("carpet"),
+        175 => {
+            // The top half doesn't say which plant it is.
+            let (name, half) = if data & 8 == 0 {
+                (
+                    *[
+                        "sunflower",
+                        "lilac",
+                        "tall_grass",
+                        "large_fern",
+                        "rose_bush",
+                        "peony",
+                    ]
+                    .get(data as usize)?,
+                    "lower",
+                )
+            } else {
+                ("sunflower", "upper")
+            };
+            return Some(
+                BlockState::new(format!("minecraft:{}", name)).with_property("half", half),
+            );
+        }
+        179 => pick(&[
+            "red_sandstone",
+            "chiseled_red_sandstone",
+            "cut_red_sandstone",
+        ]),
+        251 => coloured("concrete"),
+        252 => coloured("concrete_powder"),
+        50 | 75 | 76 => {
+            let (standing, wall) = if id == 50 {
+                ("minecraft:torch", "minecraft:wall_torch")
+            } else {
+                ("minecraft:redstone_torch", "minecraft:redstone_wall_torch")
+            };
+            let state = match data {
+                1..=4 => BlockState::new(wall).with_property(
+                    "facing",
+                    ["east", "west", "south", "north"][data as usize - 1],
+                ),
+                _ => BlockState::new(standing),
+            };
+            return Some(match id {
+                50 => state,
+                _ => state.with_property("lit", (id == 76).to_string()),
+            });
+        }
+        _ => return None,
+    };
+
+    name.map(BlockState::new)
+}
+
+/// The facing of chests, furnaces and ladders, which store it as 2 to 5.
+fn horizontal_facing(data: u8) -> Option<&'static str> {
+    match data {
+        2 => Some("north"),
+        3 => Some("south"),
+        4 => Some("west"),
+        5 => Some("east"),
+        _ => None,
+    }
+}
+
+fn slab_half(data: u8) -> &'static str {
+    if data & 8 == 0 {
+        "bottom"
+    } else {
+        "top"
+    }
+}
+
+/// Ids sharing a name with another id, eg lit furnaces and double slabs. The other id is preferred when converting
+/// back.
+const ALTERNATE_IDS: &[u16] = &[8, 10, 31, 43, 62, 74, 75, 94, 124, 125, 150, 178, 181, 204];
+
+// Values from https://minecraft.gamepedia.com/Java_Edition_data_value/Pre-flattening, using the names blocks were
+// given by the flattening for a data value of 0.
+fn base_name(id: u16) -> Option<&'static str> {
+    Some(match id {
+        0 => "minecraft:air",
+        1 => "minecraft:stone",
+        2 => "minecraft:grass_block",
+        3 => "minecraft:dirt",
+        4 => "minecraft:cobblestone",
+        5 => "minecraft:oak_planks",
+        6 => "minecraft:oak_sapling",
+        7 => "minecraft:bedrock",
+        8 => "minecraft:water",
+        9 => "minecraft:water",
+        10 => "minecraft:lava",
+        11 => "minecraft:lava",
+        12 => "minecraft:sand",
+        13 => "minecraft:gravel",
+        14 => "minecraft:gold_ore",
+        15 => "minecraft:iron_ore",
+        16 => "minecraft:coal_ore",
+        17 => "minecraft:oak_log",
+        18 => "minecraft:oak_leaves",
+        19 => "minecraft:sponge",
+        20 => "minecraft:glass",
+        21 => "minecraft:lapis_ore",
+        22 => "minecraft:lapis_block",
+        23 => "minecraft:dispenser",
+        24 => "minecraft:sandstone",
+        25 => "minecraft:note_block",
+        26 => "minecraft:red_bed",
+        27 => "minecraft:powered_rail",
+        28 => "minecraft:detector_rail",
+        29 => "minecraft:sticky_piston",
+        30 => "minecraft:cobweb",
+        31 => "minecraft:dead_bush",
+        32 => "minecraft:dead_bush",
+        33 => "minecraft:piston",
+        34 => "minecraft:piston_head",
+        35 => "minecraft:white_wool",
+        36 => "minecraft:moving_piston",
+        37 => "minecraft:dandelion",
+        38 => "minecraft:poppy",
+        39 => "minecraft:brown_mushroom",
+        40 => "minecraft:red_mushroom",
+        41 => "minecraft:gold_block",
+        42 => "minecraft:iron_block",
+        43 => "minecraft:smooth_stone_slab",
+        44 => "minecraft:smooth_stone_slab",
+        45 => "minecraft:bricks",
+        46 => "minecraft:tnt",
+        47 => "minecraft:bookshelf",
+        48 => "minecraft:mossy_cobblestone",
+        49 => "minecraft:obsidian",
+        50 => "minecraft:torch",
+        51 => "minecraft:fire",
+        52 => "minecraft:spawner",
+        53 => "minecraft:oak_stairs",
+        54 => "minecraft:chest",
+        55 => "minecraft:redstone_wire",
+        56 => "minecraft:diamond_ore",
+        57 => "minecraft:diamond_block",
+        58 => "minecraft:crafting_table",
+        59 => "minecraft:wheat",
+        60 => "minecraft:farmland",
+        61 => "minecraft:furnace",
+        62 => "minecraft:furnace",
+        63 => "minecraft:oak_sign",
+        64 => "minecraft:oak_door",
+        65 => "minecraft:ladder",
+        66 => "minecraft:rail",
+        67 => "minecraft:cobblestone_stairs",
+        68 => "minecraft:oak_wall_sign",
+        69 => "minecraft:lever",
+        70 => "minecraft:stone_pressure_plate",
+        71 => "minecraft:iron_door",
+        72 => "minecraft:oak_pressure_plate",
+        73 => "minecraft:redstone_ore",
+        74 => "minecraft:redstone_ore",
+        75 => "minecraft:redstone_torch",
+        76 => "minecraft:redstone_torch",
+        77 => "minecraft:stone_button",
+        78 => "minecraft:snow",
+        79 => "minecraft:ice",
+        80 => "minecraft:snow_block",
+        81 => "minecraft:cactus",
+        82 => "minecraft:clay",
+        83 => "minecraft:sugar_cane",
+        84 => "minecraft:jukebox",
+        85 => "minecraft:oak_fence",
+        86 => "minecraft:carved_pumpkin",
+        87 => "minecraft:netherrack",
+        88 => "minecraft:soul_sand",
+        89 => "minecraft:glowstone",
+        90 => "minecraft:nether_portal",
+        91 => "minecraft:jack_o_lantern",
+        92 => "minecraft:cake",
+        93 => "minecraft:repeater",
+        94 => "minecraft:repeater",
+        95 => "minecraft:white_stained_glass",
+        96 => "minecraft:oak_trapdoor",
+        97 => "minecraft:infested_stone",
+        98 => "minecraft:stone_bricks",
+        99 => "minecraft:brown_mushroom_block",
+        100 => "minecraft:red_mushroom_block",
+        101 => "minecraft:iron_bars",
+        102 => "minecraft:glass_pane",
+        103 => "minecraft:melon",
+        104 => "minecraft:pumpkin_stem",
+        105 => "minecraft:melon_stem",
+        106 => "minecraft:vine",
+        107 => "minecraft:oak_fence_gate",
+        108 => "minecraft:brick_stairs",
+        109 => "minecraft:stone_brick_stairs",
+        110 => "minecraft:mycelium",
+        111 => "minecraft:lily_pad",
+        112 => "minecraft:nether_bricks",
+        113 => "minecraft:nether_brick_fence",
+        114 => "minecraft:nether_brick_stairs",
+        115 => "minecraft:nether_wart",
+        116 => "minecraft:enchanting_table",
+        117 => "minecraft:brewing_stand",
+        118 => "minecraft:cauldron",
+        119 => "minecraft:end_portal",
+        120 => "minecraft:end_portal_frame",
+        121 => "minecraft:end_stone",
+        122 => "minecraft:dragon_egg",
+        123 => "minecraft:redstone_lamp",
+        124 => "minecraft:redstone_lamp",
+        125 => "minecraft:oak_slab",
+        126 => "minecraft:oak_slab",
+        127 => "minecraft:cocoa",
+        128 => "minecraft:sandstone_stairs",
+        129 => "minecraft:emerald_ore",
+        130 => "minecraft:ender_chest",
+        131 => "minecraft:tripwire_hook",
+        132 => "minecraft:tripwire",
+        133 => "minecraft:emerald_block",
+        134 => "minecraft:spruce_stairs",
+        135 => "minecraft:birch_stairs",
+        136 => "minecraft:jungle_stairs",
+        137 => "minecraft:command_block",
+        138 => "minecraft:beacon",
+        139 => "minecraft:cobblestone_wall",
+        140 => "minecraft:flower_pot",
+        141 => "minecraft:carrots",
+        142 => "minecraft:potatoes",
+        143 => "minecraft:oak_button",
+        144 => "minecraft:skeleton_skull",
+        145 => "minecraft:anvil",
+        146 => "minecraft:trapped_chest",
+        147 => "minecraft:light_weighted_pressure_plate",
+        148 => "minecraft:heavy_weighted_pressure_plate",
+        149 => "minecraft:comparator",
+        150 => "minecraft:comparator",
+        151 => "minecraft:daylight_detector",
+        152 => "minecraft:redstone_block",
+        153 => "minecraft:nether_quartz_ore",
+        154 => "minecraft:hopper",
+        155 => "minecraft:quartz_block",
+        156 => "minecraft:quartz_stairs",
+        157 => "minecraft:activator_rail",
+        158 => "minecraft:dropper",
+        159 => "minecraft:white_terracotta",
+        160 => "minecraft:white_stained_glass_pane",
+        161 => "minecraft:acacia_leaves",
+        162 => "minecraft:acacia_log",
+        163 => "minecraft:acacia_stairs",
+        164 => "minecraft:dark_oak_stairs",
+        165 => "minecraft:slime_block",
+        166 => "minecraft:barrier",
+        167 => "minecraft:iron_trapdoor",
+        168 => "minecraft:prismarine",
+        169 => "minecraft:sea_lantern",
+        170 => "minecraft:hay_block",
+        171 => "minecraft:white_carpet",
+        172 => "minecraft:terracotta",
+        173 => "minecraft:coal_block",
+        174 => "minecraft:packed_ice",
+        175 => "minecraft:sunflower",
+        176 => "minecraft:white_banner",
+        177 => "minecraft:white_wall_banner",
+        178 => "minecraft:daylight_detector",
+        179 => "minecraft:red_sandstone",
+        180 => "minecraft:red_sandstone_stairs",
+        181 => "minecraft:red_sandstone_slab",
+        182 => "minecraft:red_sandstone_slab",
+        183 => "minecraft:spruce_fence_gate",
+        184 => "minecraft:birch_fence_gate",
+        185 => "minecraft:jungle_fence_gate",
+        186 => "minecraft:dark_oak_fence_gate",
+        187 => "minecraft:acacia_fence_gate",
+        188 => "minecraft:spruce_fence",
+        189 => "minecraft:birch_fence",
+        190 => "minecraft:jungle_fence",
+        191 => "minecraft:dark_oak_fence",
+        192 => "minecraft:acacia_fence",
+        193 => "minecraft:spruce_door",
+        194 => "minecraft:birch_door",
+        195 => "minecraft:jungle_door",
+        196 => "minecraft:acacia_door",
+        197 => "minecraft:dark_oak_door",
+        198 => "minecraft:end_rod",
+        199 => "minecraft:chorus_plant",
+        200 => "minecraft:chorus_flower",
+        201 => "minecraft:purpur_block",
+        202 => "minecraft:purpur_pillar",
+        203 => "minecraft:purpur_stairs",
+        204 => "minecraft:purpur_slab",
+        205 => "minecraft:purpur_slab",
+        206 => "minecraft:end_stone_bricks",
+        207 => "minecraft:beetroots",
+        208 => "minecraft:dirt_path",
+        209 => "minecraft:end_gateway",
+        210 => "minecraft:repeating_command_block",
+        211 => "minecraft:chain_command_block",
+        212 => "minecraft:frosted_ice",
+        213 => "minecraft:magma_block",
+        214 => "minecraft:nether_wart_block",
+        215 => "minecraft:red_nether_bricks",
+        216 => "minecraft:bone_block",
+        217 => "minecraft:structure_void",
+        218 => "minecraft:observer",
+        219 => "minecraft:white_shulker_box",
+        220 => "minecraft:orange_shulker_box",
+        221 => "minecraft:magenta_shulker_box",
+        222 => "minecraft:light_blue_shulker_box",
+        223 => "minecraft:yellow_shulker_box",
+        224 => "minecraft:lime_shulker_box",
+        225 => "minecraft:pink_shulker_box",
+        226 => "minecraft:gray_shulker_box",
+        227 => "minecraft:light_gray_shulker_box",
+        228 => "minecraft:cyan_shulker_box",
+        229 => "minecraft:purple_shulker_box",
+        230 => "minecraft:blue_shulker_box",
+        231 => "minecraft:brown_shulker_box",
+        232 => "minecraft:green_shulker_box",
+        233 => "minecraft:red_shulker_box",
+        234 => "minecraft:black_shulker_box",
+        235 => "minecraft:white_glazed_terracotta",
+        236 => "minecraft:orange_glazed_terracotta",
+        237 => "minecraft:magenta_glazed_terracotta",
+        238 => "minecraft:light_blue_glazed_terracotta",
+        239 => "minecraft:yellow_glazed_terracotta",
+        240 => "minecraft:lime_glazed_terracotta",
+        241 => "minecraft:pink_glazed_terracotta",
+        242 => "minecraft:gray_glazed_terracotta",
+        243 => "minecraft:light_gray_glazed_terracotta",
+        244 => "minecraft:cyan_glazed_terracotta",
+        245 => "minecraft:purple_glazed_terracotta",
+        246 => "minecraft:blue_glazed_terracotta",
+        247 => "minecraft:brown_glazed_terracotta",
+        248 => "minecraft:green_glazed_terracotta",
+        249 => "minecraft:red_glazed_terracotta",
+        250 => "minecraft:black_glazed_terracotta",
+        251 => "minecraft:white_concrete",
+        252 => "minecraft:white_concrete_powder",
+        255 => "minecraft:structure_block",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_both_ways() {
+        let state = block_state(54, 2).unwrap();
+        assert_eq!("minecraft:chest", state.name);
+        assert_eq!(Some("north"), state.property("facing"));
+        assert_eq!(Some((54, 2)), legacy_id(&state));
+    }
+
+    #[test]
+    fn data_values() {
+        assert_eq!("minecraft:red_wool", block_state(35, 14).unwrap().name);
+        assert_eq!(
+            "minecraft:polished_andesite",
+            block_state(1, 6).unwrap().name
+        );
+        assert_eq!(
+            BlockState::new("minecraft:birch_log").with_property("axis", "x"),
+            block_state(17, 6).unwrap()
+        );
+        assert_eq!(
+            BlockState::new("minecraft:stone_brick_stairs")
+                .with_property("facing", "north")
+                .with_property("half", "top"),
+            block_state(109, 7).unwrap()
+        );
+        assert_eq!(
+            BlockState::new("minecraft:wall_torch").with_property("facing", "south"),
+            block_state(50, 3).unwrap()
+        );
+        assert_eq!(Some("3"), block_state(8, 3).unwrap().property("level"));
+
+        // Every variant converts back to where it came from.
+        for (id, data) in [
+            (35, 14),
+            (1, 6),
+            (17, 6),
+            (109, 7),
+            (50, 3),
+            (44, 9),
+            (62, 4),
+        ] {
+            assert_eq!(Some((id, data)), legacy_id(&block_state(id, data).unwrap()));
+        }
+        // Unused data values fall back to the plain block.
+        assert_eq!("minecraft:stone", block_state(1, 9).unwrap().name);
+    }
+
+    #[test]
+    fn prefers_plain_variant() {
+        assert_eq!(
+            Some((61, 0)),
+            legacy_id(&BlockState::new("minecraft:furnace"))
+        );
+        assert_eq!(
+            Some((44, 0)),
+            legacy_id(&BlockState::new("minecraft:smooth_stone_slab"))
+        );
+        assert_eq!(None, block_state(253, 0));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::nbt::tree::{Compound, Node};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+
+pub mod legacy;
+
+/// A block state: the block's namespaced name, plus properties such as `facing` or `waterlogged`.
+///
+/// Properties are kept sorted by name, so two states compare equal whatever order their properties were read in. The
+/// text form is the one used in commands and Sponge schematics, eg `minecraft:oak_stairs[facing=north,half=top]`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockState {
+    pub name: String,
+    pub properties: BTreeMap<String, String>,
+}
+
+/// Returned when a string is not a valid block state.
+#[derive(Debug, PartialEq)]
+pub struct InvalidBlockState;
+
+impl BlockState {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self {
+            name: name.into(),
+            properties: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_property<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.properties.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(|v| v.as_str())
+    }
+
+    /// Read a palette entry, ie a compound with a `Name` string and an optional `Properties` compound.
+    pub fn from_compound(c: &Compound) -> Option<Self> {
+        let mut state = BlockState::new(c.get_str("Name")?);
+
+        if let Some(props) = c.get_compound("Properties") {
+            for (k, v) in props.iter() {
+                state
+                    .properties
+                    .insert(k.to_owned(), v.as_str()?.to_owned());
+            }
+        }
+
+        Some(state)
+    }
+
+    pub fn to_compound(&self) -> Compound {
+        let mut c = Compound::new();
+        c.insert("Name", Node::String(self.name.clone()));
+
+        if !self.properties.is_empty() {
+            let mut props = Compound::new();
+            for (k, v) in &self.properties {
+                props.insert(k.as_str(), Node::String(v.clone()));
+            }
+            c.insert("Properties", Node::Compound(props));
+        }
+        c
+    }
+}
+
+/// Get a shared copy of a block state, so equal states read from many palettes take the memory of one. A game only
+/// has so many block states, so interned states are kept for the life of the program.
+pub fn intern(state: BlockState) -> Arc<BlockState> {
+    static INTERNED: OnceLock<Mutex<HashSet<Arc<BlockState>>>> = OnceLock::new();
+
+    let mut interned = INTERNED
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    if let Some(shared) = interned.get(&state) {
+        return shared.clone();
+    }
+
+    let shared = Arc::new(state);
+    interned.insert(shared.clone());
+    shared
+}
+
+impl fmt::Display for BlockState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        if !self.properties.is_empty() {
+            let props: Vec<_> = self
+                .properties
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect();
+            write!(f, "[{}]", props.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for BlockState {
+    type Err = InvalidBlockState;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, props) = match s.find('[') {
+            Some(i) if s.ends_with(']') => (&s[..i], Some(&s[i + 1..s.len() - 1])),
+            Some(_) => return Err(InvalidBlockState),
+            None => (s, None),
+        };
+
+        if name.is_empty() {
+            return Err(InvalidBlockState);
+        }
+
+        let mut state = BlockState::new(name);
+
+        for prop in props.iter().flat_map(|p| p.split(',')) {
+            if prop.is_empty() {
+                continue;
+            }
+
+            let mut kv = prop.splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some(k), Some(v)) => {
+                    state.properties.insert(k.to_owned(), v.to_owned());
+                }
+                _ => return Err(InvalidBlockState),
+            }
+        }
+
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interned_states_are_shared() {
+        let a = intern("minecraft:oak_slab[type=top]".parse().unwrap());
+        let b = intern(BlockState::new("minecraft:oak_slab").with_property("type", "top"));
+        let c = intern(BlockState::new("minecraft:oak_slab").with_property("type", "bottom"));
+
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+    }
+
+    #[test]
+    fn text_form_round_trip() {
+        let s = "minecraft:oak_stairs[facing=north,half=top]";
+        let state: BlockState = s.parse().unwrap();
+
+        assert_eq!("minecraft:oak_stairs", state.name);
+        assert_eq!(Some("top"), state.property("half"));
+        assert_eq!(s, state.to_string());
+        assert_eq!(
+            state,
+            "minecraft:oak_stairs[half=top,facing=north]"
+                .parse()
+                .unwrap()
+        );
+        assert_eq!(
+            "minecraft:air",
+            BlockState::new("minecraft:air").to_string()
+        );
+    }
+
+    #[test]
+    fn invalid_text_form() {
+        assert!("minecraft:stone[".parse::<BlockState>().is_err());
+        assert!("minecraft:stone[facing]".parse::<BlockState>().is_err());
+        assert!("[facing=up]".parse::<BlockState>().is_err());
+    }
+
+    #[test]
+    fn compound_round_trip() {
+        let state = BlockState::new("minecraft:wheat").with_property("age", "7");
+        assert_eq!(
+            Some(state.clone()),
+            BlockState::from_compound(&state.to_compound())
+        );
+    }
+}
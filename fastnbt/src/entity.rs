@@ -0,0 +1,423 @@
+use crate::anvil::{self, Region};
+use crate::item::{self, ItemStack};
+use crate::nbt::{
+    self,
+    tree::{Compound, Node},
+};
+use crate::uuid::Uuid;
+use std::io::{Read, Seek};
+
+/// An entity, such as a mob, item or minecart.
+///
+/// Only the fields common to every entity are modelled. Everything else is available from `Entity::raw`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+    /// Namespaced id, eg `minecraft:zombie`.
+    pub id: String,
+    pub uuid: Option<Uuid>,
+    pub pos: [f64; 3],
+    /// Velocity in blocks per tick.
+    pub motion: [f64; 3],
+    /// The name given with a name tag, see `ItemStack::display_name` for its format.
+    pub custom_name: Option<String>,
+    /// Entities riding this one. Passengers without an id are left out, but are still in `Entity::raw`.
+    pub passengers: Vec<Entity>,
+    raw: Compound,
+}
+
+impl Entity {
+    /// Read an entity, which fails if it has no id.
+    pub fn from_compound(raw: Compound) -> Result<Self> {
+        Self::read(raw, &mut 0)
+    }
+
+    /// Read an entity, adding passengers left out for having no id to `skipped`.
+    fn read(raw: Compound, skipped: &mut usize) -> Result<Self> {
+        let id = raw
+            .get_str("id")
+            .ok_or(Error::MissingField("id"))?
+            .to_owned();
+
+        let passengers = read_entities(raw.get_list("Passengers"), skipped);
+
+        Ok(Self {
+            id,
+            uuid: Uuid::from_compound(&raw, "UUID"),
+            pos: doubles(&raw, "Pos"),
+            motion: doubles(&raw, "Motion"),
+            custom_name: raw.get("CustomName").and_then(item::text),
+            passengers,
+            raw,
+        })
+    }
+
+    /// Everything stored for this entity, as it was read.
+    pub fn raw(&self) -> &Compound {
+        &self.raw
+    }
+
+    /// The block the entity is in.
+    pub fn block_pos(&self) -> (isize, isize, isize) {
+        (
+            self.pos[0].floor() as isize,
+            self.pos[1].floor() as isize,
+            self.pos[2].floor() as isize,
+        )
+    }
+
+    /// The single item of an item frame, dropped item or thrown item such as a trident.
+    pub fn item(&self) -> Option<ItemStack> {
+        item::read_item(&self.raw, "Item")
+    }
+
+    /// The items carried by a chest minecart, donkey or other entity with an inventory.
+    pub fn items(&self) -> Vec<ItemStack> {
+        item::read_list(&self.raw, "Items")
+    }
+
+    /// The item held or worn in a slot. Since 1.21.5 these are in `equipment`, and before that in `HandItems`,
+    /// `ArmorItems` and `body_armor_item`.
+    pub fn equipment(&self, slot: EquipmentSlot) -> Option<ItemStack> {
+        if let Some(equipment) = self.raw.get_compound("equipment") {
+            return item::read_item(equipment, slot.name());
+        }
+
+        let (list, i) = match slot {
+            EquipmentSlot::MainHand => ("HandItems", 0),
+            EquipmentSlot::OffHand => ("HandItems", 1),
+            EquipmentSlot::Feet => ("ArmorItems", 0),
+            EquipmentSlot::Legs => ("ArmorItems", 1),
+            EquipmentSlot::Chest => ("ArmorItems", 2),
+            EquipmentSlot::Head => ("ArmorItems", 3),
+            EquipmentSlot::Body => return item::read_item(&self.raw, "body_armor_item"),
+            EquipmentSlot::Saddle => return item::read_item(&self.raw, "SaddleItem"),
+        };
+        self.raw
+            .get_list(list)?
+            .get(i)?
+            .as_compound()
+            .and_then(ItemStack::from_compound)
+    }
+
+    /// This entity followed by all of its passengers, including passengers of passengers.
+    pub fn with_passengers(&self) -> Vec<&Entity> {
+        let mut all = vec![self];
+        for p in &self.passengers {
+            all.extend(p.with_passengers());
+        }
+        all
+    }
+}
+
+/// Where a mob holds or wears an item, see `Entity::equipment`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EquipmentSlot {
+    MainHand,
+    OffHand,
+    Feet,
+    Legs,
+    Chest,
+    Head,
+    /// Armour of horses, wolves and llamas.
+    Body,
+    Saddle,
+}
+
+impl EquipmentSlot {
+    /// The slot's name in `equipment`, eg `mainhand`.
+    pub fn name(self) -> &'static str {
+        match self {
+            EquipmentSlot::MainHand => "mainhand",
+            EquipmentSlot::OffHand => "offhand",
+            EquipmentSlot::Feet => "feet",
+            EquipmentSlot::Legs => "legs",
+            EquipmentSlot::Chest => "chest",
+            EquipmentSlot::Head => "head",
+            EquipmentSlot::Body => "body",
+            EquipmentSlot::Saddle => "saddle",
+        }
+    }
+}
+
+/// The entities of a chunk, as stored in a dimension's `entities` region files since 1.17.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityChunk {
+    /// Chunk coordinates, in chunks rather than blocks.
+    pub x: isize,
+    pub z: isize,
+    pub data_version: Option<i32>,
+    /// Entities in the chunk. Passengers are only found within the entity they ride.
+    pub entities: Vec<Entity>,
+    /// Number of entities and passengers left out for having no id.
+    pub skipped: usize,
+}
+
+impl EntityChunk {
+    /// Read the chunk from its root compound. The position is taken from the chunk's `Position`, falling back to
+    /// the one given, which should be where it was found. Entities without an id are counted in `skipped` rather
+    /// than failing the whole chunk.
+    pub fn from_compound(root: &Compound, x: isize, z: isize) -> Result<Self> {
+        let (x, z) = match root.get_int_array("Position") {
+            Some([x, z]) => (*x as isize, *z as isize),
+            _ => (x, z),
+        };
+
+        let mut skipped = 0;
+        let entities = read_entities(root.get_list("Entities"), &mut skipped);
+
+        Ok(Self {
+            x,
+            z,
+            data_version: root.get_i32("DataVersion"),
+            entities,
+            skipped,
+        })
+    }
+
+    /// Every entity in the chunk, including passengers.
+    pub fn all_entities(&self) -> Vec<&Entity> {
+        self.entities
+            .iter()
+            .flat_map(|e| e.with_passengers())
+            .collect()
+    }
+}
+
+/// Read a list of entities, leaving out those without an id and adding them to `skipped`.
+fn read_entities(list: Option<&[Node]>, skipped: &mut usize) -> Vec<Entity> {
+    let mut entities = vec![];
+    for e in list
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Node::as_compound)
+    {
+        match Entity::read(e.clone(), skipped) {
+            Ok(entity) => entities.push(entity),
+            Err(_) => *skipped += 1,
+        }
+    }
+    entities
+}
+
+/// Read every chunk of an entities region, given the region's coordinates.
+pub fn read_region<S: Read + Seek>(
+    region: &mut Region<S>,
+    region_x: isize,
+    region_z: isize,
+) -> Result<Vec<EntityChunk>> {
+    anvil::read_region_roots(region, region_x, region_z, EntityChunk::from_compound)
+}
+
+fn doubles(raw: &Compound, name: &str) -> [f64; 3] {
+    match raw.get_list(name) {
+        Some([x, y, z]) => [
+            x.as_f64().unwrap_or_default(),
+            y.as_f64().unwrap_or_default(),
+            z.as_f64().unwrap_or_default(),
+        ],
+        _ => [0.; 3],
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    IO(std::io::Error),
+    ParseAnvil(anvil::Error),
+    ParseNbt(nbt::Error),
+    MissingField(&'static str),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::IO(err)
+    }
+}
+
+impl From<anvil::Error> for Error {
+    fn from(err: anvil::Error) -> Error {
+        Error::ParseAnvil(err)
+    }
+}
+
+impl From<nbt::Error> for Error {
+    fn from(err: nbt::Error) -> Error {
+        Error::ParseNbt(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tree;
+    use crate::nbt::Tag;
+    use std::io::Cursor;
+
+    fn entity(id: &str, pos: [f64; 3]) -> Compound {
+        let mut c = Compound::new();
+        c.insert("id", Node::String(id.to_owned()));
+        c.insert(
+            "Pos",
+            Node::List(Tag::Double, pos.iter().map(|v| Node::Double(*v)).collect()),
+        );
+        c.insert("UUID", Node::IntArray(vec![1, 2, 3, 4]));
+        c
+    }
+
+    #[test]
+    fn read_entities_region() -> Result<()> {
+        let mut jockey = entity("minecraft:spider", [-20.5, 64., 3.2]);
+        let skeleton = entity("minecraft:skeleton", [-20.5, 65., 3.2]);
+        jockey.insert(
+            "Passengers",
+            Node::List(Tag::Compound, vec![Node::Compound(skeleton)]),
+        );
+        jockey.insert(
+            "CustomName",
+            Node::String(r#"{"text":"Jockey"}"#.to_owned()),
+        );
+        let cow = entity("minecraft:cow", [-30., 70., 1.]);
+
+        let mut root = Compound::new();
+        root.insert("DataVersion", Node::Int(2730));
+        root.insert("Position", Node::IntArray(vec![-2, 0]));
+        root.insert(
+            "Entities",
+            Node::List(
+                Tag::Compound,
+                vec![Node::Compound(jockey), Node::Compound(cow)],
+            ),
+        );
+
+        let mut data = vec![];
+        tree::write_root(&mut data, "", &root)?;
+        let mut region = Region::create(Cursor::new(vec![]))?;
+        region.write_chunk(30, 0, &data)?;
+
+        let chunks = read_region(&mut region, -1, 0)?;
+        assert_eq!(1, chunks.len());
+        let chunk = &chunks[0];
+        assert_eq!((-2, 0), (chunk.x, chunk.z));
+        assert_eq!(Some(2730), chunk.data_version);
+
+        let spider = &chunk.entities[0];
+        assert_eq!("minecraft:spider", spider.id);
+        assert_eq!((-21, 64, 3), spider.block_pos());
+        assert_eq!(Some(r#"{"text":"Jockey"}"#), spider.custom_name.as_deref());
+        assert_eq!(
+            Some(Uuid::from_int_array(&[1, 2, 3, 4]).unwrap()),
+            spider.uuid
+        );
+        assert_eq!("minecraft:skeleton", spider.passengers[0].id);
+
+        let ids: Vec<_> = chunk.all_entities().iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(
+            vec!["minecraft:spider", "minecraft:skeleton", "minecraft:cow"],
+            ids
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn entities_without_id_are_skipped() -> Result<()> {
+        let mut pig = entity("minecraft:pig", [0.; 3]);
+        let mut rider = entity("minecraft:zombie", [0.; 3]);
+        rider.remove("id");
+        pig.insert(
+            "Passengers",
+            Node::List(Tag::Compound, vec![Node::Compound(rider)]),
+        );
+        let mut broken = entity("minecraft:cow", [0.; 3]);
+        broken.remove("id");
+
+        let mut root = Compound::new();
+        root.insert(
+            "Entities",
+            Node::List(
+                Tag::Compound,
+                vec![Node::Compound(broken), Node::Compound(pig)],
+            ),
+        );
+
+        let chunk = EntityChunk::from_compound(&root, 0, 0)?;
+        assert_eq!(2, chunk.skipped);
+        assert_eq!(1, chunk.all_entities().len());
+        assert_eq!("minecraft:pig", chunk.entities[0].id);
+        assert!(chunk.entities[0].raw().contains("Passengers"));
+        Ok(())
+    }
+
+    #[test]
+    fn items_and_equipment() -> Result<()> {
+        let stack = |id: &str| {
+            let mut c = Compound::new();
+            c.insert("id", Node::String(id.to_owned()));
+            c.insert("Count", Node::Byte(1));
+            Node::Compound(c)
+        };
+
+        let mut frame = entity("minecraft:item_frame", [0.; 3]);
+        frame.insert("Item", stack("minecraft:map"));
+        let frame = Entity::from_compound(frame)?;
+        assert_eq!("minecraft:map", frame.item().unwrap().id);
+
+        // Before 1.21.5, with an empty compound for an empty slot.
+        let mut zombie = entity("minecraft:zombie", [0.; 3]);
+        zombie.insert(
+            "HandItems",
+            Node::List(
+                Tag::Compound,
+                vec![
+                    stack("minecraft:iron_sword"),
+                    Node::Compound(Compound::new()),
+                ],
+            ),
+        );
+        zombie.insert(
+            "ArmorItems",
+            Node::List(
+                Tag::Compound,
+                vec![
+                    Node::Compound(Compound::new()),
+                    Node::Compound(Compound::new()),
+                    Node::Compound(Compound::new()),
+                    stack("minecraft:carved_pumpkin"),
+                ],
+            ),
+        );
+        let zombie = Entity::from_compound(zombie)?;
+        let id = |slot| zombie.equipment(slot).map(|i| i.id);
+        assert_eq!(
+            Some("minecraft:iron_sword".to_owned()),
+            id(EquipmentSlot::MainHand)
+        );
+        assert_eq!(None, id(EquipmentSlot::OffHand));
+        assert_eq!(
+            Some("minecraft:carved_pumpkin".to_owned()),
+            id(EquipmentSlot::Head)
+        );
+
+        let mut equipment = Compound::new();
+        equipment.insert("head", stack("minecraft:diamond_helmet"));
+        let mut skeleton = entity("minecraft:skeleton", [0.; 3]);
+        skeleton.insert("equipment", Node::Compound(equipment));
+        let skeleton = Entity::from_compound(skeleton)?;
+        assert_eq!(
+            "minecraft:diamond_helmet",
+            skeleton.equipment(EquipmentSlot::Head).unwrap().id
+        );
+        assert!(skeleton.equipment(EquipmentSlot::Feet).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn entity_without_id_is_an_error() {
+        let mut c = entity("minecraft:cow", [0.; 3]);
+        c.remove("id");
+        assert!(matches!(
+            Entity::from_compound(c),
+            Err(Error::MissingField("id"))
+        ));
+    }
+}
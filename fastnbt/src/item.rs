@@ -0,0 +1,541 @@
+use crate::nbt::{
+    tree::{Compound, Node},
+    Tag,
+};
+
+/// How an item stack is stored. 1.20.5 replaced the free-form `tag` compound with a map of typed data components,
+/// and renamed `Count` to `count`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// `{id, Count, tag: {...}}`, used before 1.20.5.
+    Legacy,
+    /// `{id, count, components: {...}}`, used since 1.20.5.
+    Components,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enchantment {
+    /// Namespaced id, eg `minecraft:sharpness`. Numeric ids from before 1.13 are converted.
+    pub id: String,
+    pub level: i32,
+}
+
+/// A stack of items, as found in inventories, containers, item frames and dropped items.
+///
+/// The common parts of both formats are available as fields and methods. Anything else is kept and written back
+/// unchanged by `ItemStack::to_compound`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemStack {
+    /// The slot in the containing inventory, if the stack is in one.
+    pub slot: Option<i8>,
+    pub id: String,
+    /// Stored as a byte in the legacy format, so counts outside -128..=127 are clamped when written.
+    pub count: i32,
+    pub format: Format,
+    /// Item data in the legacy format.
+    pub tag: Option<Compound>,
+    /// Item data in the components format, keyed by component id eg `minecraft:damage`.
+    pub components: Option<Compound>,
+    /// A numeric id from before 1.8, written back as it was read unless `id` is changed.
+    numeric_id: Option<Node>,
+    /// Whether the count was stored. If not it is only written once changed from 1.
+    count_stored: bool,
+    extra: Compound,
+}
+
+impl ItemStack {
+    /// Create a stack with no extra data, in the current format.
+    pub fn new<S: Into<String>>(id: S, count: i32) -> Self {
+        Self {
+            slot: None,
+            id: id.into(),
+            count,
+            format: Format::Components,
+            tag: None,
+            components: None,
+            numeric_id: None,
+            count_stored: false,
+            extra: Compound::new(),
+        }
+    }
+
+    /// Read a stack from its compound. Returns `None` if there is no item id.
+    pub fn from_compound(compound: &Compound) -> Option<Self> {
+        let mut extra = compound.clone();
+
+        let (id, numeric_id) = match extra.remove("id")? {
+            Node::String(id) => (id, None),
+            // Before 1.8 item ids were numeric.
+            n => (format!("{}", n.as_i32()?), Some(n)),
+        };
+        let slot = extra.remove("Slot").and_then(|n| n.as_i8());
+
+        let (format, count) = match extra.remove("count") {
+            Some(n) => (Format::Components, Some(n)),
+            None => (Format::Legacy, extra.remove("Count")),
+        };
+        let count_stored = count.is_some();
+        let count = count.and_then(|n| n.as_i32()).unwrap_or(1);
+
+        let tag = remove_compound(&mut extra, "tag");
+        let components = remove_compound(&mut extra, "components");
+
+        // Items in the components format leave out `count` when it is 1.
+        let format = if components.is_some() {
+            Format::Components
+        } else {
+            format
+        };
+
+        Some(Self {
+            slot,
+            id,
+            count,
+            format,
+            tag,
+            components,
+            numeric_id,
+            count_stored,
+            extra,
+        })
+    }
+
+    pub fn to_compound(&self) -> Compound {
+        let mut c = Compound::new();
+
+        if let Some(slot) = self.slot {
+            c.insert("Slot", Node::Byte(slot));
+        }
+        let id = match &self.numeric_id {
+            Some(n) if n.as_i32().map(|i| i.to_string()).as_ref() == Some(&self.id) => n.clone(),
+            _ => Node::String(self.id.clone()),
+        };
+        c.insert("id", id);
+
+        if self.count_stored || self.count != 1 {
+            match self.format {
+                Format::Legacy => c.insert(
+                    "Count",
+                    Node::Byte(self.count.clamp(i8::MIN as i32, i8::MAX as i32) as i8),
+                ),
+                Format::Components => c.insert("count", Node::Int(self.count)),
+            };
+        }
+
+        if let Some(ref tag) = self.tag {
+            c.insert("tag", Node::Compound(tag.clone()));
+        }
+        if let Some(ref components) = self.components {
+            c.insert("components", Node::Compound(components.clone()));
+        }
+
+        for (name, node) in self.extra.iter() {
+            c.insert(name, node.clone());
+        }
+        c
+    }
+
+    /// The damage taken by a tool or armour piece.
+    pub fn damage(&self) -> Option<i32> {
+        self.component("minecraft:damage")
+            .and_then(Node::as_i32)
+            .or_else(|| self.tag.as_ref()?.get_i32("Damage"))
+            // Before 1.13 damage was stored alongside the id, and doubled as the data value.
+            .or_else(|| self.extra.get_i32("Damage"))
+    }
+
+    pub fn set_damage(&mut self, damage: i32) {
+        match self.format {
+            Format::Components => {
+                self.components_mut()
+                    .insert("minecraft:damage", Node::Int(damage));
+            }
+            Format::Legacy => {
+                self.tag_mut().insert("Damage", Node::Int(damage));
+            }
+        }
+    }
+
+    /// The custom name given to the item, eg with an anvil.
+    ///
+    /// Since 1.13 this is a JSON text component like `{"text":"Sword"}`. Since 1.21.5 it can also be stored as an NBT
+    /// text component, in which case only its `text` is returned.
+    pub fn display_name(&self) -> Option<String> {
+        if let Some(name) = self.component("minecraft:custom_name") {
+            return text(name);
+        }
+
+        let display = self.tag.as_ref()?.get_compound("display")?;
+        display.get_str("Name").map(|s| s.to_owned())
+    }
+
+    pub fn set_display_name<S: Into<String>>(&mut self, name: S) {
+        let name = Node::String(name.into());
+
+        match self.format {
+            Format::Components => {
+                self.components_mut().insert("minecraft:custom_name", name);
+            }
+            Format::Legacy => {
+                self.display_mut().insert("Name", name);
+            }
+        }
+    }
+
+    /// Lines of lore shown under the item name. Each line is a text component, see `ItemStack::display_name`.
+    pub fn lore(&self) -> Vec<String> {
+        let lines = match self.component("minecraft:lore") {
+            Some(lore) => lore.as_list(),
+            None => self
+                .tag
+                .as_ref()
+                .and_then(|t| t.get_compound("display"))
+                .and_then(|d| d.get_list("Lore")),
+        };
+
+        lines.unwrap_or_default().iter().filter_map(text).collect()
+    }
+
+    pub fn set_lore(&mut self, lines: &[String]) {
+        let lore = Node::List(
+            Tag::String,
+            lines.iter().map(|l| Node::String(l.clone())).collect(),
+        );
+
+        match self.format {
+            Format::Components => {
+                self.components_mut().insert("minecraft:lore", lore);
+            }
+            Format::Legacy => {
+                self.display_mut().insert("Lore", lore);
+            }
+        }
+    }
+
+    /// Enchantments applied to the item. Enchanted books store theirs separately, see
+    /// `ItemStack::stored_enchantments`.
+    pub fn enchantments(&self) -> Vec<Enchantment> {
+        self.read_enchantments("minecraft:enchantments", "Enchantments")
+    }
+
+    pub fn stored_enchantments(&self) -> Vec<Enchantment> {
+        self.read_enchantments("minecraft:stored_enchantments", "StoredEnchantments")
+    }
+
+    pub fn set_enchantments(&mut self, enchantments: &[Enchantment]) {
+        match self.format {
+            Format::Components => {
+                let mut levels = Compound::new();
+                for e in enchantments {
+                    levels.insert(e.id.as_str(), Node::Int(e.level));
+                }
+
+                // Keep the 1.20.5 layout with a `levels` compound if the item already uses it.
+                let components = self.components_mut();
+                let wrapped = components
+                    .get_compound("minecraft:enchantments")
+                    .is_some_and(|e| e.contains("levels"));
+
+                let node = if wrapped {
+                    let mut e = components
+                        .get_compound("minecraft:enchantments")
+                        .cloned()
+                        .unwrap_or_default();
+                    e.insert("levels", Node::Compound(levels));
+                    e
+                } else {
+                    levels
+                };
+                components.insert("minecraft:enchantments", Node::Compound(node));
+            }
+            Format::Legacy => {
+                let list = enchantments
+                    .iter()
+                    .map(|e| {
+                        let mut c = Compound::new();
+                        c.insert("id", Node::String(e.id.clone()));
+                        c.insert("lvl", Node::Short(e.level as i16));
+                        Node::Compound(c)
+                    })
+                    .collect();
+                self.tag_mut()
+                    .insert("Enchantments", Node::List(Tag::Compound, list));
+            }
+        }
+    }
+
+    /// Get a data component by its id, eg `minecraft:damage`.
+    pub fn component(&self, id: &str) -> Option<&Node> {
+        self.components.as_ref()?.get(id)
+    }
+
+    fn read_enchantments(&self, component: &str, legacy: &str) -> Vec<Enchantment> {
+        if let Some(c) = self.component(component).and_then(Node::as_compound) {
+            // 1.20.5 wrapped the levels, 1.21.5 unwrapped them.
+            let levels = c.get_compound("levels").unwrap_or(c);
+
+            return levels
+                .iter()
+                .filter_map(|(id, lvl)| {
+                    Some(Enchantment {
+                        id: id.to_owned(),
+                        level: lvl.as_i32()?,
+                    })
+                })
+                .collect();
+        }
+
+        let list = self.tag.as_ref().and_then(|t| t.get_list(legacy));
+
+        list.unwrap_or_default()
+            .iter()
+            .filter_map(Node::as_compound)
+            .filter_map(|e| {
+                let id = match e.get("id")? {
+                    Node::String(id) => id.clone(),
+                    n => legacy_enchantment(n.as_i32()?)?.to_owned(),
+                };
+
+                Some(Enchantment {
+                    id,
+                    level: e.get_i32("lvl")?,
+                })
+            })
+            .collect()
+    }
+
+    fn components_mut(&mut self) -> &mut Compound {
+        self.components.get_or_insert_with(Compound::new)
+    }
+
+    fn tag_mut(&mut self) -> &mut Compound {
+        self.tag.get_or_insert_with(Compound::new)
+    }
+
+    fn display_mut(&mut self) -> &mut Compound {
+        let tag = self.tag_mut();
+        if tag.get_compound("display").is_none() {
+            tag.insert("display", Node::Compound(Compound::new()));
+        }
+        tag.get_compound_mut("display").unwrap()
+    }
+}
+
+/// Read a list of item stacks such as a chest's `Items` or a player's `Inventory`. Entries without an item id are
+/// skipped.
+pub fn read_list(compound: &Compound, name: &str) -> Vec<ItemStack> {
+    compound
+        .get_list(name)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Node::as_compound)
+        .filter_map(ItemStack::from_compound)
+        .collect()
+}
+
+/// Read a single item stack stored under `name`, such as an item frame's `Item`.
+pub fn read_item(compound: &Compound, name: &str) -> Option<ItemStack> {
+    compound
+        .get_compound(name)
+        .and_then(ItemStack::from_compound)
+}
+
+/// Make a list node from item stacks, the inverse of `read_list`.
+pub fn to_list(items: &[ItemStack]) -> Node {
+    Node::List(
+        Tag::Compound,
+        items
+            .iter()
+            .map(|item| Node::Compound(item.to_compound()))
+            .collect(),
+    )
+}
+
+fn remove_compound(c: &mut Compound, name: &str) -> Option<Compound> {
+    match c.remove(name)? {
+        Node::Compound(inner) => Some(inner),
+        other => {
+            // Not what we expected, leave it alone.
+            c.insert(name, other);
+            None
+        }
+    }
+}
+
+pub(crate) fn text(node: &Node) -> Option<String> {
+    match node {
+        Node::String(s) => Some(s.clone()),
+        Node::Compound(c) => c.get_str("text").map(|s| s.to_owned()),
+        _ => None,
+    }
+}
+
+// Values from https://minecraft.gamepedia.com/Java_Edition_data_value/Pre-flattening#Enchantment_IDs
+fn legacy_enchantment(id: i32) -> Option<&'static str> {
+    Some(match id {
+        0 => "minecraft:protection",
+        1 => "minecraft:fire_protection",
+        2 => "minecraft:feather_falling",
+        3 => "minecraft:blast_protection",
+        4 => "minecraft:projectile_protection",
+        5 => "minecraft:respiration",
+        6 => "minecraft:aqua_affinity",
+        7 => "minecraft:thorns",
+        8 => "minecraft:depth_strider",
+        9 => "minecraft:frost_walker",
+        10 => "minecraft:binding_curse",
+        16 => "minecraft:sharpness",
+        17 => "minecraft:smite",
+        18 => "minecraft:bane_of_arthropods",
+        19 => "minecraft:knockback",
+        20 => "minecraft:fire_aspect",
+        21 => "minecraft:looting",
+        22 => "minecraft:sweeping",
+        32 => "minecraft:efficiency",
+        33 => "minecraft:silk_touch",
+        34 => "minecraft:unbreaking",
+        35 => "minecraft:fortune",
+        48 => "minecraft:power",
+        49 => "minecraft:punch",
+        50 => "minecraft:flame",
+        51 => "minecraft:infinity",
+        61 => "minecraft:luck_of_the_sea",
+        62 => "minecraft:lure",
+        70 => "minecraft:mending",
+        71 => "minecraft:vanishing_curse",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legacy_sword() -> Compound {
+        let mut sharpness = Compound::new();
+        sharpness.insert("id", Node::Short(16));
+        sharpness.insert("lvl", Node::Short(5));
+
+        let mut display = Compound::new();
+        display.insert("Name", Node::String(r#"{"text":"Sword"}"#.to_owned()));
+
+        let mut tag = Compound::new();
+        tag.insert("Damage", Node::Int(12));
+        tag.insert(
+            "Enchantments",
+            Node::List(Tag::Compound, vec![Node::Compound(sharpness)]),
+        );
+        tag.insert("display", Node::Compound(display));
+
+        let mut c = Compound::new();
+        c.insert("Slot", Node::Byte(3));
+        c.insert("id", Node::String("minecraft:diamond_sword".to_owned()));
+        c.insert("Count", Node::Byte(1));
+        c.insert("tag", Node::Compound(tag));
+        c
+    }
+
+    fn component_sword() -> Compound {
+        let mut levels = Compound::new();
+        levels.insert("minecraft:sharpness", Node::Int(5));
+        let mut enchantments = Compound::new();
+        enchantments.insert("levels", Node::Compound(levels));
+
+        let mut components = Compound::new();
+        components.insert("minecraft:damage", Node::Int(12));
+        components.insert("minecraft:enchantments", Node::Compound(enchantments));
+        components.insert(
+            "minecraft:lore",
+            Node::List(Tag::String, vec![Node::String("\"old\"".to_owned())]),
+        );
+
+        let mut c = Compound::new();
+        c.insert("id", Node::String("minecraft:diamond_sword".to_owned()));
+        c.insert("count", Node::Int(1));
+        c.insert("components", Node::Compound(components));
+        c
+    }
+
+    #[test]
+    fn both_formats_read_the_same() {
+        let legacy = ItemStack::from_compound(&legacy_sword()).unwrap();
+        let components = ItemStack::from_compound(&component_sword()).unwrap();
+
+        assert_eq!(Format::Legacy, legacy.format);
+        assert_eq!(Format::Components, components.format);
+        assert_eq!(Some(3), legacy.slot);
+
+        for item in &[legacy, components] {
+            assert_eq!(Some(12), item.damage());
+            assert_eq!(
+                vec![Enchantment {
+                    id: "minecraft:sharpness".to_owned(),
+                    level: 5
+                }],
+                item.enchantments()
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_unchanged() {
+        for c in &[legacy_sword(), component_sword()] {
+            let item = ItemStack::from_compound(c).unwrap();
+            assert_eq!(*c, item.to_compound());
+        }
+    }
+
+    #[test]
+    fn numeric_ids_round_trip() {
+        let mut c = legacy_sword();
+        c.insert("id", Node::Short(276));
+        let mut item = ItemStack::from_compound(&c).unwrap();
+        assert_eq!("276", item.id);
+        assert_eq!(c, item.to_compound());
+
+        item.id = "minecraft:iron_sword".to_owned();
+        assert_eq!(
+            Some("minecraft:iron_sword"),
+            item.to_compound().get_str("id")
+        );
+    }
+
+    #[test]
+    fn missing_count_stays_missing() {
+        let mut c = component_sword();
+        c.remove("count");
+        let mut item = ItemStack::from_compound(&c).unwrap();
+        assert_eq!(1, item.count);
+        assert_eq!(c, item.to_compound());
+
+        item.count = 2;
+        assert_eq!(Some(2), item.to_compound().get_i32("count"));
+    }
+
+    #[test]
+    fn legacy_count_is_clamped() {
+        let mut item = ItemStack::from_compound(&legacy_sword()).unwrap();
+        item.count = 200;
+        assert_eq!(Some(127), item.to_compound().get_i8("Count"));
+        item.count = -200;
+        assert_eq!(Some(-128), item.to_compound().get_i8("Count"));
+    }
+
+    #[test]
+    fn setters_use_the_items_format() {
+        let mut item = ItemStack::from_compound(&component_sword()).unwrap();
+        item.set_display_name(r#"{"text":"Blade"}"#);
+        item.set_lore(&["\"new\"".to_owned()]);
+        item.set_damage(3);
+
+        assert!(item.tag.is_none());
+        assert_eq!(Some(3), item.damage());
+        assert_eq!(vec!["\"new\"".to_owned()], item.lore());
+        assert_eq!(Some(r#"{"text":"Blade"}"#.to_owned()), item.display_name());
+
+        let mut item = ItemStack::from_compound(&legacy_sword()).unwrap();
+        item.set_lore(&["\"new\"".to_owned()]);
+        assert!(item.components.is_none());
+        assert_eq!(vec!["\"new\"".to_owned()], item.lore());
+    }
+}
@@ -14,3 +14,45 @@ pub mod anvil;
 ///
 /// `Parser` has some usage examples which might be helpful.
 pub mod nbt;
+
+/// For item stacks, which are stored the same way in inventories, containers and entities.
+///
+/// `item::ItemStack` understands both the pre-1.20.5 `tag` format and the newer data components. Stacks are read from
+/// players, `BlockEntity::items` and `Entity::item`, `Entity::items` and `Entity::equipment`.
+pub mod item;
+
+/// For player files found in a world's `playerdata` directory.
+///
+/// `player::Player` gives typed access to position, health, inventories and so on, and can be written back.
+pub mod player;
+
+/// For map items, stored in a world's `data/map_<id>.dat` files.
+///
+/// `map::Map` converts between a map's colour indices and RGBA images, so maps can be archived or made from images.
+pub mod map;
+
+/// For entities, stored per chunk in a dimension's `entities` region files since 1.17.
+pub mod entity;
+
+/// For points of interest such as beds and workstations, stored per chunk in a dimension's `poi` region files.
+pub mod poi;
+
+/// UUIDs as used for players and entities.
+pub mod uuid;
+
+/// For structure templates, as saved by structure blocks and shipped in datapacks.
+pub mod structure;
+
+/// Block states, and conversion from the numeric ids used before 1.13.
+pub mod block;
+
+/// For schematics saved by WorldEdit (Sponge and MCEdit formats) and Litematica.
+///
+/// `schematic::load` reads any of them into a `schematic::Volume`.
+pub mod schematic;
+
+/// For navigating a world's save directory: its dimensions, and the region files within them.
+///
+/// `world::World` also maps block and chunk coordinates to the region file and slot holding them, and
+/// `world::ChunkCache` looks up blocks and biomes by absolute coordinates.
+pub mod world;
@@ -0,0 +1,422 @@
+use crate::anvil::draw::Rgb;
+use crate::nbt::{
+    self,
+    tree::{self, Compound, Node},
+    Tag,
+};
+use crate::player::{legacy_dimension, numeric_dimension};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Width and height of a map in pixels.
+pub const MAP_SIZE: usize = 128;
+
+/// Base colours of the map colour table, by base index. Index 0 is transparent. Every base colour is stored with one
+/// of four shades, see `SHADES`.
+pub const BASE_COLOURS: [Rgb; 62] = [
+    [0, 0, 0],
+    [127, 178, 56],
+    [247, 233, 163],
+    [199, 199, 199],
+    [255, 0, 0],
+    [160, 160, 255],
+    [167, 167, 167],
+    [0, 124, 0],
+    [255, 255, 255],
+    [164, 168, 184],
+    [151, 109, 77],
+    [112, 112, 112],
+    [64, 64, 255],
+    [143, 119, 72],
+    [255, 252, 245],
+    [216, 127, 51],
+    [178, 76, 216],
+    [102, 153, 216],
+    [229, 229, 51],
+    [127, 204, 25],
+    [242, 127, 165],
+    [76, 76, 76],
+    [153, 153, 153],
+    [76, 127, 153],
+    [127, 63, 178],
+    [51, 76, 178],
+    [102, 76, 51],
+    [102, 127, 51],
+    [153, 51, 51],
+    [25, 25, 25],
+    [250, 238, 77],
+    [92, 219, 213],
+    [74, 128, 255],
+    [0, 217, 58],
+    [129, 86, 49],
+    [112, 2, 0],
+    [209, 177, 161],
+    [159, 82, 36],
+    [149, 87, 108],
+    [112, 108, 138],
+    [186, 133, 36],
+    [103, 117, 53],
+    [160, 77, 78],
+    [57, 41, 35],
+    [135, 107, 98],
+    [87, 92, 92],
+    [122, 73, 88],
+    [76, 62, 92],
+    [76, 50, 35],
+    [76, 82, 42],
+    [142, 60, 46],
+    [37, 22, 16],
+    [189, 48, 49],
+    [148, 61, 99],
+    [92, 25, 29],
+    [22, 126, 134],
+    [58, 142, 140],
+    [86, 44, 62],
+    [20, 180, 133],
+    [100, 100, 100],
+    [216, 175, 147],
+    [127, 167, 150],
+];
+
+/// Data version of 1.17, which added the last of the base colours. Maps made by `Map::from_rgba` are given it, so
+/// the game does not upgrade them as maps of an older version.
+pub const COLOURS_DATA_VERSION: i32 = 2724;
+
+/// Multipliers out of 255 for the four shades of each base colour. The last, darkest shade cannot be produced by
+/// terrain in survival, only by editing map data.
+pub const SHADES: [u32; 4] = [180, 220, 255, 135];
+
+/// The colour of a map colour index, or `None` if it is transparent.
+pub fn colour(index: u8) -> Option<Rgb> {
+    let base = BASE_COLOURS.get(index as usize / 4)?;
+    if index < 4 {
+        return None;
+    }
+
+    let shade = SHADES[index as usize % 4];
+    Some([
+        (base[0] as u32 * shade / 255) as u8,
+        (base[1] as u32 * shade / 255) as u8,
+        (base[2] as u32 * shade / 255) as u8,
+    ])
+}
+
+/// The map colour index closest to the given colour. If `survival` is true only shades that can be produced by
+/// terrain are considered, which is what map-art built in game needs.
+pub fn nearest(rgb: Rgb, survival: bool) -> u8 {
+    let mut best = (u32::MAX, 0);
+
+    for index in 4..(BASE_COLOURS.len() * 4) as u8 {
+        if survival && index % 4 == 3 {
+            continue;
+        }
+
+        // Always some, as transparent indices are skipped.
+        let c = colour(index).unwrap();
+        let d = distance(rgb, c);
+        if d < best.0 {
+            best = (d, index);
+        }
+    }
+
+    best.1
+}
+
+/// Squared distance between colours, weighted roughly by how sensitive eyes are to each channel.
+fn distance(a: Rgb, b: Rgb) -> u32 {
+    let d = |i: usize| (a[i] as i32 - b[i] as i32).pow(2) as u32;
+    2 * d(0) + 4 * d(1) + 3 * d(2)
+}
+
+/// A map item's data, as stored in `data/map_<id>.dat` of a world.
+///
+/// Fields that are not modelled here, such as banners and frames, are kept and written back by `Map::write`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    pub data_version: Option<i32>,
+    /// Zoom level from 0 to 4, each level doubling the blocks per pixel.
+    pub scale: i8,
+    pub dimension: String,
+    pub x_center: i32,
+    pub z_center: i32,
+    pub locked: bool,
+    pub tracking_position: bool,
+    pub unlimited_tracking: bool,
+    /// Colour index of each pixel, row by row from the north west corner. See `colour`.
+    pub colors: Vec<u8>,
+    raw: Compound,
+}
+
+impl Map {
+    /// A new, blank map centred on the given position.
+    pub fn new(dimension: &str, x_center: i32, z_center: i32) -> Self {
+        Self {
+            data_version: None,
+            scale: 0,
+            dimension: dimension.to_owned(),
+            x_center,
+            z_center,
+            locked: false,
+            tracking_position: true,
+            unlimited_tracking: false,
+            colors: vec![0; MAP_SIZE * MAP_SIZE],
+            raw: Compound::new(),
+        }
+    }
+
+    /// A locked map showing the given image, which must be `MAP_SIZE` pixels square. Pixels are RGBA, with mostly
+    /// transparent pixels left transparent on the map.
+    pub fn from_rgba(pixels: &[[u8; 4]], survival: bool) -> Result<Self> {
+        if pixels.len() != MAP_SIZE * MAP_SIZE {
+            return Err(Error::InvalidSize(pixels.len()));
+        }
+
+        let mut map = Self::new("minecraft:overworld", 0, 0);
+        map.data_version = Some(COLOURS_DATA_VERSION);
+        map.locked = true;
+        map.tracking_position = false;
+        map.colors = pixels
+            .iter()
+            .map(|p| match p[3] {
+                0..=127 => 0,
+                _ => nearest([p[0], p[1], p[2]], survival),
+            })
+            .collect();
+
+        Ok(map)
+    }
+
+    /// Read a map from GZip compressed data, as found on disk.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        let (_, root) = tree::read_root(GzDecoder::new(reader))?;
+        Self::from_compound(root)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_reader(std::fs::File::open(path)?)
+    }
+
+    /// Read a map from the root compound of its file.
+    pub fn from_compound(root: Compound) -> Result<Self> {
+        let data = root
+            .get_compound("data")
+            .ok_or(Error::MissingField("data"))?;
+
+        let colors: Vec<u8> = data
+            .get_byte_array("colors")
+            .ok_or(Error::MissingField("colors"))?
+            .iter()
+            .map(|c| *c as u8)
+            .collect();
+
+        if colors.len() != MAP_SIZE * MAP_SIZE {
+            return Err(Error::InvalidSize(colors.len()));
+        }
+
+        let dimension = match data.get("dimension") {
+            Some(Node::String(s)) => s.clone(),
+            Some(n) => legacy_dimension(n.as_i32().unwrap_or_default()).to_owned(),
+            None => "minecraft:overworld".to_owned(),
+        };
+
+        let flag = |name, default| data.get_i8(name).map_or(default, |b| b != 0);
+
+        Ok(Self {
+            data_version: root.get_i32("DataVersion"),
+            scale: data.get_i8("scale").unwrap_or_default(),
+            dimension,
+            x_center: data.get_i32("xCenter").unwrap_or_default(),
+            z_center: data.get_i32("zCenter").unwrap_or_default(),
+            locked: flag("locked", false),
+            // The game tracks positions on maps from before the tag existed.
+            tracking_position: flag("trackingPosition", true),
+            unlimited_tracking: flag("unlimitedTracking", false),
+            colors,
+            raw: root,
+        })
+    }
+
+    /// The root compound of the map's file, ie what was read with the modelled fields replaced by their current
+    /// values.
+    pub fn to_compound(&self) -> Compound {
+        let mut root = self.raw.clone();
+        let mut data = root.get_compound("data").cloned().unwrap_or_default();
+
+        // Keep the numeric form if that is what the file used, so older versions can still read it.
+        let dimension = match data.get("dimension") {
+            Some(Node::String(_)) | None => Node::String(self.dimension.clone()),
+            Some(_) => match numeric_dimension(&self.dimension) {
+                Some(n) => Node::Byte(n as i8),
+                None => Node::String(self.dimension.clone()),
+            },
+        };
+
+        data.insert("scale", Node::Byte(self.scale));
+        data.insert("dimension", dimension);
+        data.insert("xCenter", Node::Int(self.x_center));
+        data.insert("zCenter", Node::Int(self.z_center));
+        data.insert("locked", Node::Byte(self.locked as i8));
+        data.insert("trackingPosition", Node::Byte(self.tracking_position as i8));
+        data.insert(
+            "unlimitedTracking",
+            Node::Byte(self.unlimited_tracking as i8),
+        );
+        data.insert(
+            "colors",
+            Node::ByteArray(self.colors.iter().map(|c| *c as i8).collect()),
+        );
+        for list in &["banners", "frames"] {
+            if !data.contains(list) {
+                data.insert(*list, Node::List(Tag::Compound, vec![]));
+            }
+        }
+
+        root.insert("data", Node::Compound(data));
+        if let Some(v) = self.data_version {
+            root.insert("DataVersion", Node::Int(v));
+        }
+        root
+    }
+
+    /// Write the map GZip compressed, as Minecraft does.
+    pub fn write<W: Write>(&self, writer: W) -> Result<()> {
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        tree::write_root(&mut encoder, "", &self.to_compound())?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.write(std::io::BufWriter::new(file))
+    }
+
+    /// The map as RGBA pixels, row by row from the north west corner. Transparent pixels have an alpha of zero.
+    pub fn to_rgba(&self) -> Vec<[u8; 4]> {
+        self.colors
+            .iter()
+            .map(|c| match colour(*c) {
+                Some([r, g, b]) => [r, g, b, 255],
+                None => [0, 0, 0, 0],
+            })
+            .collect()
+    }
+
+    /// Number of blocks along each side of the map.
+    pub fn blocks_wide(&self) -> i32 {
+        MAP_SIZE as i32 * (1 << self.scale.clamp(0, 4))
+    }
+}
+
+/// Paths to every map file in a world directory, along with the map id.
+pub fn map_paths<P: AsRef<Path>>(world: P) -> Result<Vec<(i32, PathBuf)>> {
+    let dir = world.as_ref().join("data");
+    let mut paths = vec![];
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let id = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix("map_"))
+            .and_then(|n| n.strip_suffix(".dat"))
+            .and_then(|n| n.parse().ok());
+
+        if let Some(id) = id {
+            paths.push((id, path));
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    IO(std::io::Error),
+    ParseNbt(nbt::Error),
+    MissingField(&'static str),
+    /// Map colours or images must be `MAP_SIZE` pixels square. Contains the number of pixels given.
+    InvalidSize(usize),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::IO(err)
+    }
+}
+
+impl From<nbt::Error> for Error {
+    fn from(err: nbt::Error) -> Error {
+        Error::ParseNbt(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colour_shades() {
+        assert_eq!(None, colour(0));
+        assert_eq!(None, colour(3));
+        // Grass, normal shade.
+        assert_eq!(Some([127, 178, 56]), colour(6));
+        assert_eq!(Some([89, 125, 39]), colour(4));
+        assert_eq!(None, colour(62 * 4));
+    }
+
+    #[test]
+    fn nearest_colour() {
+        assert_eq!(6, nearest([127, 178, 56], true));
+        assert_eq!(34, nearest([255, 255, 255], true));
+
+        // Black is closest to one of the darkest shades, which survival maps can't produce.
+        assert_eq!(3, nearest([0, 0, 0], false) % 4);
+        assert_ne!(3, nearest([0, 0, 0], true) % 4);
+    }
+
+    #[test]
+    fn image_round_trip() -> Result<()> {
+        let mut pixels = vec![[0, 0, 0, 0]; MAP_SIZE * MAP_SIZE];
+        pixels[1] = [255, 0, 0, 255];
+        pixels[MAP_SIZE] = [64, 64, 255, 255];
+
+        let map = Map::from_rgba(&pixels, true)?;
+        let mut buf = vec![];
+        map.write(&mut buf)?;
+        let read = Map::from_reader(buf.as_slice())?;
+
+        assert_eq!(map.colors, read.colors);
+        assert!(read.locked);
+        assert!(!read.tracking_position);
+        assert_eq!(Some(COLOURS_DATA_VERSION), read.data_version);
+        let rgba = read.to_rgba();
+        assert_eq!([0, 0, 0, 0], rgba[0]);
+        assert_eq!([255, 0, 0, 255], rgba[1]);
+        assert_eq!([64, 64, 255, 255], rgba[MAP_SIZE]);
+        Ok(())
+    }
+
+    #[test]
+    fn tracking_position_defaults_on() -> Result<()> {
+        let mut root = Map::new("minecraft:overworld", 0, 0).to_compound();
+        match root.get_mut("data") {
+            Some(Node::Compound(data)) => data.remove("trackingPosition"),
+            _ => unreachable!(),
+        };
+
+        let map = Map::from_compound(root)?;
+        assert!(map.tracking_position);
+        assert!(!map.locked);
+        Ok(())
+    }
+
+    #[test]
+    fn wrong_image_size() {
+        assert!(Map::from_rgba(&[[0; 4]; 10], true).is_err());
+    }
+}
@@ -8,6 +8,9 @@ type Name = Option<String>;
 #[cfg(test)]
 mod test;
 
+/// Owned NBT values, for when data needs to be kept around or written back rather than streamed.
+pub mod tree;
+
 /// The NBT tag. This does not carry the value or the name.
 #[derive(Debug, TryFromPrimitive, PartialEq, Clone)]
 #[repr(u8)]
@@ -150,6 +153,7 @@ impl<R: Read> Parser<R> {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<Value> {
         let v = self.next_inner();
         //println!("{:?}", v);
@@ -333,7 +337,7 @@ impl From<std::io::Error> for Error {
 }
 
 fn u8_to_tag(tag: u8) -> Result<Tag> {
-    Tag::try_from(tag).or_else(|_| Err(Error::InvalidTag(tag)))
+    Tag::try_from(tag).map_err(|_| Error::InvalidTag(tag))
 }
 
 #[derive(Clone)]
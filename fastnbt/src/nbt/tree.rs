@@ -0,0 +1,546 @@
+use super::{Error, Parser, Result, Tag, Value};
+use byteorder::{BigEndian, WriteBytesExt};
+use std::io::{Read, Write};
+
+/// An owned NBT value, including everything nested inside it.
+///
+/// Where `Value` is a single event from a `Parser`, a `Node` is a complete value. This is convenient for data that is
+/// small or needs to be written back, such as player files. Lists carry the tag of their elements so that empty lists
+/// can be written back unchanged.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Tag, Vec<Node>),
+    Compound(Compound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Node {
+    /// The tag this node would be written with.
+    pub fn tag(&self) -> Tag {
+        match self {
+            Node::Byte(_) => Tag::Byte,
+            Node::Short(_) => Tag::Short,
+            Node::Int(_) => Tag::Int,
+            Node::Long(_) => Tag::Long,
+            Node::Float(_) => Tag::Float,
+            Node::Double(_) => Tag::Double,
+            Node::ByteArray(_) => Tag::ByteArray,
+            Node::String(_) => Tag::String,
+            Node::List(_, _) => Tag::List,
+            Node::Compound(_) => Tag::Compound,
+            Node::IntArray(_) => Tag::IntArray,
+            Node::LongArray(_) => Tag::LongArray,
+        }
+    }
+
+    pub fn as_i8(&self) -> Option<i8> {
+        match *self {
+            Node::Byte(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Get an integer that fits in an `i16`. Bytes are widened.
+    pub fn as_i16(&self) -> Option<i16> {
+        match *self {
+            Node::Byte(b) => Some(b as i16),
+            Node::Short(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Get an integer that fits in an `i32`. Bytes and shorts are widened.
+    pub fn as_i32(&self) -> Option<i32> {
+        match *self {
+            Node::Byte(b) => Some(b as i32),
+            Node::Short(s) => Some(s as i32),
+            Node::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Get any integer as an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Node::Byte(b) => Some(b as i64),
+            Node::Short(s) => Some(s as i64),
+            Node::Int(i) => Some(i as i64),
+            Node::Long(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match *self {
+            Node::Float(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// Get a floating point value as an `f64`. Floats are widened.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Node::Float(f) => Some(f as f64),
+            Node::Double(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Node::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Node]> {
+        match self {
+            Node::List(_, items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&Compound> {
+        match self {
+            Node::Compound(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound_mut(&mut self) -> Option<&mut Compound> {
+        match self {
+            Node::Compound(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    pub fn as_byte_array(&self) -> Option<&[i8]> {
+        match self {
+            Node::ByteArray(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_int_array(&self) -> Option<&[i32]> {
+        match self {
+            Node::IntArray(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_long_array(&self) -> Option<&[i64]> {
+        match self {
+            Node::LongArray(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+/// The contents of an NBT compound. Entries keep the order they were read or inserted in, so that re-written files
+/// look like the originals.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Compound {
+    entries: Vec<(String, Node)>,
+}
+
+impl Compound {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Node> {
+        self.entries.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Node> {
+        self.entries
+            .iter_mut()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v)
+    }
+
+    /// Insert a value, replacing and returning any existing value with the same name. A replaced value keeps its
+    /// position.
+    pub fn insert<N: Into<String>>(&mut self, name: N, node: Node) -> Option<Node> {
+        let name = name.into();
+        match self.get_mut(&name) {
+            Some(existing) => Some(std::mem::replace(existing, node)),
+            None => {
+                self.entries.push((name, node));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Node> {
+        let i = self.entries.iter().position(|(n, _)| n == name)?;
+        Some(self.entries.remove(i).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Node)> {
+        self.entries.iter().map(|(n, v)| (n.as_str(), v))
+    }
+
+    pub fn get_i8(&self, name: &str) -> Option<i8> {
+        self.get(name).and_then(Node::as_i8)
+    }
+
+    pub fn get_i16(&self, name: &str) -> Option<i16> {
+        self.get(name).and_then(Node::as_i16)
+    }
+
+    pub fn get_i32(&self, name: &str) -> Option<i32> {
+        self.get(name).and_then(Node::as_i32)
+    }
+
+    pub fn get_i64(&self, name: &str) -> Option<i64> {
+        self.get(name).and_then(Node::as_i64)
+    }
+
+    pub fn get_f32(&self, name: &str) -> Option<f32> {
+        self.get(name).and_then(Node::as_f32)
+    }
+
+    pub fn get_f64(&self, name: &str) -> Option<f64> {
+        self.get(name).and_then(Node::as_f64)
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(Node::as_str)
+    }
+
+    pub fn get_list(&self, name: &str) -> Option<&[Node]> {
+        self.get(name).and_then(Node::as_list)
+    }
+
+    pub fn get_compound(&self, name: &str) -> Option<&Compound> {
+        self.get(name).and_then(Node::as_compound)
+    }
+
+    pub fn get_compound_mut(&mut self, name: &str) -> Option<&mut Compound> {
+        self.get_mut(name).and_then(Node::as_compound_mut)
+    }
+
+    pub fn get_byte_array(&self, name: &str) -> Option<&[i8]> {
+        self.get(name).and_then(Node::as_byte_array)
+    }
+
+    pub fn get_int_array(&self, name: &str) -> Option<&[i32]> {
+        self.get(name).and_then(Node::as_int_array)
+    }
+
+    pub fn get_long_array(&self, name: &str) -> Option<&[i64]> {
+        self.get(name).and_then(Node::as_long_array)
+    }
+}
+
+/// Read the rest of a compound into a `Compound`.
+///
+/// This assumes the parser has just returned the `Value::Compound` that opens the compound, and will consume
+/// everything up to and including the matching `Value::CompoundEnd`.
+pub fn read_compound<R: Read>(parser: &mut Parser<R>) -> Result<Compound> {
+    read_compound_without(parser, &[])
+}
+
+fn read_compound_without<R: Read>(parser: &mut Parser<R>, skip: &[&str]) -> Result<Compound> {
+    let mut compound = Compound::new();
+
+    loop {
+        let value = parser.next()?;
+        let name = match value_name(&value) {
+            Some(name) => name.to_owned(),
+            None => match value {
+                Value::CompoundEnd => return Ok(compound),
+                _ => return Err(Error::InvalidTag(0)),
+            },
+        };
+
+        if skip.contains(&name.as_str()) {
+            skip_node(parser, value)?;
+            continue;
+        }
+        let node = read_node(parser, value, skip)?;
+        compound.entries.push((name, node));
+    }
+}
+
+/// Read a complete NBT document such as a player file or structure (after decompression), returning the name and
+/// contents of the root compound.
+pub fn read_root<R: Read>(reader: R) -> Result<(String, Compound)> {
+    read_root_without(reader, &[])
+}
+
+/// Like `read_root`, but leaving out entries with any of the given names, wherever they are. No nodes are built for
+/// them, so large parts of a document that aren't needed cost little to read past.
+pub fn read_root_without<R: Read>(reader: R, skip: &[&str]) -> Result<(String, Compound)> {
+    let mut parser = Parser::new(reader);
+
+    match parser.next()? {
+        Value::Compound(name) => {
+            let root = read_compound_without(&mut parser, skip)?;
+            Ok((name.unwrap_or_default(), root))
+        }
+        other => Err(Error::InvalidTag(value_tag(&other) as u8)),
+    }
+}
+
+/// Write a complete NBT document with the given root compound. This does not do compression.
+pub fn write_root<W: Write>(mut writer: W, name: &str, root: &Compound) -> Result<()> {
+    writer.write_u8(Tag::Compound as u8)?;
+    write_string(&mut writer, name)?;
+    write_compound_payload(&mut writer, root)
+}
+
+fn read_node<R: Read>(parser: &mut Parser<R>, value: Value, skip: &[&str]) -> Result<Node> {
+    Ok(match value {
+        Value::Byte(_, v) => Node::Byte(v),
+        Value::Short(_, v) => Node::Short(v),
+        Value::Int(_, v) => Node::Int(v),
+        Value::Long(_, v) => Node::Long(v),
+        Value::Float(_, v) => Node::Float(v),
+        Value::Double(_, v) => Node::Double(v),
+        Value::ByteArray(_, v) => Node::ByteArray(v),
+        Value::String(_, v) => Node::String(v),
+        Value::IntArray(_, v) => Node::IntArray(v),
+        Value::LongArray(_, v) => Node::LongArray(v),
+        Value::Compound(_) => Node::Compound(read_compound_without(parser, skip)?),
+        Value::List(_, tag, size) => {
+            let mut items = Vec::with_capacity(size.max(0) as usize);
+            loop {
+                match parser.next()? {
+                    Value::ListEnd => break,
+                    Value::CompoundEnd => return Err(Error::InvalidTag(0)),
+                    v => items.push(read_node(parser, v, skip)?),
+                }
+            }
+            Node::List(tag, items)
+        }
+        Value::CompoundEnd | Value::ListEnd => return Err(Error::InvalidTag(0)),
+    })
+}
+
+/// Consume the rest of a value, ie everything in it if it is a compound or list.
+fn skip_node<R: Read>(parser: &mut Parser<R>, value: Value) -> Result<()> {
+    match value {
+        Value::Compound(_) => super::skip_compound(parser),
+        Value::List(..) => loop {
+            match parser.next()? {
+                Value::ListEnd => return Ok(()),
+                Value::CompoundEnd => return Err(Error::InvalidTag(0)),
+                v => skip_node(parser, v)?,
+            }
+        },
+        _ => Ok(()),
+    }
+}
+
+fn value_name(value: &Value) -> Option<&str> {
+    match value {
+        Value::Byte(n, _)
+        | Value::Short(n, _)
+        | Value::Int(n, _)
+        | Value::Long(n, _)
+        | Value::Float(n, _)
+        | Value::Double(n, _)
+        | Value::ByteArray(n, _)
+        | Value::String(n, _)
+        | Value::List(n, _, _)
+        | Value::IntArray(n, _)
+        | Value::LongArray(n, _)
+        | Value::Compound(n) => n.as_deref(),
+        Value::CompoundEnd | Value::ListEnd => None,
+    }
+}
+
+fn value_tag(value: &Value) -> Tag {
+    match value {
+        Value::Byte(_, _) => Tag::Byte,
+        Value::Short(_, _) => Tag::Short,
+        Value::Int(_, _) => Tag::Int,
+        Value::Long(_, _) => Tag::Long,
+        Value::Float(_, _) => Tag::Float,
+        Value::Double(_, _) => Tag::Double,
+        Value::ByteArray(_, _) => Tag::ByteArray,
+        Value::String(_, _) => Tag::String,
+        Value::List(_, _, _) => Tag::List,
+        Value::Compound(_) => Tag::Compound,
+        Value::IntArray(_, _) => Tag::IntArray,
+        Value::LongArray(_, _) => Tag::LongArray,
+        Value::CompoundEnd | Value::ListEnd => Tag::End,
+    }
+}
+
+fn write_string<W: Write>(writer: &mut W, s: &str) -> Result<()> {
+    if s.len() > u16::MAX as usize {
+        return Err(Error::InvalidName);
+    }
+    writer.write_u16::<BigEndian>(s.len() as u16)?;
+    writer.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+fn write_compound_payload<W: Write>(writer: &mut W, compound: &Compound) -> Result<()> {
+    for (name, node) in compound.iter() {
+        writer.write_u8(node.tag() as u8)?;
+        write_string(writer, name)?;
+        write_payload(writer, node)?;
+    }
+    writer.write_u8(Tag::End as u8)?;
+    Ok(())
+}
+
+fn write_payload<W: Write>(writer: &mut W, node: &Node) -> Result<()> {
+    match node {
+        Node::Byte(v) => writer.write_i8(*v)?,
+        Node::Short(v) => writer.write_i16::<BigEndian>(*v)?,
+        Node::Int(v) => writer.write_i32::<BigEndian>(*v)?,
+        Node::Long(v) => writer.write_i64::<BigEndian>(*v)?,
+        Node::Float(v) => writer.write_f32::<BigEndian>(*v)?,
+        Node::Double(v) => writer.write_f64::<BigEndian>(*v)?,
+        Node::ByteArray(v) => {
+            writer.write_i32::<BigEndian>(v.len() as i32)?;
+            for b in v {
+                writer.write_i8(*b)?;
+            }
+        }
+        Node::String(v) => write_string(writer, v)?,
+        Node::List(tag, items) => {
+            // Minecraft writes empty lists with an End element tag, but reads any tag fine.
+            writer.write_u8(tag.clone() as u8)?;
+            writer.write_i32::<BigEndian>(items.len() as i32)?;
+            for item in items {
+                if item.tag() != *tag {
+                    return Err(Error::InvalidTag(item.tag() as u8));
+                }
+                write_payload(writer, item)?;
+            }
+        }
+        Node::Compound(c) => write_compound_payload(writer, c)?,
+        Node::IntArray(v) => {
+            writer.write_i32::<BigEndian>(v.len() as i32)?;
+            for i in v {
+                writer.write_i32::<BigEndian>(*i)?;
+            }
+        }
+        Node::LongArray(v) => {
+            writer.write_i32::<BigEndian>(v.len() as i32)?;
+            for l in v {
+                writer.write_i64::<BigEndian>(*l)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Compound {
+        let mut inner = Compound::new();
+        inner.insert("id", Node::String("minecraft:stone".to_owned()));
+        inner.insert("Count", Node::Byte(3));
+
+        let mut root = Compound::new();
+        root.insert("health", Node::Float(20.));
+        root.insert(
+            "Pos",
+            Node::List(Tag::Double, vec![Node::Double(1.5), Node::Double(-64.)]),
+        );
+        root.insert(
+            "Inventory",
+            Node::List(Tag::Compound, vec![Node::Compound(inner)]),
+        );
+        root.insert("Empty", Node::List(Tag::End, vec![]));
+        root.insert("States", Node::LongArray(vec![i64::MIN, 0, i64::MAX]));
+        root
+    }
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let root = sample();
+        let mut buf = Vec::new();
+        write_root(&mut buf, "data", &root)?;
+
+        let (name, read) = read_root(buf.as_slice())?;
+        assert_eq!("data", name);
+        assert_eq!(root, read);
+        Ok(())
+    }
+
+    #[test]
+    fn read_without() -> Result<()> {
+        let mut root = sample();
+        let mut nested = sample();
+        nested.insert("Pos", Node::Int(1));
+        root.insert(
+            "Nested",
+            Node::List(Tag::Compound, vec![Node::Compound(nested)]),
+        );
+        root.insert("After", Node::Byte(1));
+        let mut buf = Vec::new();
+        write_root(&mut buf, "", &root)?;
+
+        let (_, read) = read_root_without(buf.as_slice(), &["Inventory", "Pos"])?;
+        let names: Vec<_> = read.iter().map(|(n, _)| n).collect();
+        assert_eq!(vec!["health", "Empty", "States", "Nested", "After"], names);
+        let nested = read.get_list("Nested").unwrap()[0].as_compound().unwrap();
+        assert!(!nested.contains("Pos") && !nested.contains("Inventory"));
+        assert_eq!(Some(20.), nested.get_f32("health"));
+        Ok(())
+    }
+
+    #[test]
+    fn insert_replaces_in_place() {
+        let mut root = sample();
+        let old = root.insert("health", Node::Float(5.));
+
+        assert_eq!(Some(Node::Float(20.)), old);
+        assert_eq!(Some(5.), root.get_f32("health"));
+        assert_eq!(Some("health"), root.iter().next().map(|(n, _)| n));
+    }
+
+    #[test]
+    fn integer_getters_widen() {
+        let root = sample();
+        let item = root.get_list("Inventory").unwrap()[0]
+            .as_compound()
+            .unwrap();
+
+        assert_eq!(Some(3), item.get_i32("Count"));
+        assert_eq!(Some(3), item.get_i64("Count"));
+        assert_eq!(None, item.get_f64("Count"));
+    }
+
+    #[test]
+    fn mismatched_list_is_not_written() {
+        let mut root = Compound::new();
+        root.insert("bad", Node::List(Tag::Int, vec![Node::Byte(1)]));
+
+        match write_root(Vec::new(), "", &root) {
+            Err(Error::InvalidTag(1)) => {}
+            o => panic!("should error {:?}", o),
+        }
+    }
+}
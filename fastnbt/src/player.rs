@@ -0,0 +1,479 @@
+use crate::item::{self, ItemStack};
+use crate::nbt::{
+    self,
+    tree::{self, Compound, Node},
+    Tag,
+};
+use crate::uuid::Uuid;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[repr(i32)] // i32 as in corresponding NBT.
+pub enum GameMode {
+    Survival = 0,
+    Creative = 1,
+    Adventure = 2,
+    Spectator = 3,
+}
+
+/// Where the player respawns, usually a bed or respawn anchor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnPoint {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub dimension: String,
+    pub forced: bool,
+}
+
+/// A player, as stored in `playerdata/<uuid>.dat` of a world, or under `Data.Player` of a singleplayer `level.dat`.
+///
+/// Fields that are not modelled here are kept, and are written back unchanged by `Player::write`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Player {
+    /// Taken from the file name if loaded with `Player::open`, otherwise from the data itself.
+    pub uuid: Option<Uuid>,
+    pub pos: [f64; 3],
+    /// Yaw then pitch, in degrees.
+    pub rotation: [f32; 2],
+    /// Namespaced dimension, eg `minecraft:the_nether`. Pre-1.16 numeric dimensions are converted.
+    pub dimension: String,
+    pub health: f32,
+    pub xp_level: i32,
+    pub xp_total: i32,
+    /// Progress towards the next level, between 0 and 1.
+    pub xp_progress: f32,
+    pub game_mode: GameMode,
+    pub inventory: Vec<ItemStack>,
+    pub ender_items: Vec<ItemStack>,
+    pub spawn: Option<SpawnPoint>,
+    raw: Compound,
+}
+
+impl Player {
+    /// Read a player from GZip compressed data, as found on disk.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        let (_, root) = tree::read_root(GzDecoder::new(reader))?;
+        Self::from_compound(root)
+    }
+
+    /// Read a player file, taking the UUID from its name.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut player = Self::from_reader(std::fs::File::open(path)?)?;
+
+        let from_name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse().ok());
+
+        if from_name.is_some() {
+            player.uuid = from_name;
+        }
+
+        Ok(player)
+    }
+
+    pub fn from_compound(raw: Compound) -> Result<Self> {
+        let pos = raw.get_list("Pos").ok_or(Error::MissingField("Pos"))?;
+        let pos = match pos {
+            [x, y, z] => [
+                x.as_f64().unwrap_or_default(),
+                y.as_f64().unwrap_or_default(),
+                z.as_f64().unwrap_or_default(),
+            ],
+            _ => return Err(Error::MissingField("Pos")),
+        };
+
+        let rotation = match raw.get_list("Rotation") {
+            Some([yaw, pitch]) => [
+                yaw.as_f32().unwrap_or_default(),
+                pitch.as_f32().unwrap_or_default(),
+            ],
+            _ => [0., 0.],
+        };
+
+        let dimension = match raw.get("Dimension") {
+            Some(Node::String(s)) => s.clone(),
+            Some(n) => legacy_dimension(n.as_i32().unwrap_or_default()).to_owned(),
+            None => "minecraft:overworld".to_owned(),
+        };
+
+        let game_mode = raw
+            .get_i32("playerGameType")
+            .and_then(|m| GameMode::try_from(m).ok())
+            .unwrap_or(GameMode::Survival);
+
+        Ok(Self {
+            uuid: Uuid::from_compound(&raw, "UUID"),
+            pos,
+            rotation,
+            dimension,
+            health: raw.get_f32("Health").unwrap_or(20.),
+            xp_level: raw.get_i32("XpLevel").unwrap_or_default(),
+            xp_total: raw.get_i32("XpTotal").unwrap_or_default(),
+            xp_progress: raw.get_f32("XpP").unwrap_or_default(),
+            game_mode,
+            inventory: item::read_list(&raw, "Inventory"),
+            ender_items: item::read_list(&raw, "EnderItems"),
+            spawn: spawn_point(&raw),
+            raw,
+        })
+    }
+
+    /// Everything stored for this player, as it was read.
+    pub fn raw(&self) -> &Compound {
+        &self.raw
+    }
+
+    /// The player as NBT, ie what was read with the modelled fields replaced by their current values. Fields that
+    /// were not stored are only added once changed from what reading them assumed, eg full health.
+    pub fn to_compound(&self) -> Compound {
+        let mut c = self.raw.clone();
+
+        c.insert(
+            "Pos",
+            Node::List(
+                Tag::Double,
+                self.pos.iter().map(|v| Node::Double(*v)).collect(),
+            ),
+        );
+        set_field(
+            &mut c,
+            "Rotation",
+            Node::List(
+                Tag::Float,
+                self.rotation.iter().map(|v| Node::Float(*v)).collect(),
+            ),
+            self.rotation == [0., 0.],
+        );
+
+        // Keep the numeric form if that is what the file used, so older versions can still read it.
+        let dimension = match c.get("Dimension") {
+            Some(Node::Int(_)) => match numeric_dimension(&self.dimension) {
+                Some(n) => Node::Int(n),
+                None => Node::String(self.dimension.clone()),
+            },
+            _ => Node::String(self.dimension.clone()),
+        };
+        let overworld = self.dimension == "minecraft:overworld";
+        set_field(&mut c, "Dimension", dimension, overworld);
+
+        set_field(
+            &mut c,
+            "Health",
+            Node::Float(self.health),
+            self.health == 20.,
+        );
+        set_field(
+            &mut c,
+            "XpLevel",
+            Node::Int(self.xp_level),
+            self.xp_level == 0,
+        );
+        set_field(
+            &mut c,
+            "XpTotal",
+            Node::Int(self.xp_total),
+            self.xp_total == 0,
+        );
+        set_field(
+            &mut c,
+            "XpP",
+            Node::Float(self.xp_progress),
+            self.xp_progress == 0.,
+        );
+        set_field(
+            &mut c,
+            "playerGameType",
+            Node::Int(self.game_mode.into()),
+            self.game_mode == GameMode::Survival,
+        );
+        set_field(
+            &mut c,
+            "Inventory",
+            item::to_list(&self.inventory),
+            self.inventory.is_empty(),
+        );
+        set_field(
+            &mut c,
+            "EnderItems",
+            item::to_list(&self.ender_items),
+            self.ender_items.is_empty(),
+        );
+
+        if let Some(uuid) = self.uuid {
+            if c.contains("UUIDMost") {
+                let (most, least) = uuid.to_most_least();
+                c.insert("UUIDMost", Node::Long(most));
+                c.insert("UUIDLeast", Node::Long(least));
+            } else {
+                c.insert("UUID", uuid.to_node());
+            }
+        }
+
+        set_spawn_point(&mut c, self.spawn.as_ref());
+        c
+    }
+
+    /// Write the player GZip compressed, as Minecraft does.
+    pub fn write<W: Write>(&self, writer: W) -> Result<()> {
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        tree::write_root(&mut encoder, "", &self.to_compound())?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.write(std::io::BufWriter::new(file))
+    }
+
+    /// The block the player is standing in.
+    pub fn block_pos(&self) -> (isize, isize, isize) {
+        (
+            self.pos[0].floor() as isize,
+            self.pos[1].floor() as isize,
+            self.pos[2].floor() as isize,
+        )
+    }
+
+    /// The region the player is in, matching the `r.<x>.<z>.mca` region file names.
+    pub fn region(&self) -> (isize, isize) {
+        let (x, _, z) = self.block_pos();
+        (x.div_euclid(512), z.div_euclid(512))
+    }
+}
+
+/// Paths to every player file in a world directory.
+pub fn player_paths<P: AsRef<Path>>(world: P) -> Result<Vec<PathBuf>> {
+    let dir = world.as_ref().join("playerdata");
+    let mut paths = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|e| e == "dat") {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    IO(std::io::Error),
+    ParseNbt(nbt::Error),
+    MissingField(&'static str),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::IO(err)
+    }
+}
+
+impl From<nbt::Error> for Error {
+    fn from(err: nbt::Error) -> Error {
+        Error::ParseNbt(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+fn spawn_point(raw: &Compound) -> Option<SpawnPoint> {
+    // 1.21.5 moved the spawn into a single compound.
+    if let Some(respawn) = raw.get_compound("respawn") {
+        return match respawn.get_int_array("pos")? {
+            [x, y, z] => Some(SpawnPoint {
+                x: *x,
+                y: *y,
+                z: *z,
+                dimension: respawn
+                    .get_str("dimension")
+                    .unwrap_or("minecraft:overworld")
+                    .to_owned(),
+                forced: respawn.get_i8("forced").unwrap_or_default() != 0,
+            }),
+            _ => None,
+        };
+    }
+
+    Some(SpawnPoint {
+        x: raw.get_i32("SpawnX")?,
+        y: raw.get_i32("SpawnY")?,
+        z: raw.get_i32("SpawnZ")?,
+        dimension: raw
+            .get_str("SpawnDimension")
+            .unwrap_or("minecraft:overworld")
+            .to_owned(),
+        forced: raw.get_i8("SpawnForced").unwrap_or_default() != 0,
+    })
+}
+
+fn set_spawn_point(c: &mut Compound, spawn: Option<&SpawnPoint>) {
+    let spawn = match spawn {
+        Some(spawn) => spawn,
+        None => {
+            for name in &[
+                "respawn",
+                "SpawnX",
+                "SpawnY",
+                "SpawnZ",
+                "SpawnDimension",
+                "SpawnForced",
+            ] {
+                c.remove(name);
+            }
+            return;
+        }
+    };
+
+    if let Some(respawn) = c.get_compound_mut("respawn") {
+        respawn.insert("pos", Node::IntArray(vec![spawn.x, spawn.y, spawn.z]));
+        respawn.insert("dimension", Node::String(spawn.dimension.clone()));
+        set_field(
+            respawn,
+            "forced",
+            Node::Byte(spawn.forced as i8),
+            !spawn.forced,
+        );
+    } else {
+        c.insert("SpawnX", Node::Int(spawn.x));
+        c.insert("SpawnY", Node::Int(spawn.y));
+        c.insert("SpawnZ", Node::Int(spawn.z));
+        set_field(
+            c,
+            "SpawnDimension",
+            Node::String(spawn.dimension.clone()),
+            spawn.dimension == "minecraft:overworld",
+        );
+        set_field(
+            c,
+            "SpawnForced",
+            Node::Byte(spawn.forced as i8),
+            !spawn.forced,
+        );
+    }
+}
+
+/// Write a field, unless it wasn't stored and still has the value assumed when reading it without.
+fn set_field(c: &mut Compound, name: &str, node: Node, assumed: bool) {
+    if c.contains(name) || !assumed {
+        c.insert(name, node);
+    }
+}
+
+pub(crate) fn legacy_dimension(id: i32) -> &'static str {
+    match id {
+        -1 => "minecraft:the_nether",
+        1 => "minecraft:the_end",
+        _ => "minecraft:overworld",
+    }
+}
+
+pub(crate) fn numeric_dimension(name: &str) -> Option<i32> {
+    match name {
+        "minecraft:overworld" => Some(0),
+        "minecraft:the_nether" => Some(-1),
+        "minecraft:the_end" => Some(1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stone(slot: i8) -> Node {
+        let mut c = Compound::new();
+        c.insert("Slot", Node::Byte(slot));
+        c.insert("id", Node::String("minecraft:stone".to_owned()));
+        c.insert("Count", Node::Byte(64));
+        Node::Compound(c)
+    }
+
+    fn raw_player() -> Compound {
+        let mut c = Compound::new();
+        c.insert(
+            "Pos",
+            Node::List(
+                Tag::Double,
+                vec![Node::Double(-0.5), Node::Double(70.), Node::Double(1030.)],
+            ),
+        );
+        c.insert("Dimension", Node::Int(-1));
+        c.insert("playerGameType", Node::Int(1));
+        c.insert(
+            "Inventory",
+            Node::List(Tag::Compound, vec![stone(0), stone(8)]),
+        );
+        c.insert("SpawnX", Node::Int(10));
+        c.insert("SpawnY", Node::Int(64));
+        c.insert("SpawnZ", Node::Int(-10));
+        c.insert("foodLevel", Node::Int(20));
+        c
+    }
+
+    #[test]
+    fn reads_fields() -> Result<()> {
+        let player = Player::from_compound(raw_player())?;
+
+        assert_eq!("minecraft:the_nether", player.dimension);
+        assert_eq!(GameMode::Creative, player.game_mode);
+        assert_eq!((-1, 70, 1030), player.block_pos());
+        assert_eq!((-1, 2), player.region());
+        assert_eq!(2, player.inventory.len());
+        assert_eq!(64, player.inventory[1].count);
+        assert_eq!(Some(8), player.inventory[1].slot);
+        assert_eq!(Some(-10), player.spawn.map(|s| s.z));
+        Ok(())
+    }
+
+    #[test]
+    fn write_keeps_unknown_fields() -> Result<()> {
+        let mut player = Player::from_compound(raw_player())?;
+        player.pos = [1., 2., 3.];
+        player.inventory.truncate(1);
+
+        let mut buf = Vec::new();
+        player.write(&mut buf)?;
+        let read = Player::from_reader(buf.as_slice())?;
+
+        assert_eq!([1., 2., 3.], read.pos);
+        assert_eq!(1, read.inventory.len());
+        assert_eq!(Some(&Node::Int(-1)), read.raw().get("Dimension"));
+        assert_eq!(Some(20), read.raw().get_i32("foodLevel"));
+        Ok(())
+    }
+
+    #[test]
+    fn write_leaves_out_fields_not_stored() -> Result<()> {
+        let mut raw = raw_player();
+        for name in ["Health", "XpLevel", "XpP", "playerGameType", "EnderItems"] {
+            raw.remove(name);
+        }
+        let mut player = Player::from_compound(raw.clone())?;
+        assert_eq!(raw, player.to_compound());
+
+        player.health = 5.;
+        assert_eq!(Some(5.), player.to_compound().get_f32("Health"));
+        Ok(())
+    }
+
+    #[test]
+    fn missing_position_errors() {
+        let mut raw = raw_player();
+        raw.remove("Pos");
+
+        match Player::from_compound(raw) {
+            Err(Error::MissingField("Pos")) => {}
+            o => panic!("should error {:?}", o),
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use crate::anvil::{self, Region};
+use crate::nbt::{
+    self,
+    tree::{Compound, Node},
+};
+use std::io::{Read, Seek};
+
+/// A point of interest, such as a bed, a workstation or a nether portal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoiRecord {
+    /// Namespaced type, eg `minecraft:home` for beds or `minecraft:armorer` for blast furnaces.
+    pub kind: String,
+    pub pos: [i32; 3],
+    /// How many more villagers can claim it. A claimed bed or workstation has none left.
+    pub free_tickets: i32,
+}
+
+/// The points of interest within one 16x16x16 section of a chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoiSection {
+    /// Section y, so the section spans blocks `16 * y` to `16 * y + 15`.
+    pub y: i32,
+    /// False if the section is waiting to be rescanned by the game, in which case the records may be stale.
+    pub valid: bool,
+    pub records: Vec<PoiRecord>,
+}
+
+/// The points of interest of a chunk, as stored in a dimension's `poi` region files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoiChunk {
+    /// Chunk coordinates, in chunks rather than blocks.
+    pub x: isize,
+    pub z: isize,
+    pub data_version: Option<i32>,
+    /// Sections with points of interest, ordered by y.
+    pub sections: Vec<PoiSection>,
+    /// Number of records left out for having no type or position.
+    pub skipped: usize,
+}
+
+impl PoiChunk {
+    /// Read the chunk from its root compound. These hold no position, so it must be given. Records without a type
+    /// or position are counted in `skipped` rather than failing the whole chunk.
+    pub fn from_compound(root: &Compound, x: isize, z: isize) -> Result<Self> {
+        let mut sections = vec![];
+        let mut skipped = 0;
+
+        if let Some(raw) = root.get_compound("Sections") {
+            for (name, section) in raw.iter() {
+                let y = name.parse().map_err(|_| Error::InvalidSection)?;
+                let section = section.as_compound().ok_or(Error::InvalidSection)?;
+
+                let mut records = vec![];
+                for c in section
+                    .get_list("Records")
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(Node::as_compound)
+                {
+                    match record(c) {
+                        Ok(r) => records.push(r),
+                        Err(_) => skipped += 1,
+                    }
+                }
+
+                sections.push(PoiSection {
+                    y,
+                    valid: section.get_i8("Valid").unwrap_or(1) != 0,
+                    records,
+                });
+            }
+        }
+
+        sections.sort_by_key(|s| s.y);
+
+        Ok(Self {
+            x,
+            z,
+            data_version: root.get_i32("DataVersion"),
+            sections,
+            skipped,
+        })
+    }
+
+    /// Every point of interest in the chunk.
+    pub fn records(&self) -> impl Iterator<Item = &PoiRecord> {
+        self.sections.iter().flat_map(|s| &s.records)
+    }
+}
+
+fn record(c: &Compound) -> Result<PoiRecord> {
+    let pos = match c.get_int_array("pos") {
+        Some([x, y, z]) => [*x, *y, *z],
+        _ => return Err(Error::MissingField("pos")),
+    };
+
+    Ok(PoiRecord {
+        kind: c
+            .get_str("type")
+            .ok_or(Error::MissingField("type"))?
+            .to_owned(),
+        pos,
+        free_tickets: c.get_i32("free_tickets").unwrap_or_default(),
+    })
+}
+
+/// Read every chunk of a `poi` region, given the region's coordinates.
+pub fn read_region<S: Read + Seek>(
+    region: &mut Region<S>,
+    region_x: isize,
+    region_z: isize,
+) -> Result<Vec<PoiChunk>> {
+    anvil::read_region_roots(region, region_x, region_z, PoiChunk::from_compound)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    IO(std::io::Error),
+    ParseAnvil(anvil::Error),
+    ParseNbt(nbt::Error),
+    MissingField(&'static str),
+    /// A section's name was not its y, or it was not a compound.
+    InvalidSection,
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::IO(err)
+    }
+}
+
+impl From<anvil::Error> for Error {
+    fn from(err: anvil::Error) -> Error {
+        Error::ParseAnvil(err)
+    }
+}
+
+impl From<nbt::Error> for Error {
+    fn from(err: nbt::Error) -> Error {
+        Error::ParseNbt(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tree;
+    use crate::nbt::Tag;
+    use std::io::Cursor;
+
+    fn record(kind: &str, pos: [i32; 3], free_tickets: i32) -> Node {
+        let mut c = Compound::new();
+        c.insert("type", Node::String(kind.to_owned()));
+        c.insert("pos", Node::IntArray(pos.to_vec()));
+        c.insert("free_tickets", Node::Int(free_tickets));
+        Node::Compound(c)
+    }
+
+    fn section(valid: bool, records: Vec<Node>) -> Node {
+        let mut c = Compound::new();
+        c.insert("Valid", Node::Byte(valid as i8));
+        c.insert("Records", Node::List(Tag::Compound, records));
+        Node::Compound(c)
+    }
+
+    #[test]
+    fn read_poi_region() -> Result<()> {
+        let mut sections = Compound::new();
+        sections.insert(
+            "4",
+            section(true, vec![record("minecraft:home", [33, 70, -5], 0)]),
+        );
+        sections.insert(
+            "-1",
+            section(
+                false,
+                vec![
+                    record("minecraft:armorer", [34, -10, -6], 1),
+                    record("minecraft:nether_portal", [35, -12, -6], 0),
+                ],
+            ),
+        );
+
+        let mut root = Compound::new();
+        root.insert("DataVersion", Node::Int(3465));
+        root.insert("Sections", Node::Compound(sections));
+
+        let mut data = vec![];
+        tree::write_root(&mut data, "", &root)?;
+        let mut region = Region::create(Cursor::new(vec![]))?;
+        region.write_chunk(2, 31, &data)?;
+
+        let chunks = read_region(&mut region, 0, -1)?;
+        assert_eq!(1, chunks.len());
+        let chunk = &chunks[0];
+        assert_eq!((2, -1), (chunk.x, chunk.z));
+
+        assert_eq!(
+            vec![-1, 4],
+            chunk.sections.iter().map(|s| s.y).collect::<Vec<_>>()
+        );
+        assert!(!chunk.sections[0].valid);
+
+        let kinds: Vec<_> = chunk.records().map(|r| r.kind.as_str()).collect();
+        assert_eq!(
+            vec![
+                "minecraft:armorer",
+                "minecraft:nether_portal",
+                "minecraft:home"
+            ],
+            kinds
+        );
+        assert_eq!(
+            &PoiRecord {
+                kind: "minecraft:armorer".to_owned(),
+                pos: [34, -10, -6],
+                free_tickets: 1,
+            },
+            chunk.records().next().unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn records_without_type_are_skipped() {
+        let mut broken = Compound::new();
+        broken.insert("pos", Node::IntArray(vec![0, 0, 0]));
+        let mut sections = Compound::new();
+        sections.insert(
+            "0",
+            section(
+                true,
+                vec![
+                    Node::Compound(broken),
+                    record("minecraft:home", [1, 2, 3], 0),
+                ],
+            ),
+        );
+        let mut root = Compound::new();
+        root.insert("Sections", Node::Compound(sections));
+
+        let chunk = PoiChunk::from_compound(&root, 0, 0).unwrap();
+        assert_eq!(1, chunk.skipped);
+        assert_eq!(1, chunk.records().count());
+    }
+
+    #[test]
+    fn bad_section_name_is_an_error() {
+        let mut sections = Compound::new();
+        sections.insert("top", section(true, vec![]));
+        let mut root = Compound::new();
+        root.insert("Sections", Node::Compound(sections));
+
+        assert!(matches!(
+            PoiChunk::from_compound(&root, 0, 0),
+            Err(Error::InvalidSection)
+        ));
+    }
+}
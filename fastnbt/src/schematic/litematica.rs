@@ -0,0 +1,408 @@
+//! Litematica schematics (`.litematic`).
+//!
+//! A schematic is made of named regions, each with its own palette and position relative to the schematic's origin.
+//! Region sizes can be negative, meaning the region extends in the negative direction from its position. Block states
+//! are tightly packed into longs, with values allowed to span two longs.
+
+use super::{Error, Result, Volume};
+use crate::anvil::bits;
+use crate::block::BlockState;
+use crate::nbt::{
+    tree::{self, Compound, Node},
+    Tag,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::io::{Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Schematic version written. Litematica upgrades older versions itself.
+const VERSION: i32 = 4;
+
+/// Data version used when a volume does not have one. Block states are at least as new as the flattening in 1.13.
+const FLATTENING_DATA_VERSION: i32 = 1519;
+
+/// A named region of a Litematica schematic. The volume's offset is the region's minimum corner relative to the
+/// schematic's origin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub name: String,
+    pub volume: Volume,
+}
+
+/// Read the regions of a schematic from GZip compressed data, as found on disk.
+pub fn read<R: Read>(reader: R) -> Result<Vec<Region>> {
+    let (_, root) = tree::read_root(GzDecoder::new(reader))?;
+    from_compound(&root)
+}
+
+pub fn from_compound(root: &Compound) -> Result<Vec<Region>> {
+    let data_version = root.get_i32("MinecraftDataVersion");
+    let regions = root
+        .get_compound("Regions")
+        .ok_or(Error::MissingField("Regions"))?;
+
+    regions
+        .iter()
+        .map(|(name, region)| {
+            let region = region.as_compound().ok_or(Error::MissingField("Regions"))?;
+            let mut volume = read_region(region)?;
+            volume.data_version = data_version;
+
+            Ok(Region {
+                name: name.to_owned(),
+                volume,
+            })
+        })
+        .collect()
+}
+
+/// Write regions as a schematic with the given name, GZip compressed.
+pub fn write<W: Write>(regions: &[Region], name: &str, writer: W) -> Result<()> {
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    tree::write_root(&mut encoder, "", &to_compound(regions, name))?;
+    encoder.finish()?;
+    Ok(())
+}
+
+pub fn to_compound(regions: &[Region], name: &str) -> Compound {
+    let (min, max) = bounds(regions);
+
+    let mut total_blocks = 0;
+    let mut total_volume = 0;
+    let mut regions_c = Compound::new();
+
+    for region in regions {
+        let v = &region.volume;
+        total_volume += v.blocks.len() as i32;
+        total_blocks += v
+            .blocks
+            .iter()
+            .filter(|b| !is_air(&v.palette[**b as usize]))
+            .count() as i32;
+
+        regions_c.insert(region.name.as_str(), Node::Compound(write_region(v)));
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default();
+
+    let mut metadata = Compound::new();
+    metadata.insert("Name", Node::String(name.to_owned()));
+    metadata.insert("Author", Node::String(String::new()));
+    metadata.insert("Description", Node::String(String::new()));
+    metadata.insert("RegionCount", Node::Int(regions.len() as i32));
+    metadata.insert("TotalVolume", Node::Int(total_volume));
+    metadata.insert("TotalBlocks", Node::Int(total_blocks));
+    metadata.insert("TimeCreated", Node::Long(now));
+    metadata.insert("TimeModified", Node::Long(now));
+    metadata.insert(
+        "EnclosingSize",
+        xyz(max.0 - min.0, max.1 - min.1, max.2 - min.2),
+    );
+
+    let data_version = regions
+        .iter()
+        .find_map(|r| r.volume.data_version)
+        .unwrap_or(FLATTENING_DATA_VERSION);
+
+    let mut root = Compound::new();
+    root.insert("Version", Node::Int(VERSION));
+    root.insert("MinecraftDataVersion", Node::Int(data_version));
+    root.insert("Metadata", Node::Compound(metadata));
+    root.insert("Regions", Node::Compound(regions_c));
+    root
+}
+
+/// Merge all regions into a single volume covering all of them. Where regions overlap, later regions win. The
+/// volume's offset is the minimum corner of the regions relative to the schematic's origin.
+pub fn merge(regions: &[Region]) -> Volume {
+    let (min, max) = bounds(regions);
+    let size = (
+        (max.0 - min.0) as usize,
+        (max.1 - min.1) as usize,
+        (max.2 - min.2) as usize,
+    );
+
+    let mut merged = Volume::new(size);
+    merged.offset = min;
+    merged.data_version = regions.iter().find_map(|r| r.volume.data_version);
+
+    for region in regions {
+        let v = &region.volume;
+        let delta = (
+            (v.offset.0 - min.0) as usize,
+            (v.offset.1 - min.1) as usize,
+            (v.offset.2 - min.2) as usize,
+        );
+
+        let indices: Vec<u16> = v.palette.iter().map(|s| merged.palette_index(s)).collect();
+
+        for (x, y, z) in v.yzx() {
+            let i = merged.index(x + delta.0, y + delta.1, z + delta.2);
+            merged.blocks[i] = indices[v.blocks[v.index(x, y, z)] as usize];
+        }
+
+        for (pos, be) in &v.block_entities {
+            merged.block_entities.insert(
+                (pos.0 + delta.0, pos.1 + delta.1, pos.2 + delta.2),
+                be.clone(),
+            );
+        }
+
+        for entity in &v.entities {
+            let mut entity = entity.clone();
+            if let Some(Node::List(Tag::Double, pos)) = entity.get_mut("Pos") {
+                let delta = [delta.0, delta.1, delta.2];
+                for (p, d) in pos.iter_mut().zip(&delta) {
+                    if let Node::Double(p) = p {
+                        *p += *d as f64;
+                    }
+                }
+            }
+            merged.entities.push(entity);
+        }
+    }
+
+    merged
+}
+
+/// The minimum and maximum corners enclosing all regions.
+fn bounds(regions: &[Region]) -> ((i32, i32, i32), (i32, i32, i32)) {
+    if regions.is_empty() {
+        return ((0, 0, 0), (0, 0, 0));
+    }
+
+    let mut min = (i32::MAX, i32::MAX, i32::MAX);
+    let mut max = (i32::MIN, i32::MIN, i32::MIN);
+
+    for region in regions {
+        let (o, s) = (region.volume.offset, region.volume.size);
+        min = (min.0.min(o.0), min.1.min(o.1), min.2.min(o.2));
+        max = (
+            max.0.max(o.0 + s.0 as i32),
+            max.1.max(o.1 + s.1 as i32),
+            max.2.max(o.2 + s.2 as i32),
+        );
+    }
+    (min, max)
+}
+
+fn read_region(region: &Compound) -> Result<Volume> {
+    let position = read_xyz(region, "Position")?;
+    let size = read_xyz(region, "Size")?;
+
+    // A negative size extends from the position in the negative direction, the position itself included.
+    let axis = |pos: i32, size: i32| {
+        if size < 0 {
+            (pos + size + 1, (-size) as usize)
+        } else {
+            (pos, size as usize)
+        }
+    };
+    let (x, sx) = axis(position.0, size.0);
+    let (y, sy) = axis(position.1, size.1);
+    let (z, sz) = axis(position.2, size.2);
+
+    let mut volume = Volume::new((sx, sy, sz));
+    volume.offset = (x, y, z);
+
+    volume.palette = region
+        .get_list("BlockStatePalette")
+        .ok_or(Error::MissingField("BlockStatePalette"))?
+        .iter()
+        .map(|c| {
+            c.as_compound()
+                .and_then(BlockState::from_compound)
+                .ok_or(Error::InvalidPalette)
+        })
+        .collect::<Result<_>>()?;
+
+    if volume.palette.is_empty() {
+        return Err(Error::InvalidPalette);
+    }
+
+    let states = region
+        .get_long_array("BlockStates")
+        .ok_or(Error::MissingField("BlockStates"))?;
+    let indices = bits::expand_generic_1_15(states, bits_per_block(volume.palette.len()));
+    volume.fill_yzx(&indices)?;
+
+    for be in region.get_list("TileEntities").unwrap_or_default() {
+        let mut be = be
+            .as_compound()
+            .ok_or(Error::MissingField("TileEntities"))?
+            .clone();
+
+        let pos = match (be.remove("x"), be.remove("y"), be.remove("z")) {
+            (Some(x), Some(y), Some(z)) => match (x.as_i32(), y.as_i32(), z.as_i32()) {
+                (Some(x), Some(y), Some(z)) if x >= 0 && y >= 0 && z >= 0 => {
+                    (x as usize, y as usize, z as usize)
+                }
+                _ => return Err(Error::MissingField("x")),
+            },
+            _ => return Err(Error::MissingField("x")),
+        };
+        volume.block_entities.insert(pos, be);
+    }
+
+    for entity in region.get_list("Entities").unwrap_or_default() {
+        let entity = entity
+            .as_compound()
+            .ok_or(Error::MissingField("Entities"))?;
+        volume.entities.push(entity.clone());
+    }
+
+    Ok(volume)
+}
+
+fn write_region(v: &Volume) -> Compound {
+    let indices: Vec<u16> = v
+        .yzx()
+        .map(|(x, y, z)| v.blocks[v.index(x, y, z)])
+        .collect();
+
+    let palette = v
+        .palette
+        .iter()
+        .map(|s| Node::Compound(s.to_compound()))
+        .collect();
+
+    let tile_entities = v
+        .block_entities
+        .iter()
+        .map(|(pos, be)| {
+            let mut c = be.clone();
+            c.insert("x", Node::Int(pos.0 as i32));
+            c.insert("y", Node::Int(pos.1 as i32));
+            c.insert("z", Node::Int(pos.2 as i32));
+            Node::Compound(c)
+        })
+        .collect();
+
+    let entities = v
+        .entities
+        .iter()
+        .map(|e| Node::Compound(e.clone()))
+        .collect();
+
+    let mut c = Compound::new();
+    c.insert("Position", xyz(v.offset.0, v.offset.1, v.offset.2));
+    c.insert(
+        "Size",
+        xyz(v.size.0 as i32, v.size.1 as i32, v.size.2 as i32),
+    );
+    c.insert("BlockStatePalette", Node::List(Tag::Compound, palette));
+    c.insert(
+        "BlockStates",
+        Node::LongArray(bits::pack_generic_1_15(
+            &indices,
+            bits_per_block(v.palette.len()),
+        )),
+    );
+    c.insert("TileEntities", Node::List(Tag::Compound, tile_entities));
+    c.insert("Entities", Node::List(Tag::Compound, entities));
+    c.insert("PendingBlockTicks", Node::List(Tag::Compound, vec![]));
+    c.insert("PendingFluidTicks", Node::List(Tag::Compound, vec![]));
+    c
+}
+
+/// Litematica packs at least 2 bits per block.
+fn bits_per_block(palette_len: usize) -> usize {
+    bits::min_bits(palette_len).max(2)
+}
+
+fn read_xyz(c: &Compound, name: &'static str) -> Result<(i32, i32, i32)> {
+    let xyz = c.get_compound(name).ok_or(Error::MissingField(name))?;
+    match (xyz.get_i32("x"), xyz.get_i32("y"), xyz.get_i32("z")) {
+        (Some(x), Some(y), Some(z)) => Ok((x, y, z)),
+        _ => Err(Error::MissingField(name)),
+    }
+}
+
+fn xyz(x: i32, y: i32, z: i32) -> Node {
+    let mut c = Compound::new();
+    c.insert("x", Node::Int(x));
+    c.insert("y", Node::Int(y));
+    c.insert("z", Node::Int(z));
+    Node::Compound(c)
+}
+
+fn is_air(state: &BlockState) -> bool {
+    matches!(
+        state.name.as_str(),
+        "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(name: &str, offset: (i32, i32, i32), size: (usize, usize, usize)) -> Region {
+        let mut volume = Volume::new(size);
+        volume.offset = offset;
+        Region {
+            name: name.to_owned(),
+            volume,
+        }
+    }
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let mut a = region("a", (0, 0, 0), (5, 3, 7));
+        for i in 0..20 {
+            // Enough states that packed values span longs.
+            a.volume.set_block(
+                i % 5,
+                i % 3,
+                i % 7,
+                &BlockState::new(format!("test:b{}", i)),
+            );
+        }
+        let mut b = region("b", (-4, 2, 1), (2, 2, 2));
+        b.volume
+            .set_block(1, 1, 1, &BlockState::new("minecraft:stone"));
+
+        let regions = vec![a, b];
+        let mut buf = vec![];
+        write(&regions, "test", &mut buf)?;
+
+        let mut read = read(buf.as_slice())?;
+        for r in &mut read {
+            r.volume.data_version = None;
+        }
+        assert_eq!(regions, read);
+        Ok(())
+    }
+
+    #[test]
+    fn negative_size() -> Result<()> {
+        let mut c = write_region(&Volume::new((2, 1, 3)));
+        c.insert("Position", xyz(10, 5, 0));
+        c.insert("Size", xyz(-2, 1, -3));
+
+        let v = read_region(&c)?;
+        assert_eq!((9, 5, -2), v.offset);
+        assert_eq!((2, 1, 3), v.size);
+        Ok(())
+    }
+
+    #[test]
+    fn merge_regions() {
+        let mut a = region("a", (0, 0, 0), (2, 1, 1));
+        a.volume
+            .set_block(1, 0, 0, &BlockState::new("minecraft:stone"));
+        let mut b = region("b", (-1, 0, 2), (1, 1, 1));
+        b.volume
+            .set_block(0, 0, 0, &BlockState::new("minecraft:dirt"));
+
+        let merged = merge(&[a, b]);
+        assert_eq!((-1, 0, 0), merged.offset);
+        assert_eq!((3, 1, 3), merged.size);
+        assert_eq!("minecraft:stone", merged.block(2, 0, 0).name);
+        assert_eq!("minecraft:dirt", merged.block(0, 0, 2).name);
+        assert_eq!("minecraft:air", merged.block(0, 0, 0).name);
+    }
+}
@@ -0,0 +1,226 @@
+//! MCEdit schematics (`.schematic`), the format used by MCEdit and WorldEdit before 1.13.
+//!
+//! Blocks are stored as pre-flattening numeric ids and data values, converted with `block::legacy`. Ids above 255 put
+//! their high bits in `AddBlocks`, two blocks to a byte.
+
+use super::{Error, Result, Volume};
+use crate::block::{legacy, BlockState};
+use crate::nbt::{
+    tree::{self, Compound, Node},
+    Tag,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// Read a schematic from GZip compressed data, as found on disk.
+pub fn read<R: Read>(reader: R) -> Result<Volume> {
+    let (_, root) = tree::read_root(GzDecoder::new(reader))?;
+    from_compound(&root)
+}
+
+/// Read a schematic from its root compound. Block ids without a known block state, such as those added by mods, are
+/// read as air.
+pub fn from_compound(root: &Compound) -> Result<Volume> {
+    let size = (
+        dimension(root, "Width")?,
+        dimension(root, "Height")?,
+        dimension(root, "Length")?,
+    );
+
+    let mut volume = Volume::new(size);
+    volume.offset = (
+        root.get_i32("WEOffsetX").unwrap_or_default(),
+        root.get_i32("WEOffsetY").unwrap_or_default(),
+        root.get_i32("WEOffsetZ").unwrap_or_default(),
+    );
+
+    let blocks = root
+        .get_byte_array("Blocks")
+        .ok_or(Error::MissingField("Blocks"))?;
+    let data = root
+        .get_byte_array("Data")
+        .ok_or(Error::MissingField("Data"))?;
+    let add = root.get_byte_array("AddBlocks");
+
+    if blocks.len() < volume.blocks.len() || data.len() < volume.blocks.len() {
+        return Err(Error::InvalidBlockData);
+    }
+
+    let mut lookup: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut indices = Vec::with_capacity(volume.blocks.len());
+
+    for i in 0..volume.blocks.len() {
+        let mut id = blocks[i] as u8 as u16;
+        if let Some(add) = add {
+            let nibbles = *add.get(i >> 1).ok_or(Error::InvalidBlockData)? as u8 as u16;
+            id |= if i & 1 == 0 {
+                (nibbles & 0x0F) << 8
+            } else {
+                (nibbles & 0xF0) << 4
+            };
+        }
+        let key = (id, data[i] as u8 & 0x0F);
+
+        let index = match lookup.get(&key) {
+            Some(index) => *index,
+            None => {
+                let state = legacy::block_state(key.0, key.1)
+                    .unwrap_or_else(|| BlockState::new("minecraft:air"));
+                let index = volume.palette_index(&state);
+                lookup.insert(key, index);
+                index
+            }
+        };
+        indices.push(index);
+    }
+    volume.fill_yzx(&indices)?;
+
+    for be in root.get_list("TileEntities").unwrap_or_default() {
+        let mut be = be
+            .as_compound()
+            .ok_or(Error::MissingField("TileEntities"))?
+            .clone();
+
+        let pos = match (be.remove("x"), be.remove("y"), be.remove("z")) {
+            (Some(x), Some(y), Some(z)) => match (x.as_i32(), y.as_i32(), z.as_i32()) {
+                (Some(x), Some(y), Some(z)) if x >= 0 && y >= 0 && z >= 0 => {
+                    (x as usize, y as usize, z as usize)
+                }
+                _ => return Err(Error::MissingField("x")),
+            },
+            _ => return Err(Error::MissingField("x")),
+        };
+        volume.block_entities.insert(pos, be);
+    }
+
+    for entity in root.get_list("Entities").unwrap_or_default() {
+        let entity = entity
+            .as_compound()
+            .ok_or(Error::MissingField("Entities"))?;
+        volume.entities.push(entity.clone());
+    }
+
+    Ok(volume)
+}
+
+/// Write a schematic GZip compressed.
+pub fn write<W: Write>(volume: &Volume, writer: W) -> Result<()> {
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    tree::write_root(&mut encoder, "Schematic", &to_compound(volume))?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// The root compound for a volume. The format predates the flattening, so block states are reduced to their closest
+/// numeric id, and blocks that did not exist before 1.13 are written as air.
+pub fn to_compound(volume: &Volume) -> Compound {
+    let ids: Vec<(u16, u8)> = volume
+        .palette
+        .iter()
+        .map(|state| legacy::legacy_id(state).unwrap_or((0, 0)))
+        .collect();
+
+    let len = volume.blocks.len();
+    let mut blocks = Vec::with_capacity(len);
+    let mut data = Vec::with_capacity(len);
+    let mut add = vec![0u8; len.div_ceil(2)];
+
+    for (i, (x, y, z)) in volume.yzx().enumerate() {
+        let (id, value) = ids[volume.blocks[volume.index(x, y, z)] as usize];
+        blocks.push(id as u8 as i8);
+        data.push(value as i8);
+
+        let high = ((id >> 8) & 0x0F) as u8;
+        add[i >> 1] |= if i & 1 == 0 { high } else { high << 4 };
+    }
+
+    let tile_entities = volume
+        .block_entities
+        .iter()
+        .map(|(pos, be)| {
+            let mut c = be.clone();
+            c.insert("x", Node::Int(pos.0 as i32));
+            c.insert("y", Node::Int(pos.1 as i32));
+            c.insert("z", Node::Int(pos.2 as i32));
+            Node::Compound(c)
+        })
+        .collect();
+
+    let entities = volume
+        .entities
+        .iter()
+        .map(|e| Node::Compound(e.clone()))
+        .collect();
+
+    let mut c = Compound::new();
+    c.insert("Width", Node::Short(volume.size.0 as i16));
+    c.insert("Height", Node::Short(volume.size.1 as i16));
+    c.insert("Length", Node::Short(volume.size.2 as i16));
+    c.insert("Materials", Node::String("Alpha".to_owned()));
+    c.insert("Blocks", Node::ByteArray(blocks));
+    c.insert("Data", Node::ByteArray(data));
+
+    if add.iter().any(|b| *b != 0) {
+        c.insert(
+            "AddBlocks",
+            Node::ByteArray(add.into_iter().map(|b| b as i8).collect()),
+        );
+    }
+
+    c.insert("TileEntities", Node::List(Tag::Compound, tile_entities));
+    c.insert("Entities", Node::List(Tag::Compound, entities));
+    c.insert("WEOffsetX", Node::Int(volume.offset.0));
+    c.insert("WEOffsetY", Node::Int(volume.offset.1));
+    c.insert("WEOffsetZ", Node::Int(volume.offset.2));
+    c
+}
+
+fn dimension(c: &Compound, name: &'static str) -> Result<usize> {
+    c.get_i16(name)
+        .map(|v| v as u16 as usize)
+        .ok_or(Error::MissingField(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let mut v = Volume::new((4, 3, 2));
+        v.offset = (-2, 0, 1);
+        v.set_block(1, 2, 1, &BlockState::new("minecraft:stone"));
+        v.set_block(3, 0, 0, &BlockState::new("minecraft:chest"));
+
+        let mut chest = Compound::new();
+        chest.insert("id", Node::String("minecraft:chest".to_owned()));
+        v.block_entities.insert((3, 0, 0), chest);
+
+        let mut buf = vec![];
+        write(&v, &mut buf)?;
+        let read = read(buf.as_slice())?;
+
+        assert_eq!(v.size, read.size);
+        assert_eq!(v.offset, read.offset);
+        assert_eq!("minecraft:stone", read.block(1, 2, 1).name);
+        assert_eq!("minecraft:chest", read.block(3, 0, 0).name);
+        assert_eq!("minecraft:air", read.block(0, 0, 0).name);
+        assert_eq!(v.block_entities, read.block_entities);
+        Ok(())
+    }
+
+    #[test]
+    fn add_blocks_nibbles() -> Result<()> {
+        let mut root = to_compound(&Volume::new((2, 1, 1)));
+        // Block 0 gets id 0x101, block 1 keeps id 1.
+        root.insert("Blocks", Node::ByteArray(vec![1, 1]));
+        root.insert("AddBlocks", Node::ByteArray(vec![0x01]));
+
+        let v = from_compound(&root)?;
+        // 257 was never used, so reads as air.
+        assert_eq!("minecraft:air", v.block(0, 0, 0).name);
+        assert_eq!("minecraft:stone", v.block(1, 0, 0).name);
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use crate::block::BlockState;
+use crate::nbt::{self, tree::Compound};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub mod litematica;
+pub mod mcedit;
+pub mod sponge;
+
+pub type Pos = (usize, usize, usize);
+
+/// A box of blocks loaded from any of the schematic formats.
+///
+/// Blocks are stored as an index into a palette of block states for every position, in the same order as
+/// `structure::Structure`. Block entities and entities are kept as the NBT Minecraft itself would use, with positions
+/// relative to the volume.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Volume {
+    /// Size in x, y and z.
+    pub size: Pos,
+    /// Where the volume is meant to be placed relative to some origin. For Sponge and MCEdit schematics this is
+    /// relative to the player pasting it, for Litematica it is relative to the schematic's origin.
+    pub offset: (i32, i32, i32),
+    pub data_version: Option<i32>,
+    pub palette: Vec<BlockState>,
+    /// Palette index of every position, see `Volume::index`.
+    pub blocks: Vec<u16>,
+    /// Block entity data such as chest contents, by position. These do not contain their own position.
+    pub block_entities: BTreeMap<Pos, Compound>,
+    /// Entity data, with `id` and `Pos` relative to the volume.
+    pub entities: Vec<Compound>,
+}
+
+impl Volume {
+    /// Create a volume of the given size filled with air.
+    pub fn new(size: Pos) -> Self {
+        Self {
+            size,
+            offset: (0, 0, 0),
+            data_version: None,
+            palette: vec![BlockState::new("minecraft:air")],
+            blocks: vec![0; size.0 * size.1 * size.2],
+            block_entities: BTreeMap::new(),
+            entities: vec![],
+        }
+    }
+
+    /// Index of a position in `blocks`. This is ordered by y, then x, then z.
+    pub fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        (y * self.size.0 + x) * self.size.2 + z
+    }
+
+    pub fn block(&self, x: usize, y: usize, z: usize) -> &BlockState {
+        &self.palette[self.blocks[self.index(x, y, z)] as usize]
+    }
+
+    /// Set the block at a position, adding it to the palette if needed.
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, state: &BlockState) {
+        let i = self.index(x, y, z);
+        self.blocks[i] = self.palette_index(state);
+    }
+
+    /// Find the palette index of a state, adding it to the palette if needed.
+    pub fn palette_index(&mut self, state: &BlockState) -> u16 {
+        match self.palette.iter().position(|p| p == state) {
+            Some(i) => i as u16,
+            None => {
+                self.palette.push(state.clone());
+                (self.palette.len() - 1) as u16
+            }
+        }
+    }
+
+    /// Iterate over every position in the order the schematic formats store blocks, ie y, then z, then x.
+    pub(crate) fn yzx(&self) -> impl Iterator<Item = Pos> {
+        let (sx, sy, sz) = self.size;
+        (0..sy).flat_map(move |y| (0..sz).flat_map(move |z| (0..sx).map(move |x| (x, y, z))))
+    }
+
+    /// Fill the volume from a list of palette indices in y, z, x order.
+    pub(crate) fn fill_yzx(&mut self, indices: &[u16]) -> Result<()> {
+        if indices.len() < self.blocks.len() {
+            return Err(Error::InvalidBlockData);
+        }
+
+        let positions: Vec<_> = self.yzx().collect();
+        for (pos, block) in positions.into_iter().zip(indices) {
+            if *block as usize >= self.palette.len() {
+                return Err(Error::InvalidPalette);
+            }
+            let i = self.index(pos.0, pos.1, pos.2);
+            self.blocks[i] = *block;
+        }
+        Ok(())
+    }
+}
+
+/// Load a schematic, choosing the format from the file extension: `.schem` for Sponge, `.schematic` for MCEdit and
+/// `.litematic` for Litematica. Litematica regions are merged into a single volume.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Volume> {
+    let path = path.as_ref();
+    let file = std::fs::File::open(path)?;
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("schem") => sponge::read(file),
+        Some("schematic") => mcedit::read(file),
+        Some("litematic") => Ok(litematica::merge(&litematica::read(file)?)),
+        _ => Err(Error::UnknownFormat),
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    IO(std::io::Error),
+    ParseNbt(nbt::Error),
+    MissingField(&'static str),
+    InvalidPalette,
+    InvalidBlockData,
+    UnsupportedVersion(i32),
+    UnknownFormat,
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::IO(err)
+    }
+}
+
+impl From<nbt::Error> for Error {
+    fn from(err: nbt::Error) -> Error {
+        Error::ParseNbt(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_get() {
+        let mut v = Volume::new((3, 2, 4));
+        let stone = BlockState::new("minecraft:stone");
+        v.set_block(2, 1, 3, &stone);
+
+        assert_eq!(&stone, v.block(2, 1, 3));
+        assert_eq!("minecraft:air", v.block(0, 0, 0).name);
+        assert_eq!(2, v.palette.len());
+    }
+
+    #[test]
+    fn fill_in_yzx_order() -> Result<()> {
+        let mut v = Volume::new((2, 1, 2));
+        v.palette_index(&BlockState::new("minecraft:stone"));
+        v.fill_yzx(&[0, 1, 0, 0])?;
+
+        assert_eq!("minecraft:stone", v.block(1, 0, 0).name);
+        assert_eq!("minecraft:air", v.block(0, 0, 1).name);
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+//! Sponge schematics (`.schem`), as used by WorldEdit since 1.13.
+//!
+//! Blocks are stored as a palette of block state strings plus one varint per block. Versions 1 and 2 keep everything
+//! in the root compound, version 3 moves blocks into a `Blocks` compound and wraps entity data in `Data`.
+
+use super::{Error, Result, Volume};
+use crate::block::BlockState;
+use crate::nbt::{
+    tree::{self, Compound, Node},
+    Tag,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::io::{Read, Write};
+
+/// The version of the format to write.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Version {
+    V2,
+    V3,
+}
+
+/// Data version used when a volume does not have one. Block states are at least as new as the flattening in 1.13.
+const FLATTENING_DATA_VERSION: i32 = 1519;
+
+/// Read a schematic from GZip compressed data, as found on disk.
+pub fn read<R: Read>(reader: R) -> Result<Volume> {
+    let (_, root) = tree::read_root(GzDecoder::new(reader))?;
+    from_compound(&root)
+}
+
+pub fn from_compound(root: &Compound) -> Result<Volume> {
+    // Version 3 nests everything in a Schematic compound inside an unnamed root.
+    let schematic = root.get_compound("Schematic").unwrap_or(root);
+
+    let version = schematic
+        .get_i32("Version")
+        .ok_or(Error::MissingField("Version"))?;
+
+    let size = (
+        dimension(schematic, "Width")?,
+        dimension(schematic, "Height")?,
+        dimension(schematic, "Length")?,
+    );
+
+    let mut volume = Volume::new(size);
+    volume.data_version = schematic.get_i32("DataVersion");
+
+    if let Some([x, y, z]) = schematic.get_int_array("Offset") {
+        volume.offset = (*x, *y, *z);
+    }
+
+    let (blocks, block_entities) = match version {
+        1 | 2 => {
+            let block_entities = schematic
+                .get_list("BlockEntities")
+                .or_else(|| schematic.get_list("TileEntities"));
+            (schematic, block_entities)
+        }
+        3 => {
+            let blocks = schematic
+                .get_compound("Blocks")
+                .ok_or(Error::MissingField("Blocks"))?;
+            (blocks, blocks.get_list("BlockEntities"))
+        }
+        v => return Err(Error::UnsupportedVersion(v)),
+    };
+
+    volume.palette = read_palette(
+        blocks
+            .get_compound("Palette")
+            .ok_or(Error::MissingField("Palette"))?,
+    )?;
+
+    let data = match version {
+        3 => blocks.get_byte_array("Data"),
+        _ => blocks.get_byte_array("BlockData"),
+    };
+    let data = data.ok_or(Error::MissingField("BlockData"))?;
+    volume.fill_yzx(&read_varints(data, volume.blocks.len())?)?;
+
+    for be in block_entities.unwrap_or_default() {
+        let be = be
+            .as_compound()
+            .ok_or(Error::MissingField("BlockEntities"))?;
+        let pos = match be.get_int_array("Pos") {
+            Some([x, y, z]) if *x >= 0 && *y >= 0 && *z >= 0 => {
+                (*x as usize, *y as usize, *z as usize)
+            }
+            _ => return Err(Error::MissingField("Pos")),
+        };
+
+        volume.block_entities.insert(pos, unwrap_data(be, version));
+    }
+
+    for entity in schematic.get_list("Entities").unwrap_or_default() {
+        let entity = entity
+            .as_compound()
+            .ok_or(Error::MissingField("Entities"))?;
+        let mut data = unwrap_data(entity, version);
+
+        if let Some(pos) = entity.get("Pos") {
+            data.insert("Pos", pos.clone());
+        }
+        volume.entities.push(data);
+    }
+
+    Ok(volume)
+}
+
+/// Write a schematic GZip compressed, as WorldEdit does.
+pub fn write<W: Write>(volume: &Volume, version: Version, writer: W) -> Result<()> {
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+
+    match version {
+        Version::V2 => tree::write_root(&mut encoder, "Schematic", &to_compound(volume, version))?,
+        Version::V3 => {
+            let mut root = Compound::new();
+            root.insert("Schematic", Node::Compound(to_compound(volume, version)));
+            tree::write_root(&mut encoder, "", &root)?
+        }
+    }
+
+    encoder.finish()?;
+    Ok(())
+}
+
+/// The `Schematic` compound for a volume.
+pub fn to_compound(volume: &Volume, version: Version) -> Compound {
+    let mut palette = Compound::new();
+    for (i, state) in volume.palette.iter().enumerate() {
+        palette.insert(state.to_string(), Node::Int(i as i32));
+    }
+
+    let mut data = vec![];
+    for (x, y, z) in volume.yzx() {
+        write_varint(&mut data, volume.blocks[volume.index(x, y, z)]);
+    }
+
+    let block_entities = volume
+        .block_entities
+        .iter()
+        .map(|(pos, be)| {
+            let mut c = wrap_data(be, version);
+            c.insert(
+                "Pos",
+                Node::IntArray(vec![pos.0 as i32, pos.1 as i32, pos.2 as i32]),
+            );
+            Node::Compound(c)
+        })
+        .collect();
+
+    let entities = volume
+        .entities
+        .iter()
+        .map(|e| {
+            let mut inner = e.clone();
+            let pos = inner.remove("Pos");
+            let mut c = wrap_data(&inner, version);
+            if let Some(pos) = pos {
+                c.insert("Pos", pos);
+            }
+            Node::Compound(c)
+        })
+        .collect();
+
+    let mut c = Compound::new();
+    c.insert(
+        "Version",
+        Node::Int(match version {
+            Version::V2 => 2,
+            Version::V3 => 3,
+        }),
+    );
+    c.insert(
+        "DataVersion",
+        Node::Int(volume.data_version.unwrap_or(FLATTENING_DATA_VERSION)),
+    );
+    c.insert("Width", Node::Short(volume.size.0 as i16));
+    c.insert("Height", Node::Short(volume.size.1 as i16));
+    c.insert("Length", Node::Short(volume.size.2 as i16));
+    c.insert(
+        "Offset",
+        Node::IntArray(vec![volume.offset.0, volume.offset.1, volume.offset.2]),
+    );
+
+    match version {
+        Version::V2 => {
+            c.insert("PaletteMax", Node::Int(volume.palette.len() as i32));
+            c.insert("Palette", Node::Compound(palette));
+            c.insert("BlockData", Node::ByteArray(data));
+            c.insert("BlockEntities", Node::List(Tag::Compound, block_entities));
+        }
+        Version::V3 => {
+            let mut blocks = Compound::new();
+            blocks.insert("Palette", Node::Compound(palette));
+            blocks.insert("Data", Node::ByteArray(data));
+            blocks.insert("BlockEntities", Node::List(Tag::Compound, block_entities));
+            c.insert("Blocks", Node::Compound(blocks));
+        }
+    }
+
+    c.insert("Entities", Node::List(Tag::Compound, entities));
+    c
+}
+
+fn dimension(c: &Compound, name: &'static str) -> Result<usize> {
+    // Stored as shorts, but treated as unsigned.
+    c.get_i16(name)
+        .map(|v| v as u16 as usize)
+        .ok_or(Error::MissingField(name))
+}
+
+fn read_palette(palette: &Compound) -> Result<Vec<BlockState>> {
+    let mut states: Vec<Option<BlockState>> = vec![None; palette.len()];
+
+    for (key, index) in palette.iter() {
+        let state = key.parse().map_err(|_| Error::InvalidPalette)?;
+        match index.as_i32() {
+            Some(i) if i >= 0 && (i as usize) < states.len() => states[i as usize] = Some(state),
+            _ => return Err(Error::InvalidPalette),
+        }
+    }
+
+    states
+        .into_iter()
+        .map(|s| s.ok_or(Error::InvalidPalette))
+        .collect()
+}
+
+fn read_varints(data: &[i8], count: usize) -> Result<Vec<u16>> {
+    let mut values = Vec::with_capacity(count);
+    let mut value = 0u32;
+    let mut shift = 0;
+
+    for b in data {
+        let b = *b as u8;
+        value |= ((b & 0x7f) as u32) << shift;
+
+        if b & 0x80 != 0 {
+            shift += 7;
+            if shift > 21 {
+                return Err(Error::InvalidBlockData);
+            }
+        } else {
+            values.push(value as u16);
+            value = 0;
+            shift = 0;
+        }
+    }
+
+    if values.len() != count || shift != 0 {
+        return Err(Error::InvalidBlockData);
+    }
+    Ok(values)
+}
+
+fn write_varint(out: &mut Vec<i8>, value: u16) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7f == 0 {
+            out.push(value as u8 as i8);
+            return;
+        }
+        out.push(((value & 0x7f) | 0x80) as u8 as i8);
+        value >>= 7;
+    }
+}
+
+/// Turn a schematic (block) entity into Minecraft's form: `Id` becomes `id`, and version 3's `Data` is unwrapped.
+fn unwrap_data(c: &Compound, version: i32) -> Compound {
+    let mut data = match (version, c.get_compound("Data")) {
+        (3, Some(data)) => data.clone(),
+        _ => {
+            let mut data = c.clone();
+            data.remove("Pos");
+            data.remove("Id");
+            data
+        }
+    };
+
+    if let Some(id) = c.get("Id") {
+        data.insert("id", id.clone());
+    }
+    data
+}
+
+fn wrap_data(c: &Compound, version: Version) -> Compound {
+    let mut data = c.clone();
+    let id = data.remove("id");
+
+    let mut wrapped = match version {
+        Version::V2 => data,
+        Version::V3 => {
+            let mut wrapped = Compound::new();
+            wrapped.insert("Data", Node::Compound(data));
+            wrapped
+        }
+    };
+
+    if let Some(id) = id {
+        wrapped.insert("Id", id);
+    }
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Volume {
+        let mut v = Volume::new((3, 2, 300));
+        v.data_version = Some(2586);
+        v.offset = (-1, 0, 5);
+        v.set_block(
+            2,
+            1,
+            299,
+            &"minecraft:oak_stairs[facing=east]".parse().unwrap(),
+        );
+        v.set_block(0, 0, 0, &BlockState::new("minecraft:chest"));
+
+        // Enough states to need two-byte varints.
+        for i in 0..200 {
+            v.palette_index(&BlockState::new(format!("test:block_{}", i)));
+        }
+        v.set_block(1, 1, 1, &BlockState::new("test:block_199"));
+
+        let mut chest = Compound::new();
+        chest.insert("id", Node::String("minecraft:chest".to_owned()));
+        v.block_entities.insert((0, 0, 0), chest);
+        v
+    }
+
+    #[test]
+    fn round_trip_both_versions() -> Result<()> {
+        for version in &[Version::V2, Version::V3] {
+            let v = sample();
+            let mut buf = vec![];
+            write(&v, *version, &mut buf)?;
+
+            assert_eq!(v, read(buf.as_slice())?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn varints() -> Result<()> {
+        let mut out = vec![];
+        for v in &[0, 127, 128, 300, u16::MAX] {
+            write_varint(&mut out, *v);
+        }
+
+        assert_eq!(vec![0, 127, 128, 300, u16::MAX], read_varints(&out, 5)?);
+        assert!(read_varints(&out[..out.len() - 1], 5).is_err());
+        Ok(())
+    }
+}
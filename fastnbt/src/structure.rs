@@ -0,0 +1,739 @@
+use crate::anvil::{self, draw};
+use crate::block::BlockState;
+use crate::entity::{self, Entity};
+use crate::nbt::{
+    self,
+    tree::{self, Compound, Node},
+    Tag,
+};
+use crate::world::{self, ChunkCache};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Positions a structure does not set are filled with this block. When the structure is placed, the world keeps
+/// whatever was there before.
+pub const STRUCTURE_VOID: &str = "minecraft:structure_void";
+
+pub type Pos = (usize, usize, usize);
+
+/// An entity saved in a structure.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureEntity {
+    /// Exact position relative to the structure's origin.
+    pub pos: [f64; 3],
+    /// The block containing `pos`.
+    pub block_pos: [i32; 3],
+    pub nbt: Compound,
+}
+
+/// A structure template, as saved by structure blocks and used by datapacks (`.nbt` files).
+///
+/// Blocks are stored like `anvil::chunk::Section`: a palette of block states, and for every position an index into
+/// that palette.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Structure {
+    pub data_version: Option<i32>,
+    /// Size in x, y and z.
+    pub size: Pos,
+    pub palette: Vec<BlockState>,
+    /// Further palettes using the same indices as `palette`. Minecraft picks one palette at random when placing the
+    /// structure, eg to vary the wood type of shipwrecks.
+    pub alternate_palettes: Vec<Vec<BlockState>>,
+    /// Palette index of every position, see `Structure::index`.
+    pub states: Vec<u16>,
+    /// Block entity data such as chest contents, by position.
+    pub block_entities: BTreeMap<Pos, Compound>,
+    pub entities: Vec<StructureEntity>,
+}
+
+impl Structure {
+    /// Create a structure of the given size filled with structure void.
+    pub fn new(size: Pos) -> Self {
+        Self {
+            data_version: None,
+            size,
+            palette: vec![BlockState::new(STRUCTURE_VOID)],
+            alternate_palettes: vec![],
+            states: vec![0; size.0 * size.1 * size.2],
+            block_entities: BTreeMap::new(),
+            entities: vec![],
+        }
+    }
+
+    /// Read a structure from GZip compressed data, as found on disk.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        let (_, root) = tree::read_root(GzDecoder::new(reader))?;
+        Self::from_compound(&root)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_reader(std::fs::File::open(path)?)
+    }
+
+    pub fn from_compound(root: &Compound) -> Result<Self> {
+        let size = match root.get_list("size") {
+            Some([x, y, z]) => (to_usize(x)?, to_usize(y)?, to_usize(z)?),
+            _ => return Err(Error::MissingField("size")),
+        };
+
+        let mut palettes = match root.get_list("palette") {
+            Some(palette) => vec![read_palette(palette)?],
+            None => root
+                .get_list("palettes")
+                .ok_or(Error::MissingField("palette"))?
+                .iter()
+                .map(|p| read_palette(p.as_list().ok_or(Error::InvalidPalette)?))
+                .collect::<Result<Vec<_>>>()?,
+        };
+
+        if palettes.is_empty() {
+            return Err(Error::MissingField("palette"));
+        }
+        // Alternate palettes are indexed like the first, so each must have an entry for all of its indices.
+        if palettes.iter().any(|p| p.len() < palettes[0].len()) {
+            return Err(Error::InvalidPalette);
+        }
+
+        let mut structure = Structure::new(size);
+        structure.data_version = root.get_i32("DataVersion");
+
+        // Unset positions need an index of their own. One is added if the palette has none, and left out again when
+        // the structure is written.
+        let len = palettes[0].len();
+        let void = match palettes[0].iter().position(|p| p.name == STRUCTURE_VOID) {
+            Some(void) => void,
+            None => {
+                for palette in &mut palettes {
+                    palette.push(BlockState::new(STRUCTURE_VOID));
+                }
+                len
+            }
+        };
+        structure.states.iter_mut().for_each(|s| *s = void as u16);
+
+        for block in root.get_list("blocks").unwrap_or_default() {
+            let block = block.as_compound().ok_or(Error::MissingField("blocks"))?;
+            let pos = read_pos(block.get_list("pos"))?;
+            let state = block.get_i32("state").ok_or(Error::MissingField("state"))?;
+
+            if state < 0 || state as usize >= len {
+                return Err(Error::InvalidPalette);
+            }
+            if pos.0 >= size.0 || pos.1 >= size.1 || pos.2 >= size.2 {
+                return Err(Error::OutOfBounds);
+            }
+
+            let i = structure.index(pos.0, pos.1, pos.2);
+            structure.states[i] = state as u16;
+
+            if let Some(nbt) = block.get_compound("nbt") {
+                structure.block_entities.insert(pos, nbt.clone());
+            }
+        }
+
+        for entity in root.get_list("entities").unwrap_or_default() {
+            let entity = entity
+                .as_compound()
+                .ok_or(Error::MissingField("entities"))?;
+            structure.entities.push(read_entity(entity)?);
+        }
+
+        structure.palette = palettes.remove(0);
+        structure.alternate_palettes = palettes;
+        Ok(structure)
+    }
+
+    /// Capture an area of a dimension, given by its lowest corner in world block coordinates. The area can span
+    /// regions and reach below y 0 in 1.18 worlds. Blocks in chunks that have not been generated, or outside the
+    /// world's height, are captured as air.
+    ///
+    /// Block entities and entities in the area are captured too, whether stored in the chunks or, since 1.17, in the
+    /// dimension's entities region files. Players are not entities of either, so are never captured.
+    pub fn from_world(
+        cache: &mut ChunkCache,
+        min: (isize, isize, isize),
+        size: Pos,
+    ) -> Result<Self> {
+        let mut structure = Structure::new(size);
+        if structure.states.is_empty() {
+            return Ok(structure);
+        }
+        structure.palette.clear();
+
+        let max = (
+            min.0 + size.0 as isize,
+            min.1 + size.1 as isize,
+            min.2 + size.2 as isize,
+        );
+        let air = BlockState::new("minecraft:air");
+
+        for cx in min.0.div_euclid(16)..=(max.0 - 1).div_euclid(16) {
+            for cz in min.2.div_euclid(16)..=(max.2 - 1).div_euclid(16) {
+                let chunk = cache.read_chunk(cx, cz)?;
+
+                for x in (cx * 16).max(min.0)..(cx * 16 + 16).min(max.0) {
+                    for z in (cz * 16).max(min.2)..(cz * 16 + 16).min(max.2) {
+                        for y in min.1..max.1 {
+                            // Chunk::state_of takes the within-chunk z first, as the data is stored z-major.
+                            let state = match &chunk {
+                                Some(chunk) => chunk.state_of(
+                                    z.rem_euclid(16) as usize,
+                                    y,
+                                    x.rem_euclid(16) as usize,
+                                ),
+                                None => &air,
+                            };
+                            structure.set_state(
+                                (x - min.0) as usize,
+                                (y - min.1) as usize,
+                                (z - min.2) as usize,
+                                state,
+                            );
+                        }
+                    }
+                }
+
+                if let Some(chunk) = chunk {
+                    for be in &chunk.block_entities {
+                        let pos = (be.x as isize, be.y as isize, be.z as isize);
+                        if let Some(pos) = relative(pos, min, max) {
+                            // Placing the structure gives block entities their new position.
+                            let mut nbt = be.raw().clone();
+                            for coord in ["x", "y", "z"] {
+                                nbt.remove(coord);
+                            }
+                            structure.block_entities.insert(pos, nbt);
+                        }
+                    }
+                    structure
+                        .entities
+                        .extend(chunk.entities.iter().filter_map(|e| capture(e, min, max)));
+                }
+            }
+        }
+
+        let regions = (
+            min.0.div_euclid(512)..=(max.0 - 1).div_euclid(512),
+            min.2.div_euclid(512)..=(max.2 - 1).div_euclid(512),
+        );
+        for file in cache.dimension().entity_regions()? {
+            if !regions.0.contains(&file.x) || !regions.1.contains(&file.z) {
+                continue;
+            }
+            for chunk in entity::read_region(&mut file.open()?, file.x, file.z)? {
+                structure
+                    .entities
+                    .extend(chunk.entities.iter().filter_map(|e| capture(e, min, max)));
+            }
+        }
+
+        Ok(structure)
+    }
+
+    /// Index of a position in `states`. Like sections, this is ordered by y, then x, then z.
+    pub fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        (y * self.size.0 + x) * self.size.2 + z
+    }
+
+    pub fn id_of(&self, x: usize, y: usize, z: usize) -> &str {
+        &self.state_of(x, y, z).name
+    }
+
+    pub fn state_of(&self, x: usize, y: usize, z: usize) -> &BlockState {
+        &self.palette[self.states[self.index(x, y, z)] as usize]
+    }
+
+    /// Set the block at a position to the default state of a block, see `Structure::set_state`.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, id: &str) {
+        self.set_state(x, y, z, &BlockState::new(id));
+    }
+
+    /// Set the block at a position, adding it to the palette if needed. Alternate palettes get the same block.
+    pub fn set_state(&mut self, x: usize, y: usize, z: usize, block: &BlockState) {
+        let state = match self.palette.iter().position(|p| p == block) {
+            Some(i) => i,
+            None => {
+                self.palette.push(block.clone());
+                for palette in &mut self.alternate_palettes {
+                    palette.push(block.clone());
+                }
+                self.palette.len() - 1
+            }
+        };
+
+        let i = self.index(x, y, z);
+        self.states[i] = state as u16;
+    }
+
+    pub fn to_compound(&self) -> Compound {
+        let void = self.palette.iter().position(|p| p.name == STRUCTURE_VOID);
+        // Void added when reading a palette without one is not written, as no block refers to it.
+        let written = match void {
+            Some(void) if void + 1 == self.palette.len() => void,
+            _ => self.palette.len(),
+        };
+        let mut blocks = vec![];
+
+        for y in 0..self.size.1 {
+            for z in 0..self.size.2 {
+                for x in 0..self.size.0 {
+                    let state = self.states[self.index(x, y, z)];
+                    if Some(state as usize) == void {
+                        continue;
+                    }
+
+                    let mut block = Compound::new();
+                    block.insert("pos", int_list(&[x as i32, y as i32, z as i32]));
+                    block.insert("state", Node::Int(state as i32));
+                    if let Some(nbt) = self.block_entities.get(&(x, y, z)) {
+                        block.insert("nbt", Node::Compound(nbt.clone()));
+                    }
+                    blocks.push(Node::Compound(block));
+                }
+            }
+        }
+
+        let entities = self
+            .entities
+            .iter()
+            .map(|e| {
+                let mut c = Compound::new();
+                c.insert(
+                    "pos",
+                    Node::List(
+                        Tag::Double,
+                        e.pos.iter().map(|v| Node::Double(*v)).collect(),
+                    ),
+                );
+                c.insert("blockPos", int_list(&e.block_pos));
+                c.insert("nbt", Node::Compound(e.nbt.clone()));
+                Node::Compound(c)
+            })
+            .collect();
+
+        let mut root = Compound::new();
+        if let Some(version) = self.data_version {
+            root.insert("DataVersion", Node::Int(version));
+        }
+        root.insert(
+            "size",
+            int_list(&[self.size.0 as i32, self.size.1 as i32, self.size.2 as i32]),
+        );
+
+        if self.alternate_palettes.is_empty() {
+            root.insert("palette", palette_node(&self.palette[..written]));
+        } else {
+            let palettes = std::iter::once(&self.palette)
+                .chain(self.alternate_palettes.iter())
+                .map(|p| palette_node(&p[..written.min(p.len())]))
+                .collect();
+            root.insert("palettes", Node::List(Tag::List, palettes));
+        }
+
+        root.insert("blocks", Node::List(Tag::Compound, blocks));
+        root.insert("entities", Node::List(Tag::Compound, entities));
+        root
+    }
+
+    /// Write the structure GZip compressed, as Minecraft does.
+    pub fn write<W: Write>(&self, writer: W) -> Result<()> {
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        tree::write_root(&mut encoder, "", &self.to_compound())?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.write(std::io::BufWriter::new(file))
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    IO(std::io::Error),
+    ParseNbt(nbt::Error),
+    ParseAnvil(anvil::Error),
+    Draw(draw::DrawError),
+    World(world::Error),
+    Entity(entity::Error),
+    MissingField(&'static str),
+    InvalidPalette,
+    OutOfBounds,
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::IO(err)
+    }
+}
+
+impl From<nbt::Error> for Error {
+    fn from(err: nbt::Error) -> Error {
+        Error::ParseNbt(err)
+    }
+}
+
+impl From<anvil::Error> for Error {
+    fn from(err: anvil::Error) -> Error {
+        Error::ParseAnvil(err)
+    }
+}
+
+impl From<draw::DrawError> for Error {
+    fn from(err: draw::DrawError) -> Error {
+        Error::Draw(err)
+    }
+}
+
+impl From<world::Error> for Error {
+    fn from(err: world::Error) -> Error {
+        Error::World(err)
+    }
+}
+
+impl From<entity::Error> for Error {
+    fn from(err: entity::Error) -> Error {
+        Error::Entity(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+fn read_palette(entries: &[Node]) -> Result<Vec<BlockState>> {
+    entries
+        .iter()
+        .map(|e| {
+            e.as_compound()
+                .and_then(BlockState::from_compound)
+                .ok_or(Error::InvalidPalette)
+        })
+        .collect()
+}
+
+fn palette_node(palette: &[BlockState]) -> Node {
+    let entries = palette
+        .iter()
+        .map(|state| Node::Compound(state.to_compound()))
+        .collect();
+    Node::List(Tag::Compound, entries)
+}
+
+fn read_pos(pos: Option<&[Node]>) -> Result<Pos> {
+    match pos {
+        Some([x, y, z]) => Ok((to_usize(x)?, to_usize(y)?, to_usize(z)?)),
+        _ => Err(Error::MissingField("pos")),
+    }
+}
+
+fn read_entity(entity: &Compound) -> Result<StructureEntity> {
+    let pos = match entity.get_list("pos") {
+        Some([x, y, z]) => [
+            x.as_f64().unwrap_or_default(),
+            y.as_f64().unwrap_or_default(),
+            z.as_f64().unwrap_or_default(),
+        ],
+        _ => return Err(Error::MissingField("pos")),
+    };
+
+    let block_pos = match entity.get_list("blockPos") {
+        Some([x, y, z]) => [
+            x.as_i32().unwrap_or_default(),
+            y.as_i32().unwrap_or_default(),
+            z.as_i32().unwrap_or_default(),
+        ],
+        _ => [
+            pos[0].floor() as i32,
+            pos[1].floor() as i32,
+            pos[2].floor() as i32,
+        ],
+    };
+
+    Ok(StructureEntity {
+        pos,
+        block_pos,
+        nbt: entity.get_compound("nbt").cloned().unwrap_or_default(),
+    })
+}
+
+/// Position relative to `min` of a block in the world, if it is between `min` and `max`.
+fn relative(
+    pos: (isize, isize, isize),
+    min: (isize, isize, isize),
+    max: (isize, isize, isize),
+) -> Option<Pos> {
+    let inside =
+        |v: isize, min: isize, max: isize| (min..max).contains(&v).then(|| (v - min) as usize);
+    Some((
+        inside(pos.0, min.0, max.0)?,
+        inside(pos.1, min.1, max.1)?,
+        inside(pos.2, min.2, max.2)?,
+    ))
+}
+
+/// An entity of the world as a structure entity, if it is between `min` and `max`. Passengers come along within
+/// the entity they ride.
+fn capture(
+    entity: &Entity,
+    min: (isize, isize, isize),
+    max: (isize, isize, isize),
+) -> Option<StructureEntity> {
+    relative(entity.block_pos(), min, max)?;
+
+    let pos = [
+        entity.pos[0] - min.0 as f64,
+        entity.pos[1] - min.1 as f64,
+        entity.pos[2] - min.2 as f64,
+    ];
+    Some(StructureEntity {
+        pos,
+        block_pos: [
+            pos[0].floor() as i32,
+            pos[1].floor() as i32,
+            pos[2].floor() as i32,
+        ],
+        nbt: entity.raw().clone(),
+    })
+}
+
+fn to_usize(n: &Node) -> Result<usize> {
+    match n.as_i32() {
+        Some(v) if v >= 0 => Ok(v as usize),
+        _ => Err(Error::OutOfBounds),
+    }
+}
+
+fn int_list(values: &[i32]) -> Node {
+    Node::List(Tag::Int, values.iter().map(|v| Node::Int(*v)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small() -> Structure {
+        let mut s = Structure::new((2, 3, 4));
+        s.data_version = Some(2586);
+        s.set(0, 0, 0, "minecraft:stone");
+        s.set(1, 2, 3, "minecraft:chest");
+        s.set(1, 1, 1, "minecraft:stone");
+        s.set_state(
+            0,
+            2,
+            0,
+            &"minecraft:oak_stairs[facing=east,half=top]"
+                .parse()
+                .unwrap(),
+        );
+
+        let mut chest = Compound::new();
+        chest.insert("id", Node::String("minecraft:chest".to_owned()));
+        s.block_entities.insert((1, 2, 3), chest);
+        s
+    }
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let s = small();
+        let mut buf = vec![];
+        s.write(&mut buf)?;
+
+        let read = Structure::from_reader(buf.as_slice())?;
+        assert_eq!(s.size, read.size);
+        assert_eq!(Some(2586), read.data_version);
+        assert_eq!("minecraft:chest", read.id_of(1, 2, 3));
+        assert_eq!("minecraft:stone", read.id_of(1, 1, 1));
+        assert_eq!(Some("east"), read.state_of(0, 2, 0).property("facing"));
+        assert_eq!(STRUCTURE_VOID, read.id_of(1, 0, 0));
+        assert_eq!(s.block_entities, read.block_entities);
+        Ok(())
+    }
+
+    #[test]
+    fn void_is_not_written() {
+        let root = small().to_compound();
+        assert_eq!(4, root.get_list("blocks").unwrap().len());
+    }
+
+    #[test]
+    fn alternate_palettes() -> Result<()> {
+        let mut s = small();
+        s.alternate_palettes.push(
+            s.palette
+                .iter()
+                .map(|p| BlockState::new(p.name.replace("stone", "dirt")))
+                .collect(),
+        );
+
+        let read = Structure::from_compound(&s.to_compound())?;
+        let alt = &read.alternate_palettes[0];
+        assert_eq!(
+            "minecraft:dirt",
+            alt[read.states[read.index(0, 0, 0)] as usize].name
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_alternate_palettes() {
+        let mut root = small().to_compound();
+        let palette = root.remove("palette").unwrap();
+
+        let mut short = small().palette;
+        short.pop();
+        for alternate in [Node::Int(0), palette_node(&short)] {
+            root.insert(
+                "palettes",
+                Node::List(Tag::List, vec![palette.clone(), alternate]),
+            );
+            assert!(matches!(
+                Structure::from_compound(&root),
+                Err(Error::InvalidPalette)
+            ));
+        }
+    }
+
+    #[test]
+    fn palette_is_stable() -> Result<()> {
+        let mut root = small().to_compound();
+        // As saved by the game, with no void in the palette.
+        let palette: Vec<_> = small().palette[1..].to_vec();
+        root.insert("palette", palette_node(&palette));
+        let blocks = match root.get_mut("blocks") {
+            Some(Node::List(_, blocks)) => blocks,
+            _ => unreachable!(),
+        };
+        for block in blocks {
+            let block = block.as_compound_mut().unwrap();
+            let state = block.get_i32("state").unwrap();
+            block.insert("state", Node::Int(state - 1));
+        }
+
+        let read = Structure::from_compound(&root)?;
+        assert_eq!(STRUCTURE_VOID, read.id_of(1, 0, 0));
+        assert_eq!(root, read.to_compound());
+
+        let again = Structure::from_compound(&read.to_compound())?;
+        assert_eq!(read.palette, again.palette);
+        assert_eq!(read.states, again.states);
+        Ok(())
+    }
+
+    #[test]
+    fn state_outside_palette_errors() {
+        let mut root = small().to_compound();
+        root.insert(
+            "palette",
+            palette_node(&[BlockState::new("minecraft:stone")]),
+        );
+
+        match Structure::from_compound(&root) {
+            Err(Error::InvalidPalette) => {}
+            o => panic!("should error {:?}", o),
+        }
+
+        // Not wrapped around to a state in the palette.
+        let mut root = small().to_compound();
+        let mut block = Compound::new();
+        block.insert("pos", int_list(&[0, 1, 0]));
+        block.insert("state", Node::Int(65536));
+        root.insert(
+            "blocks",
+            Node::List(Tag::Compound, vec![Node::Compound(block)]),
+        );
+        assert!(matches!(
+            Structure::from_compound(&root),
+            Err(Error::InvalidPalette)
+        ));
+    }
+
+    #[test]
+    fn from_world_across_regions() -> Result<()> {
+        use crate::anvil::chunk::{BlockEntity, Chunk, Section};
+        use crate::anvil::Region;
+        use crate::world::World;
+
+        let dir = std::env::temp_dir().join(format!("fastnbt-structure-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("region"))?;
+        let world = World::open(&dir)?;
+        let overworld = world.overworld();
+
+        // Blocks either side of the border between regions -1 and 0, below y 0.
+        for (cx, block) in [(-1, "minecraft:stone"), (0, "minecraft:dirt")] {
+            let mut chunk = Chunk::new(vec![], vec![Section::new(-4)], None);
+            chunk.x = cx;
+            chunk.set_block(0, -60, cx.rem_euclid(16) as usize, &BlockState::new(block));
+            for x in [cx * 16 - 1, cx] {
+                let mut chest = Compound::new();
+                chest.insert("id", Node::String("minecraft:chest".to_owned()));
+                chest.insert("x", Node::Int(x));
+                chest.insert("y", Node::Int(-60));
+                chest.insert("z", Node::Int(0));
+                chunk.block_entities.push(BlockEntity::from_compound(chest));
+            }
+            let mut data = vec![];
+            tree::write_root(&mut data, "", &chunk.to_compound()?)?;
+
+            let file = std::fs::File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(overworld.region_path(cx.div_euclid(32) as isize, 0))?;
+            let mut region = Region::create(file)?;
+            region.write_chunk(cx.rem_euclid(32) as usize, 0, &data)?;
+        }
+
+        // Since 1.17 entities are in regions of their own.
+        std::fs::create_dir_all(overworld.entities_dir())?;
+        let mut entities = Compound::new();
+        entities.insert("Position", Node::IntArray(vec![-1, 0]));
+        let list = [[-0.5, -59.0, 0.25], [-1.5, -59.0, 0.25]]
+            .iter()
+            .map(|pos| {
+                let mut e = Compound::new();
+                e.insert("id", Node::String("minecraft:pig".to_owned()));
+                e.insert(
+                    "Pos",
+                    Node::List(Tag::Double, pos.iter().map(|v| Node::Double(*v)).collect()),
+                );
+                Node::Compound(e)
+            })
+            .collect();
+        entities.insert("Entities", Node::List(Tag::Compound, list));
+        let mut data = vec![];
+        tree::write_root(&mut data, "", &entities)?;
+        let file = std::fs::File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(overworld.entities_dir().join("r.-1.0.mca"))?;
+        Region::create(file)?.write_chunk(31, 0, &data)?;
+
+        let mut cache = ChunkCache::new(overworld);
+        let s = Structure::from_world(&mut cache, (-1, -60, 0), (2, 2, 1))?;
+        assert_eq!("minecraft:stone", s.id_of(0, 0, 0));
+        assert_eq!("minecraft:dirt", s.id_of(1, 0, 0));
+        assert_eq!("minecraft:air", s.id_of(1, 1, 0));
+
+        // Chests at x -1 and 0 are inside, those at -17 and 15 are not.
+        assert_eq!(
+            vec![(0, 0, 0), (1, 0, 0)],
+            s.block_entities.keys().copied().collect::<Vec<_>>()
+        );
+        let chest = &s.block_entities[&(0, 0, 0)];
+        assert_eq!(Some("minecraft:chest"), chest.get_str("id"));
+        assert!(!chest.contains("x"));
+
+        assert_eq!(1, s.entities.len());
+        assert_eq!([0.5, 1.0, 0.25], s.entities[0].pos);
+        assert_eq!([0, 1, 0], s.entities[0].block_pos);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
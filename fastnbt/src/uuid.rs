@@ -0,0 +1,120 @@
+use crate::nbt::tree::{Compound, Node};
+use std::fmt;
+use std::str::FromStr;
+
+/// A UUID, such as a player's or an entity's.
+///
+/// Minecraft stores these as an int array of 4 since 1.16, and as a pair of longs before that. Player files are named
+/// after the hyphenated form, eg `069a79f4-44e9-4726-a5be-fca90e38aaf5.dat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Uuid(pub u128);
+
+/// Returned when a string is not a valid UUID.
+#[derive(Debug, PartialEq)]
+pub struct InvalidUuid;
+
+impl Uuid {
+    /// Create from the int array format, most significant int first.
+    pub fn from_int_array(ints: &[i32]) -> Option<Self> {
+        if ints.len() != 4 {
+            return None;
+        }
+
+        let mut v = 0u128;
+        for i in ints {
+            v = (v << 32) | (*i as u32 as u128);
+        }
+        Some(Uuid(v))
+    }
+
+    pub fn to_int_array(self) -> [i32; 4] {
+        [
+            (self.0 >> 96) as u32 as i32,
+            (self.0 >> 64) as u32 as i32,
+            (self.0 >> 32) as u32 as i32,
+            self.0 as u32 as i32,
+        ]
+    }
+
+    /// Create from the pre-1.16 pair of longs, eg `UUIDMost` and `UUIDLeast`.
+    pub fn from_most_least(most: i64, least: i64) -> Self {
+        Uuid(((most as u64 as u128) << 64) | least as u64 as u128)
+    }
+
+    pub fn to_most_least(self) -> (i64, i64) {
+        ((self.0 >> 64) as u64 as i64, self.0 as u64 as i64)
+    }
+
+    /// Find a UUID in a compound, trying the int array stored under `name` and then the `<name>Most`/`<name>Least`
+    /// pair used by older versions.
+    pub fn from_compound(compound: &Compound, name: &str) -> Option<Self> {
+        if let Some(uuid) = compound.get_int_array(name).and_then(Uuid::from_int_array) {
+            return Some(uuid);
+        }
+
+        let most = compound.get_i64(&format!("{}Most", name))?;
+        let least = compound.get_i64(&format!("{}Least", name))?;
+        Some(Uuid::from_most_least(most, least))
+    }
+
+    pub fn to_node(self) -> Node {
+        Node::IntArray(self.to_int_array().to_vec())
+    }
+}
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = format!("{:032x}", self.0);
+        write!(
+            f,
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )
+    }
+}
+
+impl FromStr for Uuid {
+    type Err = InvalidUuid;
+
+    /// Parse either the hyphenated or the plain 32 hex digit form.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex: String = s.chars().filter(|c| *c != '-').collect();
+        if hex.len() != 32 || !(s.len() == 32 || s.len() == 36) {
+            return Err(InvalidUuid);
+        }
+
+        u128::from_str_radix(&hex, 16)
+            .map(Uuid)
+            .map_err(|_| InvalidUuid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_and_parse() {
+        let s = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+        let uuid: Uuid = s.parse().unwrap();
+
+        assert_eq!(s, uuid.to_string());
+        assert_eq!(Ok(uuid), "069a79f444e94726a5befca90e38aaf5".parse());
+        assert_eq!(Err(InvalidUuid), "069a79f4-44e9".parse::<Uuid>());
+    }
+
+    #[test]
+    fn int_array_and_long_pair_agree() {
+        let uuid: Uuid = "069a79f4-44e9-4726-a5be-fca90e38aaf5".parse().unwrap();
+        let ints = uuid.to_int_array();
+        let (most, least) = uuid.to_most_least();
+
+        assert_eq!(Some(uuid), Uuid::from_int_array(&ints));
+        assert_eq!(uuid, Uuid::from_most_least(most, least));
+        assert_eq!(0x069a79f4, ints[0]);
+    }
+}
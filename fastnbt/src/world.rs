@@ -0,0 +1,767 @@
+use crate::anvil::biome::Biome;
+use crate::anvil::draw::{self, Chunk, DrawError};
+use crate::anvil::{self, ExternalChunks, Region};
+use crate::block::BlockState;
+use crate::nbt::tree;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// A Minecraft save directory, ie the one containing `level.dat`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct World {
+    path: PathBuf,
+}
+
+/// A dimension of a world. Its directory holds the `region`, `entities` and `poi` directories.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dimension {
+    /// Namespaced id of the dimension, eg `minecraft:the_nether`.
+    pub id: String,
+    pub path: PathBuf,
+}
+
+/// A region file found in a dimension, along with its region coordinates.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RegionFile {
+    pub x: isize,
+    pub z: isize,
+    pub path: PathBuf,
+}
+
+impl RegionFile {
+    /// Open the region for reading. Oversized chunks in external files next to it can be read.
+    pub fn open(&self) -> io::Result<Region<File>> {
+        Ok(self.with_external_chunks(File::open(&self.path)?))
+    }
+
+    /// Open the region for reading and writing chunks back. Oversized chunks are read from and written to external
+    /// files next to it.
+    pub fn open_writable(&self) -> io::Result<Region<File>> {
+        let file = File::options().read(true).write(true).open(&self.path)?;
+        Ok(self.with_external_chunks(file))
+    }
+
+    fn with_external_chunks(&self, file: File) -> Region<File> {
+        let dir = match self.path.parent() {
+            Some(p) if p != Path::new("") => p.to_owned(),
+            _ => PathBuf::from("."),
+        };
+
+        Region::new(file).with_external_chunks(ExternalChunks::new(dir, self.x, self.z))
+    }
+}
+
+impl World {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not a directory", path.display()),
+            ));
+        }
+
+        Ok(Self {
+            path: path.to_owned(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every dimension of the world: the overworld, the nether and the end if they have been visited, and any
+    /// dimensions added by datapacks, stored under `dimensions/<namespace>/<name>`.
+    pub fn dimensions(&self) -> io::Result<Vec<Dimension>> {
+        let mut dims = vec![self.vanilla("minecraft:overworld", "")];
+
+        for (id, dir) in &[
+            ("minecraft:the_nether", "DIM-1"),
+            ("minecraft:the_end", "DIM1"),
+        ] {
+            if self.path.join(dir).is_dir() {
+                dims.push(self.vanilla(id, dir));
+            }
+        }
+
+        let custom = self.path.join("dimensions");
+        if custom.is_dir() {
+            for ns in std::fs::read_dir(&custom)? {
+                let ns = ns?.path();
+                if let Some(namespace) = ns.file_name().and_then(|n| n.to_str()) {
+                    find_dimensions(&ns, namespace, "", &mut dims)?;
+                }
+            }
+        }
+
+        dims.sort_by(|a, b| a.id.cmp(&b.id));
+        dims.dedup_by(|a, b| a.id == b.id);
+        Ok(dims)
+    }
+
+    /// The dimension with the given id. Ids without a namespace are taken to be in `minecraft`, and `nether` and
+    /// `end` are accepted as short forms.
+    pub fn dimension(&self, id: &str) -> io::Result<Option<Dimension>> {
+        let id = match id {
+            "nether" => "minecraft:the_nether".to_owned(),
+            "end" => "minecraft:the_end".to_owned(),
+            id if !id.contains(':') => format!("minecraft:{}", id),
+            id => id.to_owned(),
+        };
+
+        Ok(self.dimensions()?.into_iter().find(|d| d.id == id))
+    }
+
+    /// Compact the block palettes of every chunk in every dimension, see `Dimension::compact_palettes`. Returns the
+    /// number of chunks rewritten.
+    pub fn compact_palettes(&self) -> Result<usize> {
+        let mut rewritten = 0;
+        for dim in self.dimensions()? {
+            rewritten += dim.compact_palettes()?;
+        }
+        Ok(rewritten)
+    }
+
+    pub fn overworld(&self) -> Dimension {
+        self.vanilla("minecraft:overworld", "")
+    }
+
+    fn vanilla(&self, id: &str, dir: &str) -> Dimension {
+        Dimension {
+            id: id.to_owned(),
+            path: self.path.join(dir),
+        }
+    }
+}
+
+/// Add every directory below `dir` holding a `region` directory as a dimension. Names can contain slashes, so this
+/// looks through every level.
+fn find_dimensions(
+    dir: &Path,
+    namespace: &str,
+    name: &str,
+    dims: &mut Vec<Dimension>,
+) -> io::Result<()> {
+    if !name.is_empty() && dir.join("region").is_dir() {
+        dims.push(Dimension {
+            id: format!("{}:{}", namespace, name),
+            path: dir.to_owned(),
+        });
+    }
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let sub = match path.file_name().and_then(|n| n.to_str()) {
+            Some(sub)
+                if path.is_dir() && !matches!(sub, "region" | "entities" | "poi" | "data") =>
+            {
+                sub.to_owned()
+            }
+            _ => continue,
+        };
+
+        let name = if name.is_empty() {
+            sub
+        } else {
+            format!("{}/{}", name, sub)
+        };
+        find_dimensions(&path, namespace, &name, dims)?;
+    }
+
+    Ok(())
+}
+
+impl Dimension {
+    pub fn region_dir(&self) -> PathBuf {
+        self.path.join("region")
+    }
+
+    /// Every region file of the dimension, sorted by coordinates. A dimension never visited has none.
+    pub fn regions(&self) -> io::Result<Vec<RegionFile>> {
+        list_regions(self.region_dir())
+    }
+
+    /// Compact the block palettes of every chunk in the dimension and drop sections that are entirely air, see
+    /// `Chunk::compact`. Only chunks that change are rewritten, and chunks from before 1.13 are skipped. Returns the
+    /// number of chunks rewritten.
+    ///
+    /// The world must not be open in the game while this runs.
+    pub fn compact_palettes(&self) -> Result<usize> {
+        let mut rewritten = 0;
+        for file in self.regions()? {
+            rewritten += compact_region_palettes(&mut file.open_writable()?)?;
+        }
+        Ok(rewritten)
+    }
+
+    /// Directory of the entities region files, used since 1.17. Read them with `entity::read_region`.
+    pub fn entities_dir(&self) -> PathBuf {
+        self.path.join("entities")
+    }
+
+    pub fn entity_regions(&self) -> io::Result<Vec<RegionFile>> {
+        list_regions(self.entities_dir())
+    }
+
+    /// Directory of the points of interest region files. Read them with `poi::read_region`.
+    pub fn poi_dir(&self) -> PathBuf {
+        self.path.join("poi")
+    }
+
+    pub fn poi_regions(&self) -> io::Result<Vec<RegionFile>> {
+        list_regions(self.poi_dir())
+    }
+
+    /// Path of the region file with the given region coordinates, whether it exists or not.
+    pub fn region_path(&self, x: isize, z: isize) -> PathBuf {
+        self.region_dir().join(format!("r.{}.{}.mca", x, z))
+    }
+
+    /// Open a region for reading, or `None` if it does not exist. Oversized chunks in external files can be read.
+    pub fn open_region(&self, x: isize, z: isize) -> io::Result<Option<Region<std::fs::File>>> {
+        let path = self.region_path(x, z);
+
+        match std::fs::File::open(&path) {
+            Ok(file) => {
+                Ok(Some(Region::new(file).with_external_chunks(
+                    ExternalChunks::new(self.region_dir(), x, z),
+                )))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The region file holding a chunk, and the chunk's place within it.
+    pub fn locate_chunk(&self, cx: isize, cz: isize) -> (PathBuf, (usize, usize)) {
+        let ((rx, rz), slot) = region_of_chunk(cx, cz);
+        (self.region_path(rx, rz), slot)
+    }
+
+    /// The region file holding a block, and the place of the block's chunk within it.
+    pub fn locate_block(&self, x: isize, z: isize) -> (PathBuf, (usize, usize)) {
+        let (cx, cz) = chunk_of_block(x, z);
+        self.locate_chunk(cx, cz)
+    }
+}
+
+/// Every `r.<x>.<z>.mca` file in a directory, sorted by coordinates.
+pub fn list_regions<P: AsRef<Path>>(dir: P) -> io::Result<Vec<RegionFile>> {
+    let dir = dir.as_ref();
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut regions = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some((x, z)) = region_coords(&path) {
+            regions.push(RegionFile { x, z, path });
+        }
+    }
+
+    regions.sort();
+    Ok(regions)
+}
+
+/// Region coordinates from a region file name, eg `r.-1.2.mca`.
+pub fn region_coords<P: AsRef<Path>>(path: P) -> Option<(isize, isize)> {
+    let name = path.as_ref().file_name()?.to_str()?;
+
+    let mut parts = name.split('.');
+    match (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) {
+        (Some("r"), Some(x), Some(z), Some("mca"), None) => {
+            Some((x.parse().ok()?, z.parse().ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// The chunk containing a block.
+pub fn chunk_of_block(x: isize, z: isize) -> (isize, isize) {
+    (x.div_euclid(16), z.div_euclid(16))
+}
+
+/// The region containing a chunk, and the chunk's place within it.
+pub fn region_of_chunk(cx: isize, cz: isize) -> ((isize, isize), (usize, usize)) {
+    (
+        (cx.div_euclid(32), cz.div_euclid(32)),
+        (cx.rem_euclid(32) as usize, cz.rem_euclid(32) as usize),
+    )
+}
+
+/// Compact the block palettes of every chunk in a region, see `Chunk::compact`. Changed chunks are written back with
+/// the compression they were stored with. Returns the number of chunks rewritten.
+///
+/// Chunks from before 1.13 are left alone. Their palettes hold an entry for every id and data value, which can't all
+/// be told apart once read, so compacting them would gain nothing and risk losing blocks.
+pub fn compact_region_palettes<S: Read + Write + Seek>(region: &mut Region<S>) -> Result<usize> {
+    let mut changed = vec![];
+
+    let mut chunks = region.iter_chunks()?;
+    while let Some(chunk) = chunks.next() {
+        let chunk = chunk?;
+        // Read in full, as it is written back.
+        let (_, root) =
+            tree::read_root(anvil::decompress_chunk(chunk.data())?).map_err(DrawError::from)?;
+        let mut parsed = Chunk::from_compound(root)?;
+        if !parsed.is_legacy() && parsed.compact() {
+            let mut data = vec![];
+            tree::write_root(&mut data, "", &parsed.to_compound()?).map_err(DrawError::from)?;
+            changed.push((
+                chunk.location.x,
+                chunk.location.z,
+                chunk.compression_scheme,
+                data,
+            ));
+        }
+    }
+
+    for (x, z, scheme, data) in &changed {
+        region.write_chunk_with(*x, *z, *scheme, data)?;
+    }
+    Ok(changed.len())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    IO(io::Error),
+    Anvil(anvil::Error),
+    ParseChunk(DrawError),
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::IO(err)
+    }
+}
+
+impl From<anvil::Error> for Error {
+    fn from(err: anvil::Error) -> Error {
+        Error::Anvil(err)
+    }
+}
+
+impl From<DrawError> for Error {
+    fn from(err: DrawError) -> Error {
+        Error::ParseChunk(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Looks up blocks and biomes of a dimension by absolute coordinates, keeping the most recently used chunks and
+/// region files around so neighbouring lookups are cheap.
+///
+/// ```no_run
+/// # use fastnbt::world::{ChunkCache, World};
+/// let world = World::open("world").unwrap();
+/// let mut cache = ChunkCache::new(world.overworld());
+///
+/// // Walk down from the top of a 1.18 world until something solid is found.
+/// let ground = (-64..320).rev().find(|&y| match cache.block_at(-200, y, 35).unwrap() {
+///     Some(id) => id != "minecraft:air",
+///     None => false,
+/// });
+/// ```
+pub struct ChunkCache {
+    dimension: Dimension,
+    regions: Lru<(isize, isize), Option<Region<File>>>,
+    chunks: Lru<(isize, isize), Option<Chunk>>,
+    height: Option<(isize, usize)>,
+}
+
+impl ChunkCache {
+    /// Cache 256 chunks and 16 region files.
+    pub fn new(dimension: Dimension) -> Self {
+        Self::with_capacity(dimension, 256, 16)
+    }
+
+    /// Cache up to `chunks` decoded chunks and keep up to `regions` region files open. Both must be at least one.
+    pub fn with_capacity(dimension: Dimension, chunks: usize, regions: usize) -> Self {
+        Self {
+            dimension,
+            regions: Lru::new(regions),
+            chunks: Lru::new(chunks),
+            height: None,
+        }
+    }
+
+    /// Give the height of the dimension, from the `min_y` and `height` of its dimension type. Without this the
+    /// height is worked out from each chunk, see `Chunk::set_height`.
+    pub fn with_height(mut self, min_y: isize, height: usize) -> Self {
+        self.height = Some((min_y, height));
+        self
+    }
+
+    pub fn dimension(&self) -> &Dimension {
+        &self.dimension
+    }
+
+    /// The chunk at the given chunk coordinates, or `None` if it has not been generated. Chunks still being
+    /// generated hold the blocks placed so far.
+    pub fn chunk(&mut self, cx: isize, cz: isize) -> Result<Option<&Chunk>> {
+        if !self.chunks.contains(&(cx, cz)) {
+            let chunk = self.load_chunk(cx, cz, false)?;
+            self.chunks.insert((cx, cz), chunk);
+        }
+
+        Ok(self.chunks.get(&(cx, cz)).and_then(|c| c.as_ref()))
+    }
+
+    /// The chunk at the given chunk coordinates read in full, including its block entities and any entities stored
+    /// in it, or `None` if it has not been generated. Unlike `ChunkCache::chunk` it is read again on every call.
+    pub fn read_chunk(&mut self, cx: isize, cz: isize) -> Result<Option<Chunk>> {
+        self.load_chunk(cx, cz, true)
+    }
+
+    /// Id of the block at the given block coordinates, eg `minecraft:stone`, or `None` if the chunk holding it has
+    /// not been generated or `y` is outside the world.
+    pub fn block_at(&mut self, x: isize, y: isize, z: isize) -> Result<Option<&str>> {
+        Ok(self.state_at(x, y, z)?.map(|s| s.name.as_str()))
+    }
+
+    /// The full block state at the given block coordinates, including properties such as `waterlogged`. `None` in
+    /// the same cases as `ChunkCache::block_at`.
+    pub fn state_at(&mut self, x: isize, y: isize, z: isize) -> Result<Option<&BlockState>> {
+        let (cx, cz) = chunk_of_block(x, z);
+        let (x, z) = (x.rem_euclid(16) as usize, z.rem_euclid(16) as usize);
+
+        // Chunk indexes blocks x-major, whereas they are stored z-major, so its x is really z.
+        Ok(self
+            .chunk(cx, cz)?
+            .filter(|c| (c.min_y()..c.max_y()).contains(&y))
+            .map(|c| c.state_of(z, y, x)))
+    }
+
+    /// The biome at the given block coordinates, or `None` if the chunk holding it has not been generated or the
+    /// biome is unknown.
+    pub fn biome_at(&mut self, x: isize, y: isize, z: isize) -> Result<Option<Biome>> {
+        let (cx, cz) = chunk_of_block(x, z);
+        let (x, z) = (x.rem_euclid(16) as usize, z.rem_euclid(16) as usize);
+
+        Ok(self.chunk(cx, cz)?.and_then(|c| c.biome_of(z, y, x)))
+    }
+
+    fn load_chunk(&mut self, cx: isize, cz: isize, full: bool) -> Result<Option<Chunk>> {
+        let ((rx, rz), (x, z)) = region_of_chunk(cx, cz);
+
+        if !self.regions.contains(&(rx, rz)) {
+            let region = self.dimension.open_region(rx, rz)?;
+            self.regions.insert((rx, rz), region);
+        }
+
+        let region = match self.regions.get_mut(&(rx, rz)) {
+            Some(Some(region)) => region,
+            _ => return Ok(None),
+        };
+
+        let loc = region.chunk_location(x, z)?;
+        if loc.sector_count == 0 {
+            return Ok(None);
+        }
+
+        let data = region.load_chunk_data(&loc)?;
+        let mut chunk = if full {
+            let (_, root) =
+                tree::read_root(anvil::decompress_chunk(&data)?).map_err(DrawError::from)?;
+            Chunk::from_compound(root)?
+        } else {
+            draw::parse_chunk(&data)?
+        };
+        if let Some((min_y, height)) = self.height {
+            chunk.set_height(min_y, height);
+        }
+        Ok(Some(chunk))
+    }
+}
+
+/// A map holding at most `capacity` entries, evicting the least recently used. Capacities are small, so finding the
+/// entry to evict by scanning is fine.
+struct Lru<K, V> {
+    entries: HashMap<K, (u64, V)>,
+    capacity: usize,
+    tick: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> Lru<K, V> {
+    fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "cache capacity must be at least one");
+        Self {
+            entries: HashMap::with_capacity(capacity),
+            capacity,
+            tick: 0,
+        }
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        self.get_mut(key).map(|v| &*v)
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.tick += 1;
+        let tick = self.tick;
+
+        self.entries.get_mut(key).map(|(used, v)| {
+            *used = tick;
+            v
+        })
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.tick += 1;
+        self.entries.insert(key, (self.tick, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tree::{Compound, Node};
+    use crate::nbt::Tag;
+
+    fn temp_world(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fastnbt-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// A 1.16 chunk of air with a stone block at 1, 18, 3 within it, and a plains biome in the corner at positive x.
+    fn chunk_with_stone() -> Vec<u8> {
+        let block = |name: &str| {
+            let mut c = Compound::new();
+            c.insert("Name", Node::String(name.to_owned()));
+            Node::Compound(c)
+        };
+
+        // Four bits per block in the 1.16 format, so 16 to a long.
+        let mut states = vec![0i64; 256];
+        let index = 2 * 256 + 3 * 16 + 1;
+        states[index / 16] = 1 << ((index % 16) * 4);
+
+        let mut section = Compound::new();
+        section.insert("Y", Node::Byte(1));
+        section.insert(
+            "Palette",
+            Node::List(
+                Tag::Compound,
+                vec![block("minecraft:air"), block("minecraft:stone")],
+            ),
+        );
+        section.insert("BlockStates", Node::LongArray(states));
+
+        let mut heightmaps = Compound::new();
+        heightmaps.insert("WORLD_SURFACE", Node::LongArray(vec![0; 37]));
+
+        let mut biomes = vec![Biome::Ocean.into(); 1024];
+        biomes[3] = Biome::Plains.into();
+
+        let mut level = Compound::new();
+        level.insert(
+            "Sections",
+            Node::List(Tag::Compound, vec![Node::Compound(section)]),
+        );
+        level.insert("Heightmaps", Node::Compound(heightmaps));
+        level.insert("Biomes", Node::IntArray(biomes));
+
+        let mut root = Compound::new();
+        root.insert("DataVersion", Node::Int(2586));
+        root.insert("Level", Node::Compound(level));
+
+        let mut buf = vec![];
+        tree::write_root(&mut buf, "", &root).unwrap();
+        buf
+    }
+
+    #[test]
+    fn cache_evicts_least_recently_used() {
+        let mut lru = Lru::new(2);
+        lru.insert(1, "a");
+        lru.insert(2, "b");
+        lru.get(&1);
+        lru.insert(3, "c");
+
+        assert!(lru.contains(&1));
+        assert!(!lru.contains(&2));
+        assert!(lru.contains(&3));
+    }
+
+    #[test]
+    fn block_and_biome_at_negative_coordinates() -> Result<()> {
+        let dir = temp_world("blocks");
+        std::fs::create_dir_all(dir.join("region"))?;
+
+        let world = World::open(&dir)?;
+        let overworld = world.overworld();
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(overworld.region_path(-1, 0))?;
+        // Chunk -1, 0 is the last in region -1, 0 along x.
+        Region::create(file)?.write_chunk(31, 0, &chunk_with_stone())?;
+
+        let mut cache = ChunkCache::with_capacity(overworld, 1, 1);
+        assert_eq!(Some("minecraft:stone"), cache.block_at(-15, 18, 3)?);
+        assert_eq!(Some("minecraft:air"), cache.block_at(-15, 18, 4)?);
+        assert_eq!(Some("minecraft:air"), cache.block_at(-1, 100, 0)?);
+        assert_eq!(None, cache.block_at(-15, -1, 3)?);
+
+        // Neither the chunk nor its region exist.
+        assert_eq!(None, cache.block_at(0, 18, 3)?);
+        assert_eq!(None, cache.block_at(-600, 18, 3)?);
+        // Evicted for the missing chunks, and loaded again.
+        assert_eq!(Some("minecraft:stone"), cache.block_at(-15, 18, 3)?);
+
+        assert!(matches!(cache.biome_at(-1, 64, 0)?, Some(Biome::Plains)));
+        assert!(matches!(cache.biome_at(-16, 64, 0)?, Some(Biome::Ocean)));
+        assert!(cache.biome_at(16, 64, 0)?.is_none());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn compact_region() -> Result<()> {
+        let (_, root) = tree::read_root(&chunk_with_stone()[..]).map_err(DrawError::from)?;
+        let mut emptied = Chunk::from_compound(root)?;
+        emptied.set_block(3, 18, 1, &BlockState::new("minecraft:air"));
+        let mut data = vec![];
+        tree::write_root(&mut data, "", &emptied.to_compound()?).map_err(DrawError::from)?;
+
+        let mut region = Region::create(io::Cursor::new(vec![]))?;
+        region.write_chunk(0, 0, &chunk_with_stone())?;
+        region.write_chunk(1, 0, &data)?;
+
+        assert_eq!(1, compact_region_palettes(&mut region)?);
+        assert_eq!(0, compact_region_palettes(&mut region)?);
+
+        let load = |region: &mut Region<_>, x, z| -> Result<Chunk> {
+            let loc = region.chunk_location(x, z)?;
+            Ok(draw::parse_chunk(&region.load_chunk_data(&loc)?)?)
+        };
+        assert!(load(&mut region, 1, 0)?.section(1).is_none());
+        assert_eq!("minecraft:stone", load(&mut region, 0, 0)?.id_of(3, 18, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn compact_region_skips_legacy_chunks() -> Result<()> {
+        // Stone with data values that don't exist read as plain stone, and torches with their facing in the data.
+        let mut blocks = vec![1i8; 4096];
+        blocks[..16].fill(50);
+        let data = (0..2048).map(|i| (i % 16) as i8 | 9 << 4).collect();
+
+        let mut section = Compound::new();
+        section.insert("Y", Node::Byte(0));
+        section.insert("Blocks", Node::ByteArray(blocks));
+        section.insert("Data", Node::ByteArray(data));
+
+        let mut level = Compound::new();
+        level.insert(
+            "Sections",
+            Node::List(Tag::Compound, vec![Node::Compound(section)]),
+        );
+        let mut root = Compound::new();
+        root.insert("Level", Node::Compound(level));
+        let mut chunk = vec![];
+        tree::write_root(&mut chunk, "", &root).map_err(DrawError::from)?;
+
+        let mut region = Region::create(io::Cursor::new(vec![]))?;
+        region.write_chunk(0, 0, &chunk)?;
+        let loc = region.chunk_location(0, 0)?;
+        let before = region.load_chunk_data(&loc)?;
+
+        assert_eq!(0, compact_region_palettes(&mut region)?);
+        let loc = region.chunk_location(0, 0)?;
+        assert_eq!(before, region.load_chunk_data(&loc)?);
+        Ok(())
+    }
+
+    #[test]
+    fn coordinates() {
+        assert_eq!((0, 0), chunk_of_block(15, 0));
+        assert_eq!((-1, 1), chunk_of_block(-1, 16));
+        assert_eq!(((0, -1), (5, 31)), region_of_chunk(5, -1));
+        assert_eq!(((-2, 1), (31, 0)), region_of_chunk(-33, 32));
+
+        assert_eq!(Some((-1, 20)), region_coords("world/region/r.-1.20.mca"));
+        assert_eq!(None, region_coords("r.1.1.mca.tmp"));
+        assert_eq!(None, region_coords("c.1.1.mcc"));
+    }
+
+    #[test]
+    fn dimensions_and_regions() -> io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("fastnbt-world-{}", std::process::id()));
+        for sub in &[
+            "region",
+            "DIM-1/region",
+            "dimensions/mypack/sky/region",
+            "dimensions/mypack/deep/caves/region",
+            "dimensions/mypack/deep/caves/entities",
+        ] {
+            std::fs::create_dir_all(dir.join(sub))?;
+        }
+        std::fs::write(dir.join("region/r.-1.0.mca"), b"")?;
+        std::fs::write(dir.join("region/r.0.0.mca"), b"")?;
+        std::fs::write(dir.join("region/notes.txt"), b"")?;
+
+        let world = World::open(&dir)?;
+        let ids: Vec<_> = world.dimensions()?.into_iter().map(|d| d.id).collect();
+        assert_eq!(
+            vec![
+                "minecraft:overworld",
+                "minecraft:the_nether",
+                "mypack:deep/caves",
+                "mypack:sky",
+            ],
+            ids
+        );
+
+        let nether = world.dimension("nether")?.unwrap();
+        assert_eq!(dir.join("DIM-1"), nether.path);
+        assert!(world.dimension("end")?.is_none());
+        assert!(nether.regions()?.is_empty());
+
+        let overworld = world.overworld();
+        let regions: Vec<_> = overworld
+            .regions()?
+            .into_iter()
+            .map(|r| (r.x, r.z))
+            .collect();
+        assert_eq!(vec![(-1, 0), (0, 0)], regions);
+
+        let (path, slot) = overworld.locate_block(-1, 17);
+        assert_eq!(overworld.region_path(-1, 0), path);
+        assert_eq!((31, 1), slot);
+        assert!(overworld.open_region(5, 5)?.is_none());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
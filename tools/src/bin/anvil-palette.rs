@@ -16,10 +16,10 @@ fn avg_colour(path: &Path) -> Result<[u8; 3]> {
     for p in img.pixels() {
         // alpha is reasonable.
         if p.0[3] > 128 {
-            avg[0] = avg[0] + ((p.0[0] as u64) * (p.0[0] as u64)) as f64;
-            avg[1] = avg[1] + ((p.0[1] as u64) * (p.0[1] as u64)) as f64;
-            avg[2] = avg[2] + ((p.0[2] as u64) * (p.0[2] as u64)) as f64;
-            count = count + 1;
+            avg[0] += ((p.0[0] as u64) * (p.0[0] as u64)) as f64;
+            avg[1] += ((p.0[1] as u64) * (p.0[1] as u64)) as f64;
+            avg[2] += ((p.0[2] as u64) * (p.0[2] as u64)) as f64;
+            count += 1;
         }
     }
 
@@ -33,9 +33,6 @@ fn avg_colour(path: &Path) -> Result<[u8; 3]> {
 #[derive(Deserialize, Debug)]
 struct Variant {
     model: String,
-    x: Option<usize>,
-    y: Option<usize>,
-    uvlock: Option<bool>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -48,18 +45,10 @@ enum Variants {
 #[derive(Deserialize, Debug)]
 struct Blockstate {
     variants: Option<HashMap<String, Variants>>,
-    multipart: Option<Vec<Part>>,
-}
-
-#[derive(Deserialize, Debug)]
-struct Part {
-    when: Option<serde_json::Value>,
-    apply: Variants,
 }
 
 #[derive(Deserialize, Debug)]
 struct Model {
-    parent: Option<String>,
     textures: Option<HashMap<String, String>>,
 }
 
@@ -187,7 +176,7 @@ fn find_model_in_variant(var: &Variant, models: &HashMap<String, Model>) -> Resu
 
 fn find_texture(state: &Blockstate, models: &HashMap<String, Model>) -> Result<String> {
     if let Some(ref v) = state.variants {
-        for (_vname, variant) in v {
+        if let Some(variant) = v.values().next() {
             return match variant {
                 Variants::Single(ref var) => find_model_in_variant(var, models),
                 Variants::Many(vars) => find_model_in_variant(&vars[0], models),
@@ -224,15 +213,15 @@ fn main() -> Result<()> {
         }
     }
 
-
     let mut palette = HashMap::new();
 
     for (id, tex) in &textured_blocks {
-        
         // // This is sometimes minecraft:block/blah, sometimes block/blah.
-        let col = textures.get(tex).or_else(|| textures.get(&("minecraft:".to_owned() + &tex)));
+        let col = textures
+            .get(tex)
+            .or_else(|| textures.get(&("minecraft:".to_owned() + tex)));
 
-         match col {        
+        match col {
             Some(c) => {
                 palette.insert(id, c);
             }
@@ -1,8 +1,12 @@
 use clap::{App, Arg, ArgMatches, SubCommand};
 use fastnbt::anvil::biome::Biome;
 use fastnbt::anvil::draw::{parse_region, Chunk, RegionDrawer, RegionMap, Rgb};
-use fastnbt::anvil::Region;
-use image;
+use fastnbt::anvil::{ExternalChunks, Region};
+use fastnbt::entity;
+use fastnbt::map::{self, Map, MAP_SIZE};
+use fastnbt::player::{self, Player};
+use fastnbt::poi;
+use fastnbt::world::{Dimension, RegionFile, World};
 use rayon::prelude::*;
 use std::path::{Path, PathBuf};
 
@@ -39,15 +43,12 @@ impl<'a, P: BlockPalette + ?Sized> RegionDrawer for RegionBlockDrawer<'a, P> {
 
         for z in 0..16 {
             for x in 0..16 {
-                let height = chunk.height_of(x, z);
-                let height = if height == 0 { 0 } else { height - 1 }; // -1 because we want the block below the air.
+                let height = chunk.height_of(x, z) - 1; // -1 because we want the block below the air.
                 let material = chunk.id_of(x, height, z);
                 let biome = chunk.biome_of(x, height, z);
 
                 // TODO: If material is grass block (and others), we need to colour it based on biome.
-                let colour = match material {
-                    _ => self.palette.pick(material, biome),
-                };
+                let colour = self.palette.pick(material, biome);
 
                 let pixel = &mut data[x * 16 + z];
                 *pixel = colour;
@@ -65,8 +66,8 @@ impl FullPalette {
     fn pick_grass(&self, b: Option<Biome>) -> [u8; 3] {
         b.map(|b| {
             let climate = fastnbt::anvil::biome::climate(b);
-            let t = climate.temperature.min(1.).max(0.);
-            let r = climate.rainfall.min(1.).max(0.) * t;
+            let t = climate.temperature.clamp(0., 1.);
+            let r = climate.rainfall.clamp(0., 1.) * t;
 
             let t = 255 - (t * 255.).ceil() as u32;
             let r = 255 - (r * 255.).ceil() as u32;
@@ -79,8 +80,8 @@ impl FullPalette {
     fn pick_foliage(&self, b: Option<Biome>) -> [u8; 3] {
         b.map(|b| {
             let climate = fastnbt::anvil::biome::climate(b);
-            let t = climate.temperature.min(1.).max(0.);
-            let r = climate.rainfall.min(1.).max(0.) * t;
+            let t = climate.temperature.clamp(0., 1.);
+            let r = climate.rainfall.clamp(0., 1.) * t;
 
             let t = 255 - (t * 255.).ceil() as u32;
             let r = 255 - (r * 255.).ceil() as u32;
@@ -194,34 +195,30 @@ fn parse_coord(coord: &str) -> Option<(isize, isize)> {
     Some((x, z))
 }
 
-/// Get all the paths to region files in a 'region' directory like 'region', 'DIM1' and 'DIM-1'.
-fn region_paths(in_path: &Path) -> Result<Vec<PathBuf>> {
-    let paths = std::fs::read_dir(in_path)?;
-
-    let paths = paths
-        .into_iter()
-        .filter_map(|path| path.ok())
-        .map(|path| path.path())
-        .filter(|path| path.is_file())
-        .filter(|path| {
-            let ext = path.extension();
-            ext.is_some() && ext.unwrap() == "mca"
-        })
-        .collect();
+/// The world and dimension given on the command line, eg `overworld`, `nether` or `mypack:sky`.
+fn open_dimension(args: &ArgMatches) -> Result<(World, Dimension)> {
+    let world = World::open(args.value_of("world").unwrap())?;
+    let dim = args.value_of("dimension").unwrap();
 
-    Ok(paths)
+    let dimension = world
+        .dimension(dim)?
+        .ok_or(format!("no {} dimension in world", dim))?;
+    Ok((world, dimension))
 }
 
-fn coords_from_region(region: &Path) -> Option<(isize, isize)> {
-    let filename = region.file_name()?.to_str()?;
-    let mut parts = filename.split('.').skip(1);
-    let x = parts.next()?.parse::<isize>().ok()?;
-    let z = parts.next()?.parse::<isize>().ok()?;
-    Some((x, z))
+/// Like `open_dimension`, along with the dimension's region files.
+fn open_regions(args: &ArgMatches) -> Result<(World, Dimension, Vec<RegionFile>)> {
+    let (world, dimension) = open_dimension(args)?;
+    let regions = dimension.regions()?;
+
+    if regions.is_empty() {
+        return Err(format!("no region files found for {} dimension", dimension.id).into());
+    }
+    Ok((world, dimension, regions))
 }
 
-fn auto_size(paths: &Vec<PathBuf>) -> Option<Rectangle> {
-    if paths.len() == 0 {
+fn auto_size(regions: &[RegionFile]) -> Option<Rectangle> {
+    if regions.is_empty() {
         return None;
     }
 
@@ -232,12 +229,11 @@ fn auto_size(paths: &Vec<PathBuf>) -> Option<Rectangle> {
         zmax: isize::MIN,
     };
 
-    for path in paths {
-        let coord = coords_from_region(path)?;
-        bounds.xmin = std::cmp::min(bounds.xmin, coord.0);
-        bounds.xmax = std::cmp::max(bounds.xmax, coord.0);
-        bounds.zmin = std::cmp::min(bounds.zmin, coord.1);
-        bounds.zmax = std::cmp::max(bounds.zmax, coord.1);
+    for region in regions {
+        bounds.xmin = std::cmp::min(bounds.xmin, region.x);
+        bounds.xmax = std::cmp::max(bounds.xmax, region.x);
+        bounds.zmin = std::cmp::min(bounds.zmin, region.z);
+        bounds.zmax = std::cmp::max(bounds.zmax, region.z);
     }
 
     Some(bounds)
@@ -310,23 +306,13 @@ fn get_palette(path: Option<&str>) -> Result<Box<dyn BlockPalette + Sync + Send>
 }
 
 fn render(args: &ArgMatches) -> Result<()> {
-    let world: PathBuf = args.value_of("world").unwrap().parse().unwrap();
-    let dim: &str = args.value_of("dimension").unwrap();
-
-    let subpath = match dim {
-        "end" => "DIM1/region",
-        "nether" => "DIM-1/region",
-        _ => "region",
-    };
-
-    let paths = region_paths(&world.join(subpath))
-        .or(Err(format!("no region files found for {} dimension", dim)))?;
+    let (world, dimension, regions) = open_regions(args)?;
 
     let bounds = match (args.value_of("size"), args.value_of("offset")) {
         (Some(size), Some(offset)) => {
             make_bounds(parse_coord(size).unwrap(), parse_coord(offset).unwrap())
         }
-        (None, _) => auto_size(&paths).unwrap(),
+        (None, _) => auto_size(&regions).unwrap(),
         _ => panic!(),
     };
 
@@ -342,15 +328,17 @@ fn render(args: &ArgMatches) -> Result<()> {
     let pal: std::sync::Arc<dyn BlockPalette + Send + Sync> =
         get_palette(args.value_of("palette"))?.into();
 
-    let region_maps: Vec<Option<RegionMap<Rgb>>> = paths
+    let region_maps: Vec<Option<RegionMap<Rgb>>> = regions
         .into_par_iter()
-        .map(|path| {
-            let (x, z) = coords_from_region(&path).unwrap();
-
+        .map(|RegionFile { x, z, path }| {
             if x < x_range.end && x >= x_range.start && z < z_range.end && z >= z_range.start {
                 println!("parsing region x: {}, z: {}", x, z);
-                let file = std::fs::File::open(path).ok()?;
-                let region = Region::new(file);
+                let file = std::fs::File::open(&path).ok()?;
+                let region = Region::new(file).with_external_chunks(ExternalChunks::new(
+                    path.parent()?,
+                    x,
+                    z,
+                ));
 
                 let map = RegionMap::new(x, z, [0, 0, 0]);
                 let mut drawer = RegionBlockDrawer::new(map, &*pal);
@@ -366,52 +354,319 @@ fn render(args: &ArgMatches) -> Result<()> {
     println!("writing map.png");
     let mut img = image::ImageBuffer::new((dx * region_len) as u32, (dz * region_len) as u32);
 
-    for region_map in region_maps {
-        if let Some(map) = region_map {
-            let xrp = map.x_region - x_range.start;
-            let zrp = map.z_region - z_range.start;
-
-            for xc in 0..32 {
-                for zc in 0..32 {
-                    let heightmap = map.chunk(xc, zc);
-                    let xcp = xrp * 32 + xc as isize;
-                    let zcp = zrp * 32 + zc as isize;
-
-                    for z in 0..16 {
-                        for x in 0..16 {
-                            let pixel = heightmap[z * 16 + x];
-                            let x = xcp * 16 + x as isize;
-                            let z = zcp * 16 + z as isize;
-                            img.put_pixel(x as u32, z as u32, image::Rgb(pixel))
-                        }
+    for map in region_maps.into_iter().flatten() {
+        let xrp = map.x_region - x_range.start;
+        let zrp = map.z_region - z_range.start;
+
+        for xc in 0..32 {
+            for zc in 0..32 {
+                let heightmap = map.chunk(xc, zc);
+                let xcp = xrp * 32 + xc as isize;
+                let zcp = zrp * 32 + zc as isize;
+
+                for z in 0..16 {
+                    for x in 0..16 {
+                        let pixel = heightmap[z * 16 + x];
+                        let x = xcp * 16 + x as isize;
+                        let z = zcp * 16 + z as isize;
+                        img.put_pixel(x as u32, z as u32, image::Rgb(pixel))
                     }
                 }
             }
         }
     }
 
+    if args.is_present("players") {
+        for player in load_players(world.path())? {
+            if player.dimension != dimension.id {
+                continue;
+            }
+
+            let (x, _, z) = player.block_pos();
+            let x = x - x_range.start * region_len as isize;
+            let z = z - z_range.start * region_len as isize;
+            draw_marker(&mut img, x, z);
+        }
+    }
+
     img.save("map.png").unwrap();
     Ok(())
 }
 
-fn biomes(args: &ArgMatches) -> Result<()> {
+fn load_players(world: &Path) -> Result<Vec<Player>> {
+    let mut players = vec![];
+
+    for path in player::player_paths(world).map_err(|e| format!("{:?}", e))? {
+        match Player::open(&path) {
+            Ok(p) => players.push(p),
+            Err(e) => eprintln!("could not read {}: {:?}", path.display(), e),
+        }
+    }
+
+    Ok(players)
+}
+
+/// Draw a small red cross centred on the given pixel, clipped to the image.
+fn draw_marker(img: &mut image::RgbImage, x: isize, z: isize) {
+    for d in -3..=3 {
+        for (px, pz) in [(x + d, z), (x, z + d)] {
+            if px >= 0 && pz >= 0 && (px as u32) < img.width() && (pz as u32) < img.height() {
+                img.put_pixel(px as u32, pz as u32, image::Rgb([255, 0, 0]));
+            }
+        }
+    }
+}
+
+fn players(args: &ArgMatches) -> Result<()> {
     let world: PathBuf = args.value_of("world").unwrap().parse().unwrap();
-    let dim: &str = args.value_of("dimension").unwrap();
 
-    let subpath = match dim {
-        "end" => "DIM1/region",
-        "nether" => "DIM-1/region",
-        _ => "region",
+    for player in load_players(&world)? {
+        let uuid = player
+            .uuid
+            .map(|u| u.to_string())
+            .unwrap_or_else(|| "unknown".to_owned());
+        let (x, y, z) = player.block_pos();
+
+        println!(
+            "{} {} {},{},{} health: {} level: {} mode: {:?}",
+            uuid, player.dimension, x, y, z, player.health, player.xp_level, player.game_mode
+        );
+
+        for item in &player.inventory {
+            let slot = item.slot.unwrap_or_default();
+            match item.display_name() {
+                Some(name) => {
+                    println!("    slot {:>3}: {} x{} {}", slot, item.id, item.count, name)
+                }
+                None => println!("    slot {:>3}: {} x{}", slot, item.id, item.count),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Count the entities of a dimension by id, including passengers.
+fn entities(args: &ArgMatches) -> Result<()> {
+    let (_, dimension) = open_dimension(args)?;
+    let mut counts = std::collections::BTreeMap::<String, usize>::new();
+    let mut skipped = 0;
+
+    for file in dimension.entity_regions()? {
+        let chunks = match entity::read_region(&mut file.open()?, file.x, file.z) {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                eprintln!("could not read {}: {:?}", file.path.display(), e);
+                continue;
+            }
+        };
+
+        for e in chunks.iter().flat_map(|c| c.all_entities()) {
+            *counts.entry(e.id.clone()).or_default() += 1;
+        }
+        skipped += chunks.iter().map(|c| c.skipped).sum::<usize>();
+    }
+
+    for (id, count) in counts {
+        println!("{:>8} {}", count, id);
+    }
+    if skipped > 0 {
+        eprintln!("{} entities without an id were skipped", skipped);
+    }
+
+    Ok(())
+}
+
+/// List the points of interest of a dimension, optionally only those of one type, eg `minecraft:home` for beds.
+fn poi(args: &ArgMatches) -> Result<()> {
+    let (_, dimension) = open_dimension(args)?;
+    let kind = args.value_of("type");
+    let mut skipped = 0;
+
+    for file in dimension.poi_regions()? {
+        let chunks = match poi::read_region(&mut file.open()?, file.x, file.z) {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                eprintln!("could not read {}: {:?}", file.path.display(), e);
+                continue;
+            }
+        };
+
+        for r in chunks.iter().flat_map(|c| c.records()) {
+            if kind.is_some_and(|k| k != r.kind) {
+                continue;
+            }
+
+            let [x, y, z] = r.pos;
+            println!("{} {},{},{} free: {}", r.kind, x, y, z, r.free_tickets);
+        }
+        skipped += chunks.iter().map(|c| c.skipped).sum::<usize>();
+    }
+
+    if skipped > 0 {
+        eprintln!(
+            "{} points of interest without a type or position were skipped",
+            skipped
+        );
+    }
+
+    Ok(())
+}
+
+/// Save every map item in a world as `map_<id>.png` in the current directory.
+fn maps(args: &ArgMatches) -> Result<()> {
+    let world: PathBuf = args.value_of("world").unwrap().parse().unwrap();
+
+    for (id, path) in map::map_paths(&world).map_err(|e| format!("{:?}", e))? {
+        let map = match Map::open(&path) {
+            Ok(map) => map,
+            Err(e) => {
+                eprintln!("could not read {}: {:?}", path.display(), e);
+                continue;
+            }
+        };
+
+        let pixels: Vec<u8> = map.to_rgba().iter().flatten().copied().collect();
+        let img = image::RgbaImage::from_raw(MAP_SIZE as u32, MAP_SIZE as u32, pixels).unwrap();
+        img.save(format!("map_{}.png", id))?;
+
+        println!(
+            "map_{}.png: {} centred on {},{} scale {}",
+            id, map.dimension, map.x_center, map.z_center, map.scale
+        );
+    }
+
+    Ok(())
+}
+
+/// Turn an image into a map file, scaling it to fit.
+fn map_art(args: &ArgMatches) -> Result<()> {
+    let img = image::open(args.value_of("image").unwrap())?
+        .resize_exact(
+            MAP_SIZE as u32,
+            MAP_SIZE as u32,
+            image::imageops::FilterType::Triangle,
+        )
+        .into_rgba();
+
+    let pixels: Vec<[u8; 4]> = img.pixels().map(|p| p.0).collect();
+    let map =
+        Map::from_rgba(&pixels, !args.is_present("creative")).map_err(|e| format!("{:?}", e))?;
+    map.save(args.value_of("output").unwrap())
+        .map_err(|e| format!("{:?}", e))?;
+
+    Ok(())
+}
+
+/// Rewrite region files with their chunks packed together, replacing each file once its copy is complete.
+fn compact(args: &ArgMatches) -> Result<()> {
+    let level = args.value_of("level").map(|l| l.parse()).transpose()?;
+    let mut total = 0;
+
+    for path in args.values_of("regions").unwrap() {
+        let path = Path::new(path);
+        let tmp = path.with_extension("mca.tmp");
+
+        let mut region = Region::new(std::fs::File::open(path)?);
+        let mut write = || -> Result<_> {
+            let mut out = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+            let stats = region
+                .compact_into(&mut out, level)
+                .map_err(|e| format!("{:?}", e))?;
+            // Only replace the region once the copy is known to be on disk.
+            out.get_ref().sync_all()?;
+            Ok(stats)
+        };
+        let stats = match write() {
+            Ok(stats) => stats,
+            Err(e) => {
+                let _ = std::fs::remove_file(&tmp);
+                return Err(format!("{}: {}", path.display(), e).into());
+            }
+        };
+        std::fs::rename(&tmp, path)?;
+
+        println!(
+            "{}: {} chunks, {} bytes reclaimed",
+            path.display(),
+            stats.chunks,
+            stats.bytes_reclaimed()
+        );
+        total += stats.bytes_reclaimed();
+    }
+
+    println!("{} bytes reclaimed in total", total);
+    Ok(())
+}
+
+/// Compact the block palettes of a world's chunks and drop sections of air, in one dimension or all of them.
+fn compact_palettes(args: &ArgMatches) -> Result<()> {
+    let world = World::open(args.value_of("world").unwrap())?;
+    let dimensions = match args.value_of("dimension") {
+        Some(dim) => vec![world
+            .dimension(dim)?
+            .ok_or(format!("no {} dimension in world", dim))?],
+        None => world.dimensions()?,
     };
 
-    let paths = region_paths(&world.join(subpath))
-        .or(Err(format!("no region files found for {} dimension", dim)))?;
+    for dimension in dimensions {
+        let rewritten = dimension
+            .compact_palettes()
+            .map_err(|e| format!("{}: {:?}", dimension.id, e))?;
+        println!("{}: {} chunks rewritten", dimension.id, rewritten);
+    }
+
+    Ok(())
+}
+
+/// Check region files for problems, fixing them if asked to.
+fn check(args: &ArgMatches) -> Result<()> {
+    let repair = args.is_present("repair");
+
+    for path in args.values_of("regions").unwrap() {
+        let path = Path::new(path);
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(repair)
+            .open(path)?;
+
+        let mut region = Region::new(file);
+        if let Some(external) = ExternalChunks::for_region_file(path) {
+            region = region.with_external_chunks(external);
+        }
+
+        let report = if repair {
+            region.repair()
+        } else {
+            region.check()
+        }
+        .map_err(|e| format!("{}: {:?}", path.display(), e))?;
+
+        println!(
+            "{}: {} chunks, {} problems",
+            path.display(),
+            report.chunks,
+            report.problems.len()
+        );
+        for p in &report.problems {
+            println!("    chunk {},{}: {:?}", p.x, p.z, p.problem);
+        }
+        for r in &report.repairs {
+            println!("    chunk {},{}: {:?}", r.x, r.z, r.fix);
+        }
+    }
+
+    Ok(())
+}
+
+fn biomes(args: &ArgMatches) -> Result<()> {
+    let (_, _, regions) = open_regions(args)?;
 
     let bounds = match (args.value_of("size"), args.value_of("offset")) {
         (Some(size), Some(offset)) => {
             make_bounds(parse_coord(size).unwrap(), parse_coord(offset).unwrap())
         }
-        (None, _) => auto_size(&paths).unwrap(),
+        (None, _) => auto_size(&regions).unwrap(),
         _ => panic!(),
     };
 
@@ -424,15 +679,17 @@ fn biomes(args: &ArgMatches) -> Result<()> {
     let dx = x_range.len();
     let dz = z_range.len();
 
-    let region_maps: Vec<Option<RegionMap<Rgb>>> = paths
+    let region_maps: Vec<Option<RegionMap<Rgb>>> = regions
         .into_par_iter()
-        .map(|path| {
-            let (x, z) = coords_from_region(&path).unwrap();
-
+        .map(|RegionFile { x, z, path }| {
             if x < x_range.end && x >= x_range.start && z < z_range.end && z >= z_range.start {
                 println!("parsing region x: {}, z: {}", x, z);
-                let file = std::fs::File::open(path).ok()?;
-                let region = Region::new(file);
+                let file = std::fs::File::open(&path).ok()?;
+                let region = Region::new(file).with_external_chunks(ExternalChunks::new(
+                    path.parent()?,
+                    x,
+                    z,
+                ));
 
                 let mut map = RegionMap::new(x, z, [0, 0, 0]);
                 let mut drawer = RegionBiomeDrawer { map: &mut map };
@@ -448,24 +705,22 @@ fn biomes(args: &ArgMatches) -> Result<()> {
     println!("writing biome.png");
     let mut img = image::ImageBuffer::new((dx * region_len) as u32, (dz * region_len) as u32);
 
-    for region_map in region_maps {
-        if let Some(map) = region_map {
-            let xrp = map.x_region - x_range.start;
-            let zrp = map.z_region - z_range.start;
-
-            for xc in 0..32 {
-                for zc in 0..32 {
-                    let heightmap = map.chunk(xc, zc);
-                    let xcp = xrp * 32 + xc as isize;
-                    let zcp = zrp * 32 + zc as isize;
-
-                    for z in 0..16 {
-                        for x in 0..16 {
-                            let pixel = heightmap[z * 16 + x];
-                            let x = xcp * 16 + x as isize;
-                            let z = zcp * 16 + z as isize;
-                            img.put_pixel(x as u32, z as u32, image::Rgb(pixel))
-                        }
+    for map in region_maps.into_iter().flatten() {
+        let xrp = map.x_region - x_range.start;
+        let zrp = map.z_region - z_range.start;
+
+        for xc in 0..32 {
+            for zc in 0..32 {
+                let heightmap = map.chunk(xc, zc);
+                let xcp = xrp * 32 + xc as isize;
+                let zcp = zrp * 32 + zc as isize;
+
+                for z in 0..16 {
+                    for x in 0..16 {
+                        let pixel = heightmap[z * 16 + x];
+                        let x = xcp * 16 + x as isize;
+                        let z = zcp * 16 + z as isize;
+                        img.put_pixel(x as u32, z as u32, image::Rgb(pixel))
                     }
                 }
             }
@@ -506,6 +761,99 @@ fn main() -> Result<()> {
                         .long("palette")
                         .takes_value(true)
                         .required(false),
+                )
+                .arg(
+                    Arg::with_name("players")
+                        .long("players")
+                        .takes_value(false)
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("players")
+                .arg(Arg::with_name("world").takes_value(true).required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("entities")
+                .arg(Arg::with_name("world").takes_value(true).required(true))
+                .arg(
+                    Arg::with_name("dimension")
+                        .long("dimension")
+                        .takes_value(true)
+                        .required(false)
+                        .default_value("overworld"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("poi")
+                .arg(Arg::with_name("world").takes_value(true).required(true))
+                .arg(
+                    Arg::with_name("dimension")
+                        .long("dimension")
+                        .takes_value(true)
+                        .required(false)
+                        .default_value("overworld"),
+                )
+                .arg(
+                    Arg::with_name("type")
+                        .long("type")
+                        .takes_value(true)
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("maps")
+                .arg(Arg::with_name("world").takes_value(true).required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("map-art")
+                .arg(Arg::with_name("image").takes_value(true).required(true))
+                .arg(Arg::with_name("output").takes_value(true).required(true))
+                .arg(
+                    Arg::with_name("creative")
+                        .long("creative")
+                        .takes_value(false)
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compact")
+                .arg(
+                    Arg::with_name("regions")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("level")
+                        .long("level")
+                        .takes_value(true)
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compact-palettes")
+                .arg(Arg::with_name("world").takes_value(true).required(true))
+                .arg(
+                    Arg::with_name("dimension")
+                        .long("dimension")
+                        .takes_value(true)
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .arg(
+                    Arg::with_name("regions")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("repair")
+                        .long("repair")
+                        .takes_value(false)
+                        .required(false),
                 ),
         )
         .subcommand(
@@ -537,6 +885,14 @@ fn main() -> Result<()> {
     match matches.subcommand() {
         ("render", Some(args)) => render(args)?,
         ("biomes", Some(args)) => biomes(args)?,
+        ("players", Some(args)) => players(args)?,
+        ("entities", Some(args)) => entities(args)?,
+        ("poi", Some(args)) => poi(args)?,
+        ("maps", Some(args)) => maps(args)?,
+        ("map-art", Some(args)) => map_art(args)?,
+        ("compact", Some(args)) => compact(args)?,
+        ("compact-palettes", Some(args)) => compact_palettes(args)?,
+        ("check", Some(args)) => check(args)?,
         _ => println!("{}", matches.usage()),
     };
 